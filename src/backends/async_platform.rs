@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context,Poll};
+
+use futures_core::Stream;
+use log::error;
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc;
+
+use crate::definitions::*;
+use super::Platform;
+
+/// Number of request batches that can be queued before `RequestSink::send` waits.
+const REQUEST_QUEUE_SIZE: usize = 64;
+
+/**
+Tokio adapter for the platform.
+It yields the platform events as a `Stream` and forwards the requests queued through its `RequestSink`s,
+so it must be created from within a tokio runtime with IO enabled.
+*/
+pub struct AsyncPlatform {
+    platform: AsyncFd<Platform>,
+    buffered_events: VecDeque<Event>,
+    sender: mpsc::Sender<Vec<Request>>,
+    receiver: mpsc::Receiver<Vec<Request>>,
+}
+impl AsyncPlatform {
+    pub fn new(platform: Platform)->std::io::Result<Self> {
+        let platform = AsyncFd::new(platform)?;
+        let (sender,receiver) = mpsc::channel(REQUEST_QUEUE_SIZE);
+        Ok(Self {
+            platform,
+            buffered_events: VecDeque::new(),
+            sender,
+            receiver,
+        })
+    }
+
+    /// Create a sink to send requests from other tasks.
+    pub fn sink(&self)->RequestSink {
+        RequestSink {sender: self.sender.clone()}
+    }
+
+    pub fn platform(&self)->&Platform {self.platform.get_ref()}
+    pub fn platform_mut(&mut self)->&mut Platform {self.platform.get_mut()}
+    pub fn into_platform(self)->Platform {self.platform.into_inner()}
+
+    /// Wait for the next event.
+    pub fn next_event(&mut self)->NextEvent<'_> {
+        NextEvent {platform: self}
+    }
+}
+
+impl Stream for AsyncPlatform {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>)->Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.buffered_events.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+
+            // The receiver can't be closed, since this adapter holds a sender too.
+            while let Poll::Ready(Some(requests)) = this.receiver.poll_recv(cx) {
+                this.platform.get_mut().requests(requests);
+            }
+
+            let events = this.platform.get_mut().events();
+            if !events.is_empty() {
+                this.buffered_events.extend(events);
+                continue;
+            }
+
+            match this.platform.poll_read_ready_mut(cx) {
+                Poll::Ready(Ok(mut guard))=>guard.clear_ready(),
+                Poll::Ready(Err(err))=>{
+                    error!("Failed to poll the platform: {}",err);
+                    return Poll::Ready(None);
+                }
+                Poll::Pending=>return Poll::Pending
+            }
+        }
+    }
+}
+
+/// Future returned by `AsyncPlatform::next_event`.
+pub struct NextEvent<'a> {
+    platform: &'a mut AsyncPlatform
+}
+impl<'a> Future for NextEvent<'a> {
+    type Output = Option<Event>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>)->Poll<Self::Output> {
+        Pin::new(&mut *self.platform).poll_next(cx)
+    }
+}
+
+#[derive(Clone)]
+/// Cloneable handle to send requests to an `AsyncPlatform` from any task.
+pub struct RequestSink {
+    sender: mpsc::Sender<Vec<Request>>
+}
+impl RequestSink {
+    /// Queue the requests, waiting if the queue is full.
+    pub async fn send(&self, requests: Vec<Request>)->Result<(),PlatformError> {
+        self.sender.send(requests).await.map_err(|_|PlatformError::Connection(String::from("The platform has been dropped")))
+    }
+}
@@ -0,0 +1,120 @@
+use crate::definitions::*;
+use super::{Backend,Platform};
+
+/// Environment variable used to override the backend selection.
+pub const BACKEND_ENV_VAR: &str = "PAL_BACKEND";
+
+/**
+Builder allowing to choose how the platform is created.
+By default the backends are tried in the order wayland, xcb, libinput (skipping the ones not compiled in),
+the virtual backend has to be requested explicitly.
+The `PAL_BACKEND` environment variable, containing a comma separated list of backend names, overrides it.
+*/
+pub struct PlatformBuilder {
+    backends: Option<Vec<BackendKind>>,
+    env_override: bool,
+    external_contexts: Vec<Box<dyn ExternalContext>>
+}
+impl PlatformBuilder {
+    pub fn new()->Self {
+        Self {
+            backends: None,
+            env_override: true,
+            external_contexts: Vec::new()
+        }
+    }
+
+    /// Force a single backend.
+    pub fn backend(self, backend: BackendKind)->Self {
+        self.backends(vec![backend])
+    }
+
+    /// Set the backends to try, in order of preference.
+    pub fn backends(mut self, backends: Vec<BackendKind>)->Self {
+        self.backends = Some(backends);
+        self
+    }
+
+    /// Enable or disable the `PAL_BACKEND` environment variable override. Enabled by default.
+    pub fn env_override(mut self, enabled: bool)->Self {
+        self.env_override = enabled;
+        self
+    }
+
+    /// Add a context used to create the surfaces.
+    pub fn external_context(mut self, external_context: Box<dyn ExternalContext>)->Self {
+        self.external_contexts.push(external_context);
+        self
+    }
+
+    /// Add multiple contexts used to create the surfaces.
+    pub fn external_contexts(mut self, mut external_contexts: Vec<Box<dyn ExternalContext>>)->Self {
+        self.external_contexts.append(&mut external_contexts);
+        self
+    }
+
+    /**
+    Create the platform with the first backend that initialize successfully.
+    On failure, the reason of every attempted backend is reported.
+    */
+    pub fn build(self)->Result<Platform,PlatformBuildError> {
+        let backends = match self.env_backends()? {
+            Some(backends)=>backends,
+            None=>self.backends.clone().unwrap_or_else(Self::default_backends)
+        };
+
+        let mut external_contexts = Some(self.external_contexts);
+        let mut failures = Vec::new();
+        for backend in backends {
+            match Self::try_backend(backend,&mut external_contexts).and_then(Platform::from_backend) {
+                Ok(platform)=>return Ok(platform),
+                Err(reason)=>failures.push(BackendFailure{backend,reason})
+            }
+        }
+        Err(PlatformBuildError::NoBackendAvailable(failures))
+    }
+
+    fn env_backends(&self)->Result<Option<Vec<BackendKind>>,PlatformBuildError> {
+        if !self.env_override {return Ok(None);}
+        match std::env::var(BACKEND_ENV_VAR) {
+            Ok(value) if !value.trim().is_empty()=>{
+                value.split(',').map(|name|name.parse()).collect::<Result<Vec<_>,_>>().map(Some)
+            }
+            _=>Ok(None)
+        }
+    }
+
+    /// The virtual backend is left out, so that a missing display is reported instead of silently running headless.
+    fn default_backends()->Vec<BackendKind> {
+        vec![
+            #[cfg(all(target_os = "linux",feature="wayland_platform"))]
+            BackendKind::Wayland,
+            #[cfg(all(target_os = "linux",feature="xcb_platform"))]
+            BackendKind::Xcb,
+            #[cfg(all(target_os = "linux",feature="libinput_platform"))]
+            BackendKind::Libinput,
+        ]
+    }
+
+    #[allow(unused_variables)]
+    fn try_backend(backend: BackendKind, external_contexts: &mut Option<Vec<Box<dyn ExternalContext>>>)->Result<Backend,PlatformError> {
+        match backend {
+            #[cfg(all(target_os = "linux",feature="wayland_platform"))]
+            BackendKind::Wayland=>super::linux::LinuxPlatform::new_wayland(external_contexts).map(Backend::Linux),
+            #[cfg(all(target_os = "linux",feature="xcb_platform"))]
+            BackendKind::Xcb=>{
+                let external_contexts = external_contexts.take().unwrap_or_default();
+                super::linux::LinuxPlatform::new_xcb(external_contexts).map(Backend::Linux)
+            }
+            #[cfg(all(target_os = "linux",feature="libinput_platform"))]
+            BackendKind::Libinput=>super::linux::LinuxPlatform::new_libinput().map(Backend::Linux),
+            #[cfg(feature="virtual_platform")]
+            BackendKind::Virtual=>super::VirtualPlatform::with_default_devices().map(Backend::Virtual),
+            _=>Err(PlatformError::BackendNotCompiled)
+        }
+    }
+}
+
+impl Default for PlatformBuilder {
+    fn default()->Self {Self::new()}
+}
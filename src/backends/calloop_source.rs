@@ -0,0 +1,80 @@
+use std::os::unix::io::{AsRawFd,RawFd};
+
+use calloop::{EventSource,Interest,Mode,Poll,PostAction,Readiness,Token,TokenFactory};
+use calloop::generic::Generic;
+use calloop::ping::{make_ping,Ping,PingSource};
+
+use crate::definitions::*;
+use super::Platform;
+
+/// Maximum number of draining passes in a single dispatch, to avoid starving the other sources.
+const MAX_DRAIN_PASSES: usize = 16;
+
+/**
+Calloop event source wrapping a platform.
+The callback receives every event along with the platform itself, so that requests can be issued from it.
+Events queued by the backend without fd activity, like the initial ones or the ones produced by requests,
+are drained in the same dispatch.
+*/
+pub struct PlatformSource {
+    platform: Platform,
+    fd: Generic<RawFd>,
+    ping: Ping,
+    ping_source: PingSource,
+}
+impl PlatformSource {
+    pub fn new(platform: Platform)->std::io::Result<Self> {
+        let fd = Generic::new(platform.as_raw_fd(),Interest::READ,Mode::Level);
+        let (ping,ping_source) = make_ping()?;
+        Ok(Self {platform,fd,ping,ping_source})
+    }
+
+    pub fn platform(&self)->&Platform {&self.platform}
+    pub fn platform_mut(&mut self)->&mut Platform {&mut self.platform}
+    pub fn into_platform(self)->Platform {self.platform}
+
+    fn dispatch<F: FnMut(Event,&mut Platform)>(&mut self, callback: &mut F) {
+        for _ in 0..MAX_DRAIN_PASSES {
+            let events = self.platform.events();
+            if events.is_empty() {return;}
+            for event in events {callback(event,&mut self.platform);}
+        }
+        // Still producing events, resume on the next dispatch.
+        self.ping.ping();
+    }
+}
+
+impl EventSource for PlatformSource {
+    type Event = Event;
+    type Metadata = Platform;
+    type Ret = ();
+    type Error = Box<dyn std::error::Error + Sync + Send>;
+
+    fn process_events<F>(&mut self, readiness: Readiness, token: Token, mut callback: F)->Result<PostAction,Self::Error>
+    where F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret {
+        self.fd.process_events(readiness,token,|_,_|Ok(PostAction::Continue))?;
+        self.ping_source.process_events(readiness,token,|_,_|{})?;
+        self.dispatch(&mut callback);
+        Ok(PostAction::Continue)
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory)->calloop::Result<()> {
+        self.fd.register(poll,token_factory)?;
+        self.ping_source.register(poll,token_factory)?;
+        // The backend may have queued events before being registered.
+        self.ping.ping();
+        Ok(())
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory)->calloop::Result<()> {
+        self.fd.reregister(poll,token_factory)?;
+        self.ping_source.reregister(poll,token_factory)?;
+        Ok(())
+    }
+
+    fn unregister(&mut self, poll: &mut Poll)->calloop::Result<()> {
+        self.fd.unregister(poll)?;
+        self.ping_source.unregister(poll)?;
+        Ok(())
+    }
+}
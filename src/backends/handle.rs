@@ -0,0 +1,32 @@
+use std::sync::{Arc,Mutex};
+use std::sync::mpsc::Sender;
+
+use crate::definitions::*;
+use super::poller::EventFd;
+
+#[derive(Clone)]
+/**
+Thread safe handle to send requests to a `Platform`.
+Requests are queued and the platform fd is woken up, they are then executed on the next `Platform::events` call.
+*/
+pub struct PlatformHandle {
+    sender: Arc<Mutex<Sender<Request>>>,
+    waker: Arc<EventFd>,
+}
+impl PlatformHandle {
+    pub(crate) fn new(sender: Sender<Request>, waker: Arc<EventFd>)->Self {
+        Self {sender: Arc::new(Mutex::new(sender)),waker}
+    }
+
+    /// Queue the requests, failing if the platform has been dropped.
+    pub fn requests(&self, requests: Vec<Request>)->Result<(),PlatformError> {
+        {
+            let sender = self.sender.lock().unwrap_or_else(|err|err.into_inner());
+            for request in requests {
+                sender.send(request).map_err(|_|PlatformError::Connection(String::from("The platform has been dropped")))?;
+            }
+        }
+        self.waker.notify();
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::{HashMap,HashSet};
+use std::time::Duration;
+use crate::definitions::*;
+
+/// Default repeat rate, in repeats per second.
+pub const DEFAULT_REPEAT_RATE: u32 = 25;
+/// Default delay before the first repeat, in milliseconds.
+pub const DEFAULT_REPEAT_DELAY: u32 = 600;
+
+struct Repeating {
+    code: u32,
+    key: Option<Key>,
+    logical_key: LogicalKey,
+    location: Location,
+    serial: u32,
+    text: Option<String>,
+    next: EventTime,
+}
+
+fn is_repeatable(key: Option<Key>)->bool {
+    !matches!(key,
+        Some(Key::ShiftLeft) | Some(Key::ShiftRight) |
+        Some(Key::ControlLeft) | Some(Key::ControlRight) |
+        Some(Key::AltLeft) | Some(Key::AltRight) |
+        Some(Key::MetaLeft) | Some(Key::MetaRight) | Some(Key::Super) |
+        Some(Key::CapsLock) | Some(Key::NumLock) | Some(Key::ScrollLock)
+    )
+}
+
+/**
+Software auto repeat, generating the repeats of the last key held on each seat.
+The repeats reported by the backend are dropped, so that repeat behaves the same on every backend.
+*/
+pub(crate) struct KeyRepeater {
+    /// Repeats per second, zero disables the repeat.
+    rate: u32,
+    /// Delay before the first repeat, in milliseconds.
+    delay: u32,
+    keyboards: HashSet<SeatId>,
+    repeating: HashMap<SeatId,Repeating>,
+}
+impl KeyRepeater {
+    pub fn new(rate: u32, delay: u32)->Self {
+        Self {rate,delay,keyboards: HashSet::new(),repeating: HashMap::new()}
+    }
+
+    pub fn configure(&mut self, rate: u32, delay: u32) {
+        self.rate = rate;
+        self.delay = delay;
+        if rate == 0 {self.repeating.clear();}
+    }
+
+    /// Seats with a keyboard attached, sorted by identifier.
+    pub fn keyboards(&self)->Vec<SeatId> {
+        let mut keyboards: Vec<SeatId> = self.keyboards.iter().cloned().collect();
+        keyboards.sort_by_key(|id|Into::<usize>::into(*id));
+        keyboards
+    }
+
+    fn interval(&self)->Duration {
+        Duration::from_nanos(1_000_000_000 / self.rate.max(1) as u64)
+    }
+
+    pub fn process(&mut self, events: Vec<Event>)->Vec<Event> {
+        let mut processed = Vec::with_capacity(events.len());
+        for event in events {
+            match &event {
+                Event::Seat{event: SeatEvent::Keyboard(KeyboardEvent::Key{repeat: true,..}),..}=>continue,
+                Event::Seat{time,id,event: SeatEvent::Keyboard(KeyboardEvent::Key{code,key,logical_key,location,state: State::Down,serial,text,..})}
+                    if self.rate > 0 && is_repeatable(*key) => {
+                    let next = EventTime::from_monotonic(time.monotonic + self.delay as u64 * 1_000_000);
+                    self.repeating.insert(*id,Repeating{code: *code,key: *key,logical_key: logical_key.clone(),location: *location,serial: *serial,text: text.clone(),next});
+                }
+                Event::Seat{id,event: SeatEvent::Keyboard(KeyboardEvent::Key{code,state: State::Up,..}),..}
+                    if self.repeating.get(id).map(|repeating|repeating.code == *code).unwrap_or(false) => {
+                    self.repeating.remove(id);
+                }
+                Event::Seat{id,event: SeatEvent::Keyboard(KeyboardEvent::Added(_)),..}=>{self.keyboards.insert(*id);}
+                Event::Seat{id,event: SeatEvent::Keyboard(KeyboardEvent::Removed),..} | Event::Seat{id,event: SeatEvent::Removed,..}=>{
+                    self.keyboards.remove(id);
+                    self.repeating.remove(id);
+                }
+                Event::Surface{event: SurfaceEvent::Focused(false),..}=>self.repeating.clear(),
+                _=>{}
+            }
+            processed.push(event);
+        }
+
+        let now = EventTime::now();
+        let interval = self.interval().as_nanos() as u64;
+        let mut ids: Vec<SeatId> = self.repeating.keys().cloned().collect();
+        ids.sort_by_key(|id|Into::<usize>::into(*id));
+        for id in ids {
+            let repeating = self.repeating.get_mut(&id).unwrap();
+            if repeating.next > now {continue;}
+
+            let event = KeyboardEvent::Key{
+                code: repeating.code,
+                key: repeating.key,
+                logical_key: repeating.logical_key.clone(),
+                location: repeating.location,
+                state: State::Down,
+                serial: repeating.serial,
+                time: repeating.next,
+                repeat: true,
+                text: repeating.text.clone()
+            };
+            processed.push(Event::Seat{time: repeating.next,id,event: event.into()});
+
+            // Missed repeats are skipped rather than reported in a burst.
+            repeating.next = EventTime::from_monotonic(repeating.next.monotonic + interval);
+            if repeating.next <= now {repeating.next = EventTime::from_monotonic(now.monotonic + interval);}
+        }
+        processed
+    }
+
+    /// Time of the next repeat.
+    pub fn deadline(&self)->Option<EventTime> {
+        self.repeating.values().map(|repeating|repeating.next).min()
+    }
+}
@@ -0,0 +1,39 @@
+use std::io::{self,Seek,SeekFrom,Write};
+use std::fs::File;
+use std::os::unix::io::{AsRawFd,FromRawFd,RawFd};
+use crate::definitions::Keymap;
+
+/**
+Keymap stored in a sealed memfd, as expected by `wl_keyboard.keymap` along with its size.
+The file can no longer be written, grown or shrunk, so the same fd can be shared with every client.
+*/
+#[derive(Debug)]
+pub struct KeymapFile {
+    file: File,
+    size: usize
+}
+impl KeymapFile {
+    pub fn new(keymap: &Keymap)->io::Result<Self> {
+        let fd = unsafe{libc::memfd_create(b"pal-keymap\0".as_ptr() as *const libc::c_char,libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING)};
+        if fd < 0 {return Err(io::Error::last_os_error());}
+        let mut file = unsafe{File::from_raw_fd(fd)};
+
+        file.write_all(keymap.text.as_bytes())?;
+        file.write_all(&[0])?;
+        file.seek(SeekFrom::Start(0))?;
+
+        let seals = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE | libc::F_SEAL_SEAL;
+        if unsafe{libc::fcntl(fd,libc::F_ADD_SEALS,seals)} < 0 {return Err(io::Error::last_os_error());}
+
+        Ok(Self {file,size: keymap.size()})
+    }
+
+    /// Size of the keymap in bytes, including the NUL terminator.
+    pub fn size(&self)->usize {self.size}
+
+    pub fn file(&self)->&File {&self.file}
+    pub fn into_file(self)->File {self.file}
+}
+impl AsRawFd for KeymapFile {
+    fn as_raw_fd(&self)->RawFd {self.file.as_raw_fd()}
+}
@@ -1,3 +1,7 @@
+/**
+W3C code guessed from a keysym, for the backends that only report keysyms.
+Keysyms depend on the layout, so the evdev code is preferred when available.
+*/
 pub fn keysym_to_w3c_keycode(keysym: u32) -> Option<keyboard_types::Code> {
     use keyboard_types::Code;
     use keystroke_decoder::keysyms;
@@ -0,0 +1,56 @@
+use xkbcommon::xkb;
+use xkbcommon::xkb::compose::{Table,State,Status,FeedResult};
+use log::warn;
+
+/**
+Compose sequences of the current locale, matched by xkbcommon.
+The table is looked up like libX11 does: `XCOMPOSEFILE`, then `~/.XCompose`, and finally the file of the locale.
+*/
+pub struct XkbCompose {
+    /// `None` when no table could be loaded, compose sequences are then disabled.
+    state: Option<State>,
+}
+impl XkbCompose {
+    pub fn from_locale()->Self {
+        let locale = ["LC_ALL","LC_CTYPE","LANG"].iter()
+            .filter_map(|name|std::env::var_os(name))
+            .find(|locale|!locale.is_empty())
+            .unwrap_or_else(||"C".into());
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        match Table::new_from_locale(&context,&locale,xkb::compose::COMPILE_NO_FLAGS) {
+            Ok(table)=>Self::from_table(&table),
+            Err(_)=>{
+                warn!("No Compose table found for the locale {:?}, compose sequences are disabled",locale);
+                Self {state: None}
+            }
+        }
+    }
+
+    pub fn from_table(table: &Table)->Self {
+        Self {state: Some(State::new(table,xkb::compose::STATE_NO_FLAGS))}
+    }
+
+    /**
+    Feed the keysym of a pressed key.
+    Modifier keysyms do not interrupt a sequence and are reported as `Status::Nothing`.
+    */
+    pub fn feed(&mut self, keysym: u32)->Status {
+        match &mut self.state {
+            Some(state)=>match state.feed(keysym) {
+                FeedResult::Accepted=>state.status(),
+                FeedResult::Ignored=>Status::Nothing
+            }
+            None=>Status::Nothing
+        }
+    }
+
+    /// Text of the sequence just composed.
+    pub fn text(&self)->Option<String> {
+        self.state.as_ref().and_then(|state|state.utf8())
+    }
+
+    /// Abort the sequence in progress, for when the keys stop being seen.
+    pub fn reset(&mut self) {
+        if let Some(state) = &mut self.state {state.reset();}
+    }
+}
@@ -0,0 +1,34 @@
+use crate::backends::linux::{evdev_to_w3c_code,w3c_code_location,keysym_to_logical_key,XkbCompose,XkbKeyboard};
+use xkbcommon::xkb::compose::Status;
+use crate::definitions::{Event,EventTime,SeatEvent,KeyboardEvent,State,Location};
+
+/// Decode a key press or release with the current keyboard state, which is left to the caller to update.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_keyboard(
+    keyboard: &XkbKeyboard,
+    compose: &mut XkbCompose,
+    id: crate::definitions::SeatId,
+    code: u32,
+    state: State,
+    serial: u32,
+    time: EventTime,
+    repeat: bool,
+    control: bool
+) -> Event {
+    let keysym = keyboard.key_sym(code);
+    let key = evdev_to_w3c_code(code);
+    let logical_key = keysym_to_logical_key(keysym);
+    let location = key.map(w3c_code_location).unwrap_or(Location::Standard);
+
+    // Control combinations are shortcuts, not text.
+    let text = if state == State::Down && !control {
+        match compose.feed(keysym) {
+            Status::Nothing => keyboard.key_text(code),
+            Status::Composed => compose.text(),
+            Status::Composing | Status::Cancelled => None,
+        }
+    } else {None};
+
+    let event = SeatEvent::Keyboard(KeyboardEvent::Key{code,key,logical_key,location,state,serial,time,repeat,text});
+    Event::Seat{time,id,event}
+}
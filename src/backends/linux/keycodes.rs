@@ -0,0 +1,388 @@
+use keyboard_types::{Code,Key as LogicalKey,Location};
+use xkbcommon::xkb;
+use crate::definitions::Button;
+
+/**
+Physical keys, from the evdev code to the W3C code, sorted by evdev code.
+The codes follow the Linux column of the W3C UI Events KeyboardEvent code values specification.
+*/
+static EVDEV_CODES: &[(u32,Code)] = &[
+    (1,Code::Escape), // KEY_ESC
+    (2,Code::Digit1), // KEY_1
+    (3,Code::Digit2), // KEY_2
+    (4,Code::Digit3), // KEY_3
+    (5,Code::Digit4), // KEY_4
+    (6,Code::Digit5), // KEY_5
+    (7,Code::Digit6), // KEY_6
+    (8,Code::Digit7), // KEY_7
+    (9,Code::Digit8), // KEY_8
+    (10,Code::Digit9), // KEY_9
+    (11,Code::Digit0), // KEY_0
+    (12,Code::Minus), // KEY_MINUS
+    (13,Code::Equal), // KEY_EQUAL
+    (14,Code::Backspace), // KEY_BACKSPACE
+    (15,Code::Tab), // KEY_TAB
+    (16,Code::KeyQ), // KEY_Q
+    (17,Code::KeyW), // KEY_W
+    (18,Code::KeyE), // KEY_E
+    (19,Code::KeyR), // KEY_R
+    (20,Code::KeyT), // KEY_T
+    (21,Code::KeyY), // KEY_Y
+    (22,Code::KeyU), // KEY_U
+    (23,Code::KeyI), // KEY_I
+    (24,Code::KeyO), // KEY_O
+    (25,Code::KeyP), // KEY_P
+    (26,Code::BracketLeft), // KEY_LEFTBRACE
+    (27,Code::BracketRight), // KEY_RIGHTBRACE
+    (28,Code::Enter), // KEY_ENTER
+    (29,Code::ControlLeft), // KEY_LEFTCTRL
+    (30,Code::KeyA), // KEY_A
+    (31,Code::KeyS), // KEY_S
+    (32,Code::KeyD), // KEY_D
+    (33,Code::KeyF), // KEY_F
+    (34,Code::KeyG), // KEY_G
+    (35,Code::KeyH), // KEY_H
+    (36,Code::KeyJ), // KEY_J
+    (37,Code::KeyK), // KEY_K
+    (38,Code::KeyL), // KEY_L
+    (39,Code::Semicolon), // KEY_SEMICOLON
+    (40,Code::Quote), // KEY_APOSTROPHE
+    (41,Code::Backquote), // KEY_GRAVE
+    (42,Code::ShiftLeft), // KEY_LEFTSHIFT
+    (43,Code::Backslash), // KEY_BACKSLASH
+    (44,Code::KeyZ), // KEY_Z
+    (45,Code::KeyX), // KEY_X
+    (46,Code::KeyC), // KEY_C
+    (47,Code::KeyV), // KEY_V
+    (48,Code::KeyB), // KEY_B
+    (49,Code::KeyN), // KEY_N
+    (50,Code::KeyM), // KEY_M
+    (51,Code::Comma), // KEY_COMMA
+    (52,Code::Period), // KEY_DOT
+    (53,Code::Slash), // KEY_SLASH
+    (54,Code::ShiftRight), // KEY_RIGHTSHIFT
+    (55,Code::NumpadMultiply), // KEY_KPASTERISK
+    (56,Code::AltLeft), // KEY_LEFTALT
+    (57,Code::Space), // KEY_SPACE
+    (58,Code::CapsLock), // KEY_CAPSLOCK
+    (59,Code::F1), // KEY_F1
+    (60,Code::F2), // KEY_F2
+    (61,Code::F3), // KEY_F3
+    (62,Code::F4), // KEY_F4
+    (63,Code::F5), // KEY_F5
+    (64,Code::F6), // KEY_F6
+    (65,Code::F7), // KEY_F7
+    (66,Code::F8), // KEY_F8
+    (67,Code::F9), // KEY_F9
+    (68,Code::F10), // KEY_F10
+    (69,Code::NumLock), // KEY_NUMLOCK
+    (70,Code::ScrollLock), // KEY_SCROLLLOCK
+    (71,Code::Numpad7), // KEY_KP7
+    (72,Code::Numpad8), // KEY_KP8
+    (73,Code::Numpad9), // KEY_KP9
+    (74,Code::NumpadSubtract), // KEY_KPMINUS
+    (75,Code::Numpad4), // KEY_KP4
+    (76,Code::Numpad5), // KEY_KP5
+    (77,Code::Numpad6), // KEY_KP6
+    (78,Code::NumpadAdd), // KEY_KPPLUS
+    (79,Code::Numpad1), // KEY_KP1
+    (80,Code::Numpad2), // KEY_KP2
+    (81,Code::Numpad3), // KEY_KP3
+    (82,Code::Numpad0), // KEY_KP0
+    (83,Code::NumpadDecimal), // KEY_KPDOT
+    (85,Code::Lang5), // KEY_ZENKAKUHANKAKU
+    (86,Code::IntlBackslash), // KEY_102ND
+    (87,Code::F11), // KEY_F11
+    (88,Code::F12), // KEY_F12
+    (89,Code::IntlRo), // KEY_RO
+    (90,Code::Lang3), // KEY_KATAKANA
+    (91,Code::Lang4), // KEY_HIRAGANA
+    (92,Code::Convert), // KEY_HENKAN
+    (93,Code::KanaMode), // KEY_KATAKANAHIRAGANA
+    (94,Code::NonConvert), // KEY_MUHENKAN
+    (96,Code::NumpadEnter), // KEY_KPENTER
+    (97,Code::ControlRight), // KEY_RIGHTCTRL
+    (98,Code::NumpadDivide), // KEY_KPSLASH
+    (99,Code::PrintScreen), // KEY_SYSRQ
+    (100,Code::AltRight), // KEY_RIGHTALT
+    (102,Code::Home), // KEY_HOME
+    (103,Code::ArrowUp), // KEY_UP
+    (104,Code::PageUp), // KEY_PAGEUP
+    (105,Code::ArrowLeft), // KEY_LEFT
+    (106,Code::ArrowRight), // KEY_RIGHT
+    (107,Code::End), // KEY_END
+    (108,Code::ArrowDown), // KEY_DOWN
+    (109,Code::PageDown), // KEY_PAGEDOWN
+    (110,Code::Insert), // KEY_INSERT
+    (111,Code::Delete), // KEY_DELETE
+    (113,Code::AudioVolumeMute), // KEY_MUTE
+    (114,Code::AudioVolumeDown), // KEY_VOLUMEDOWN
+    (115,Code::AudioVolumeUp), // KEY_VOLUMEUP
+    (116,Code::Power), // KEY_POWER
+    (117,Code::NumpadEqual), // KEY_KPEQUAL
+    (119,Code::Pause), // KEY_PAUSE
+    (121,Code::NumpadComma), // KEY_KPCOMMA
+    (122,Code::Lang1), // KEY_HANGEUL
+    (123,Code::Lang2), // KEY_HANJA
+    (124,Code::IntlYen), // KEY_YEN
+    (125,Code::MetaLeft), // KEY_LEFTMETA
+    (126,Code::MetaRight), // KEY_RIGHTMETA
+    (127,Code::ContextMenu), // KEY_COMPOSE
+    (128,Code::BrowserStop), // KEY_STOP
+    (129,Code::Again), // KEY_AGAIN
+    (130,Code::Props), // KEY_PROPS
+    (131,Code::Undo), // KEY_UNDO
+    (132,Code::Select), // KEY_FRONT
+    (133,Code::Copy), // KEY_COPY
+    (134,Code::Open), // KEY_OPEN
+    (135,Code::Paste), // KEY_PASTE
+    (136,Code::Find), // KEY_FIND
+    (137,Code::Cut), // KEY_CUT
+    (138,Code::Help), // KEY_HELP
+    (140,Code::LaunchApp2), // KEY_CALC
+    (142,Code::Sleep), // KEY_SLEEP
+    (143,Code::WakeUp), // KEY_WAKEUP
+    (144,Code::LaunchApp1), // KEY_FILE
+    (155,Code::LaunchMail), // KEY_MAIL
+    (156,Code::BrowserFavorites), // KEY_BOOKMARKS
+    (158,Code::BrowserBack), // KEY_BACK
+    (159,Code::BrowserForward), // KEY_FORWARD
+    (161,Code::Eject), // KEY_EJECTCD
+    (163,Code::MediaTrackNext), // KEY_NEXTSONG
+    (164,Code::MediaPlayPause), // KEY_PLAYPAUSE
+    (165,Code::MediaTrackPrevious), // KEY_PREVIOUSSONG
+    (166,Code::MediaStop), // KEY_STOPCD
+    (172,Code::BrowserHome), // KEY_HOMEPAGE
+    (173,Code::BrowserRefresh), // KEY_REFRESH
+    (179,Code::NumpadParenLeft), // KEY_KPLEFTPAREN
+    (180,Code::NumpadParenRight), // KEY_KPRIGHTPAREN
+    (183,Code::F13), // KEY_F13
+    (184,Code::F14), // KEY_F14
+    (185,Code::F15), // KEY_F15
+    (186,Code::F16), // KEY_F16
+    (187,Code::F17), // KEY_F17
+    (188,Code::F18), // KEY_F18
+    (189,Code::F19), // KEY_F19
+    (190,Code::F20), // KEY_F20
+    (191,Code::F21), // KEY_F21
+    (192,Code::F22), // KEY_F22
+    (193,Code::F23), // KEY_F23
+    (194,Code::F24), // KEY_F24
+    (205,Code::Suspend), // KEY_SUSPEND
+    (217,Code::BrowserSearch), // KEY_SEARCH
+    (226,Code::MediaSelect), // KEY_MEDIA
+    (464,Code::Fn), // KEY_FN
+];
+
+/**
+W3C code of the physical key with the given evdev code.
+The XCB keycodes are the evdev codes offset by 8.
+*/
+pub fn evdev_to_w3c_code(code: u32) -> Option<Code> {
+    EVDEV_CODES.binary_search_by_key(&code,|(code,_)|*code).ok().map(|index|EVDEV_CODES[index].1)
+}
+
+/// Evdev code of the physical key with the given W3C code.
+pub fn w3c_code_to_evdev(code: Code) -> Option<u32> {
+    EVDEV_CODES.iter().find(|(_,entry)|*entry == code).map(|(code,_)|*code)
+}
+
+/// Mouse button with the given evdev code, as reported by libinput and Wayland.
+pub fn evdev_to_button(code: u32) -> Option<Button> {
+    match code {
+        0x110 => Some(Button::Left),
+        0x111 => Some(Button::Right),
+        0x112 => Some(Button::Middle),
+        _ => None
+    }
+}
+
+/// Location of the physical key with the given W3C code.
+pub fn w3c_code_location(code: Code) -> Location {
+    match code {
+        Code::ShiftLeft | Code::ControlLeft | Code::AltLeft | Code::MetaLeft => Location::Left,
+        Code::ShiftRight | Code::ControlRight | Code::AltRight | Code::MetaRight => Location::Right,
+        Code::Numpad0 | Code::Numpad1 | Code::Numpad2 | Code::Numpad3 | Code::Numpad4 |
+        Code::Numpad5 | Code::Numpad6 | Code::Numpad7 | Code::Numpad8 | Code::Numpad9 |
+        Code::NumpadAdd | Code::NumpadBackspace | Code::NumpadClear | Code::NumpadClearEntry |
+        Code::NumpadComma | Code::NumpadDecimal | Code::NumpadDivide | Code::NumpadEnter |
+        Code::NumpadEqual | Code::NumpadHash | Code::NumpadMemoryAdd | Code::NumpadMemoryClear |
+        Code::NumpadMemoryRecall | Code::NumpadMemoryStore | Code::NumpadMemorySubtract |
+        Code::NumpadMultiply | Code::NumpadParenLeft | Code::NumpadParenRight | Code::NumpadStar |
+        Code::NumpadSubtract => Location::Numpad,
+        _ => Location::Standard
+    }
+}
+
+/// Named logical keys, from the keysym to the W3C key value, sorted by keysym.
+static KEYSYM_KEYS: &[(u32,LogicalKey)] = &[
+    (0xfe03,LogicalKey::AltGraph), // ISO_Level3_Shift
+    (0xfe08,LogicalKey::GroupNext), // ISO_Next_Group
+    (0xfe0a,LogicalKey::GroupPrevious), // ISO_Prev_Group
+    (0xfe0c,LogicalKey::GroupFirst), // ISO_First_Group
+    (0xfe0e,LogicalKey::GroupLast), // ISO_Last_Group
+    (0xfe20,LogicalKey::Tab), // ISO_Left_Tab
+    (0xff08,LogicalKey::Backspace), // BackSpace
+    (0xff09,LogicalKey::Tab), // Tab
+    (0xff0b,LogicalKey::Clear), // Clear
+    (0xff0d,LogicalKey::Enter), // Return
+    (0xff13,LogicalKey::Pause), // Pause
+    (0xff14,LogicalKey::ScrollLock), // Scroll_Lock
+    (0xff1b,LogicalKey::Escape), // Escape
+    (0xff20,LogicalKey::Compose), // Multi_key
+    (0xff21,LogicalKey::KanjiMode), // Kanji
+    (0xff22,LogicalKey::NonConvert), // Muhenkan
+    (0xff23,LogicalKey::Convert), // Henkan_Mode
+    (0xff24,LogicalKey::Romaji), // Romaji
+    (0xff25,LogicalKey::Hiragana), // Hiragana
+    (0xff26,LogicalKey::Katakana), // Katakana
+    (0xff27,LogicalKey::HiraganaKatakana), // Hiragana_Katakana
+    (0xff28,LogicalKey::Zenkaku), // Zenkaku
+    (0xff29,LogicalKey::Hankaku), // Hankaku
+    (0xff2a,LogicalKey::ZenkakuHankaku), // Zenkaku_Hankaku
+    (0xff2d,LogicalKey::KanaMode), // Kana_Lock
+    (0xff30,LogicalKey::Eisu), // Eisu_toggle
+    (0xff31,LogicalKey::HangulMode), // Hangul
+    (0xff34,LogicalKey::HanjaMode), // Hangul_Hanja
+    (0xff37,LogicalKey::CodeInput), // Codeinput
+    (0xff3c,LogicalKey::SingleCandidate), // SingleCandidate
+    (0xff3d,LogicalKey::AllCandidates), // MultipleCandidate
+    (0xff3e,LogicalKey::PreviousCandidate), // PreviousCandidate
+    (0xff50,LogicalKey::Home), // Home
+    (0xff51,LogicalKey::ArrowLeft), // Left
+    (0xff52,LogicalKey::ArrowUp), // Up
+    (0xff53,LogicalKey::ArrowRight), // Right
+    (0xff54,LogicalKey::ArrowDown), // Down
+    (0xff55,LogicalKey::PageUp), // Prior
+    (0xff56,LogicalKey::PageDown), // Next
+    (0xff57,LogicalKey::End), // End
+    (0xff58,LogicalKey::Clear), // Begin
+    (0xff60,LogicalKey::Select), // Select
+    (0xff61,LogicalKey::PrintScreen), // Print
+    (0xff62,LogicalKey::Execute), // Execute
+    (0xff63,LogicalKey::Insert), // Insert
+    (0xff65,LogicalKey::Undo), // Undo
+    (0xff66,LogicalKey::Redo), // Redo
+    (0xff67,LogicalKey::ContextMenu), // Menu
+    (0xff68,LogicalKey::Find), // Find
+    (0xff69,LogicalKey::Cancel), // Cancel
+    (0xff6a,LogicalKey::Help), // Help
+    (0xff6b,LogicalKey::Pause), // Break
+    (0xff7e,LogicalKey::ModeChange), // Mode_switch
+    (0xff7f,LogicalKey::NumLock), // Num_Lock
+    (0xff89,LogicalKey::Tab), // KP_Tab
+    (0xff8d,LogicalKey::Enter), // KP_Enter
+    (0xff95,LogicalKey::Home), // KP_Home
+    (0xff96,LogicalKey::ArrowLeft), // KP_Left
+    (0xff97,LogicalKey::ArrowUp), // KP_Up
+    (0xff98,LogicalKey::ArrowRight), // KP_Right
+    (0xff99,LogicalKey::ArrowDown), // KP_Down
+    (0xff9a,LogicalKey::PageUp), // KP_Prior
+    (0xff9b,LogicalKey::PageDown), // KP_Next
+    (0xff9c,LogicalKey::End), // KP_End
+    (0xff9d,LogicalKey::Clear), // KP_Begin
+    (0xff9e,LogicalKey::Insert), // KP_Insert
+    (0xff9f,LogicalKey::Delete), // KP_Delete
+    (0xffbe,LogicalKey::F1), // F1
+    (0xffbf,LogicalKey::F2), // F2
+    (0xffc0,LogicalKey::F3), // F3
+    (0xffc1,LogicalKey::F4), // F4
+    (0xffc2,LogicalKey::F5), // F5
+    (0xffc3,LogicalKey::F6), // F6
+    (0xffc4,LogicalKey::F7), // F7
+    (0xffc5,LogicalKey::F8), // F8
+    (0xffc6,LogicalKey::F9), // F9
+    (0xffc7,LogicalKey::F10), // F10
+    (0xffc8,LogicalKey::F11), // F11
+    (0xffc9,LogicalKey::F12), // F12
+    (0xffca,LogicalKey::F13), // F13
+    (0xffcb,LogicalKey::F14), // F14
+    (0xffcc,LogicalKey::F15), // F15
+    (0xffcd,LogicalKey::F16), // F16
+    (0xffce,LogicalKey::F17), // F17
+    (0xffcf,LogicalKey::F18), // F18
+    (0xffd0,LogicalKey::F19), // F19
+    (0xffd1,LogicalKey::F20), // F20
+    (0xffd2,LogicalKey::F21), // F21
+    (0xffd3,LogicalKey::F22), // F22
+    (0xffd4,LogicalKey::F23), // F23
+    (0xffd5,LogicalKey::F24), // F24
+    (0xffe1,LogicalKey::Shift), // Shift_L
+    (0xffe2,LogicalKey::Shift), // Shift_R
+    (0xffe3,LogicalKey::Control), // Control_L
+    (0xffe4,LogicalKey::Control), // Control_R
+    (0xffe5,LogicalKey::CapsLock), // Caps_Lock
+    (0xffe6,LogicalKey::CapsLock), // Shift_Lock
+    (0xffe7,LogicalKey::Meta), // Meta_L
+    (0xffe8,LogicalKey::Meta), // Meta_R
+    (0xffe9,LogicalKey::Alt), // Alt_L
+    (0xffea,LogicalKey::Alt), // Alt_R
+    (0xffeb,LogicalKey::Super), // Super_L
+    (0xffec,LogicalKey::Super), // Super_R
+    (0xffed,LogicalKey::Hyper), // Hyper_L
+    (0xffee,LogicalKey::Hyper), // Hyper_R
+    (0xffff,LogicalKey::Delete), // Delete
+    (0x1008ff02,LogicalKey::BrightnessUp), // XF86MonBrightnessUp
+    (0x1008ff03,LogicalKey::BrightnessDown), // XF86MonBrightnessDown
+    (0x1008ff11,LogicalKey::AudioVolumeDown), // XF86AudioLowerVolume
+    (0x1008ff12,LogicalKey::AudioVolumeMute), // XF86AudioMute
+    (0x1008ff13,LogicalKey::AudioVolumeUp), // XF86AudioRaiseVolume
+    (0x1008ff14,LogicalKey::MediaPlay), // XF86AudioPlay
+    (0x1008ff15,LogicalKey::MediaStop), // XF86AudioStop
+    (0x1008ff16,LogicalKey::MediaTrackPrevious), // XF86AudioPrev
+    (0x1008ff17,LogicalKey::MediaTrackNext), // XF86AudioNext
+    (0x1008ff18,LogicalKey::BrowserHome), // XF86HomePage
+    (0x1008ff19,LogicalKey::LaunchMail), // XF86Mail
+    (0x1008ff1b,LogicalKey::BrowserSearch), // XF86Search
+    (0x1008ff1c,LogicalKey::MediaRecord), // XF86AudioRecord
+    (0x1008ff1d,LogicalKey::LaunchApplication2), // XF86Calculator
+    (0x1008ff20,LogicalKey::LaunchCalendar), // XF86Calendar
+    (0x1008ff26,LogicalKey::BrowserBack), // XF86Back
+    (0x1008ff27,LogicalKey::BrowserForward), // XF86Forward
+    (0x1008ff28,LogicalKey::BrowserStop), // XF86Stop
+    (0x1008ff29,LogicalKey::BrowserRefresh), // XF86Refresh
+    (0x1008ff2a,LogicalKey::PowerOff), // XF86PowerOff
+    (0x1008ff2b,LogicalKey::WakeUp), // XF86WakeUp
+    (0x1008ff2c,LogicalKey::Eject), // XF86Eject
+    (0x1008ff2d,LogicalKey::LaunchScreenSaver), // XF86ScreenSaver
+    (0x1008ff2e,LogicalKey::LaunchWebBrowser), // XF86WWW
+    (0x1008ff2f,LogicalKey::Standby), // XF86Sleep
+    (0x1008ff30,LogicalKey::BrowserFavorites), // XF86Favorites
+    (0x1008ff31,LogicalKey::MediaPause), // XF86AudioPause
+    (0x1008ff32,LogicalKey::LaunchMediaPlayer), // XF86AudioMedia
+    (0x1008ff33,LogicalKey::LaunchApplication1), // XF86MyComputer
+    (0x1008ff3e,LogicalKey::MediaRewind), // XF86AudioRewind
+    (0x1008ff56,LogicalKey::Close), // XF86Close
+    (0x1008ff57,LogicalKey::Copy), // XF86Copy
+    (0x1008ff58,LogicalKey::Cut), // XF86Cut
+    (0x1008ff61,LogicalKey::LogOff), // XF86LogOff
+    (0x1008ff68,LogicalKey::New), // XF86New
+    (0x1008ff6b,LogicalKey::Open), // XF86Open
+    (0x1008ff6d,LogicalKey::Paste), // XF86Paste
+    (0x1008ff72,LogicalKey::MailReply), // XF86Reply
+    (0x1008ff77,LogicalKey::Save), // XF86Save
+    (0x1008ff7b,LogicalKey::MailSend), // XF86Send
+    (0x1008ff7c,LogicalKey::SpellCheck), // XF86Spell
+    (0x1008ff8b,LogicalKey::ZoomIn), // XF86ZoomIn
+    (0x1008ff8c,LogicalKey::ZoomOut), // XF86ZoomOut
+    (0x1008ff90,LogicalKey::MailForward), // XF86MailForward
+    (0x1008ff92,LogicalKey::LaunchMusicPlayer), // XF86Music
+    (0x1008ff97,LogicalKey::MediaFastForward), // XF86AudioForward
+    (0x1008ffa8,LogicalKey::Hibernate), // XF86Hibernate
+    (0x1008ffb2,LogicalKey::MicrophoneVolumeMute), // XF86AudioMicMute
+];
+
+/**
+Logical key produced by a keysym, with the layout and the modifiers already applied by the keysym lookup.
+Printable keysyms are reported as `LogicalKey::Character`.
+*/
+pub fn keysym_to_logical_key(keysym: u32) -> LogicalKey {
+    // The dead_* keysyms.
+    if (0xfe50..=0xfe93).contains(&keysym) {return LogicalKey::Dead;}
+    if let Ok(index) = KEYSYM_KEYS.binary_search_by_key(&keysym,|(keysym,_)|*keysym) {
+        return KEYSYM_KEYS[index].1.clone();
+    }
+    let text = xkb::keysym_to_utf8(keysym).trim_end_matches('\0').to_string();
+    if text.is_empty() || text.chars().any(char::is_control) {return LogicalKey::Unidentified;}
+    LogicalKey::Character(text)
+}
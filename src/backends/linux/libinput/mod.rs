@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+use std::fs::{File,OpenOptions};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd,OwnedFd,RawFd};
+use std::path::Path;
+
+use input::{Libinput,LibinputInterface,Device,DeviceCapability};
+use input::event::Event as LibinputEvent;
+use input::event::{DeviceEvent,EventTrait};
+use input::event::keyboard::{KeyboardEvent as LibinputKeyboardEvent,KeyboardEventTrait,KeyState};
+use input::event::pointer::{PointerEvent,PointerEventTrait,PointerScrollEvent,ButtonState,Axis};
+use libc::{O_ACCMODE,O_RDONLY,O_RDWR,O_WRONLY};
+use log::error;
+
+use crate::definitions::*;
+use crate::backends::linux::{XkbCompose,XkbKeyboard,handle_keyboard,evdev_to_button};
+
+/// Name of the seat whose devices are read.
+const SEAT_NAME: &str = "seat0";
+
+/// Scroll value of a wheel notch, in the v120 unit of libinput.
+const WHEEL_NOTCH: f64 = 120.0;
+
+/// Open the devices for libinput directly, which requires the permissions on them, usually by being in the `input` group.
+struct Interface;
+impl LibinputInterface for Interface {
+    fn open_restricted(&mut self, path: &Path, flags: i32) -> Result<OwnedFd, i32> {
+        let access = flags & O_ACCMODE;
+        OpenOptions::new()
+            .custom_flags(flags)
+            .read(access == O_RDONLY || access == O_RDWR)
+            .write(access == O_WRONLY || access == O_RDWR)
+            .open(path)
+            .map(OwnedFd::from)
+            .map_err(|err|err.raw_os_error().unwrap_or(libc::EIO))
+    }
+    fn close_restricted(&mut self, fd: OwnedFd) {
+        drop(File::from(fd));
+    }
+}
+
+/// Libinput timestamps are taken on the monotonic clock, in microseconds.
+fn event_time(time_usec: u64)->EventTime {
+    EventTime {monotonic: time_usec * 1000, raw: Some((time_usec / 1000) as u32)}
+}
+
+/**
+Devices of a libinput seat.
+Libinput has a keyboard state per device, so the keys of all the keyboards of the seat are decoded through a shared one.
+*/
+struct Seat {
+    id: SeatId,
+    keyboard: XkbKeyboard,
+    keyboards: Vec<String>,
+    cursors: Vec<String>,
+}
+
+/**
+Platform reading the input devices directly through libinput, without a display server.
+It reports the seats, keyboards and cursors, while surfaces and outputs are left to a rendering backend.
+*/
+pub struct LibinputPlatform {
+    libinput: Libinput,
+    keymap: Keymap,
+    compose_state: XkbCompose,
+    seats: HashMap<String,Seat>,
+    next_seat_id: u32,
+    /// Outcomes of the requests, reported with the next events.
+    pending_events: Vec<crate::definitions::Event>,
+}
+impl LibinputPlatform {
+    pub fn new() -> Result<Self, PlatformError> {
+        let mut libinput = Libinput::new_with_udev(Interface);
+        libinput.udev_assign_seat(SEAT_NAME)
+            .map_err(|_|PlatformError::Connection(format!("Failed to assign {} to libinput",SEAT_NAME)))?;
+
+        let descriptor = KeymapDescriptor::default();
+        let keymap = XkbKeyboard::from_descriptor(&descriptor)
+            .map_err(|err|PlatformError::Io(format!("Failed to compile the default keymap: {:?}",err)))?
+            .keymap(descriptor);
+        let compose_state = XkbCompose::from_locale();
+
+        Ok(Self {
+            libinput,
+            keymap,
+            compose_state,
+            seats: HashMap::new(),
+            next_seat_id: 0,
+            pending_events: Vec::new()
+        })
+    }
+
+    fn add_device(&mut self, device: Device, time: EventTime, events: &mut Vec<crate::definitions::Event>) {
+        let seat_name = String::from(device.seat().logical_name());
+        if !self.seats.contains_key(&seat_name) {
+            let keyboard = match XkbKeyboard::from_descriptor(&self.keymap.descriptor) {
+                Ok(keyboard)=>keyboard,
+                Err(err)=>{
+                    error!("Failed to compile the keymap for {}: {:?}",seat_name,err);
+                    return;
+                }
+            };
+            let id = self.next_seat_id.into();
+            self.next_seat_id += 1;
+            let event = SeatEvent::Added{name: seat_name.clone()};
+            events.push(crate::definitions::Event::Seat{time,id,event});
+            self.seats.insert(seat_name.clone(),Seat {id,keyboard,keyboards: Vec::new(),cursors: Vec::new()});
+        }
+        let seat = match self.seats.get_mut(&seat_name) {
+            Some(seat)=>seat,
+            None=>return
+        };
+        let id = seat.id;
+        let sysname = String::from(device.sysname());
+
+        if device.has_capability(DeviceCapability::Keyboard) {
+            if seat.keyboards.is_empty() {
+                let keyboard_info = KeyboardInfo {
+                    keymap: self.keymap.descriptor.clone(),
+                    // Libinput does not repeat the keys.
+                    autorepeat: false,
+                    encoding: KeyEncoding::XkbV1
+                };
+                let event = SeatEvent::Keyboard(KeyboardEvent::Added(keyboard_info));
+                events.push(crate::definitions::Event::Seat{time,id,event});
+                let event = SeatEvent::Keyboard(KeyboardEvent::KeymapChanged{keymap: self.keymap.clone()});
+                events.push(crate::definitions::Event::Seat{time,id,event});
+                let event = SeatEvent::Keyboard(seat.keyboard.modifiers_event());
+                events.push(crate::definitions::Event::Seat{time,id,event});
+            }
+            seat.keyboards.push(sysname.clone());
+        }
+        if device.has_capability(DeviceCapability::Pointer) {
+            if seat.cursors.is_empty() {
+                // There is no surface to draw the cursor on, only its movements are reported.
+                let cursor_info = CursorInfo {
+                    mode: CursorMode::Relative,
+                    theme: CursorImage::Default,
+                    visible: false
+                };
+                let event = SeatEvent::Cursor(CursorEvent::Added(cursor_info));
+                events.push(crate::definitions::Event::Seat{time,id,event});
+            }
+            seat.cursors.push(sysname);
+        }
+    }
+
+    fn remove_device(&mut self, device: Device, time: EventTime, events: &mut Vec<crate::definitions::Event>) {
+        let seat_name = String::from(device.seat().logical_name());
+        let seat = match self.seats.get_mut(&seat_name) {
+            Some(seat)=>seat,
+            None=>return
+        };
+        let id = seat.id;
+        let sysname = device.sysname();
+
+        if let Some(index) = seat.keyboards.iter().position(|name|name == sysname) {
+            seat.keyboards.remove(index);
+            if seat.keyboards.is_empty() {
+                seat.keyboard.release_keys();
+                let event = SeatEvent::Keyboard(KeyboardEvent::Removed);
+                events.push(crate::definitions::Event::Seat{time,id,event});
+            }
+        }
+        if let Some(index) = seat.cursors.iter().position(|name|name == sysname) {
+            seat.cursors.remove(index);
+            if seat.cursors.is_empty() {
+                let event = SeatEvent::Cursor(CursorEvent::Removed);
+                events.push(crate::definitions::Event::Seat{time,id,event});
+            }
+        }
+        if seat.keyboards.is_empty() && seat.cursors.is_empty() {
+            self.seats.remove(&seat_name);
+            events.push(crate::definitions::Event::Seat{time,id,event: SeatEvent::Removed});
+        }
+    }
+
+    /**
+    Decode a key with the keyboard state of its seat, which is driven by the key events themselves.
+    A key held on several keyboards of the seat only changes the state on its first press and last release.
+    */
+    fn handle_key(&mut self, event: input::event::keyboard::KeyboardKeyEvent, events: &mut Vec<crate::definitions::Event>) {
+        let seat_name = String::from(event.device().seat().logical_name());
+        let seat = match self.seats.get_mut(&seat_name) {
+            Some(seat)=>seat,
+            None=>return
+        };
+        let time = event_time(event.time_usec());
+        let code = event.key();
+        let (state,seat_transition) = match event.key_state() {
+            KeyState::Pressed=>(State::Down,event.seat_key_count() == 1),
+            KeyState::Released=>(State::Up,event.seat_key_count() == 0)
+        };
+        if !seat_transition {return;}
+
+        let (depressed,latched,locked) = seat.keyboard.mods();
+        let control = seat.keyboard.modifiers(depressed | latched | locked).contains(Modifiers::CONTROL);
+        events.push(handle_keyboard(&seat.keyboard,&mut self.compose_state,seat.id,code,state,0,time,false,control));
+        if seat.keyboard.update_key(code,state) {
+            let event = SeatEvent::Keyboard(seat.keyboard.modifiers_event());
+            events.push(crate::definitions::Event::Seat{time,id: seat.id,event});
+        }
+    }
+
+    fn handle_pointer(&mut self, event: PointerEvent, events: &mut Vec<crate::definitions::Event>) {
+        let seat_name = String::from(event.device().seat().logical_name());
+        let id = match self.seats.get(&seat_name) {
+            Some(seat)=>seat.id,
+            None=>return
+        };
+        match event {
+            PointerEvent::Motion(event)=>{
+                let time = event_time(event.time_usec());
+                let offset = Offset2D{x: event.dx() as f32,y: event.dy() as f32};
+                let event = SeatEvent::Cursor(CursorEvent::RelativeMovement{offset});
+                events.push(crate::definitions::Event::Seat{time,id,event});
+            }
+            PointerEvent::Button(event)=>{
+                let time = event_time(event.time_usec());
+                let code = event.button();
+                let key = evdev_to_button(code);
+                let state = match event.button_state() {
+                    ButtonState::Pressed=>State::Down,
+                    ButtonState::Released=>State::Up
+                };
+                let event = SeatEvent::Cursor(CursorEvent::Button{code,key,state});
+                events.push(crate::definitions::Event::Seat{time,id,event});
+            }
+            // Finger and continuous scrolling have no axis source yet, only the wheels are reported.
+            PointerEvent::ScrollWheel(event)=>{
+                let time = event_time(event.time_usec());
+                for (axis,direction) in [(Axis::Vertical,AxisDirection::Vertical),(Axis::Horizontal,AxisDirection::Horizontal)] {
+                    if !event.has_axis(axis) {continue;}
+                    let value = event.scroll_value_v120(axis);
+                    if value == 0.0 {continue;}
+                    // High resolution wheels move by fractions of a notch.
+                    let value = if value % WHEEL_NOTCH == 0.0 {
+                        AxisValue::Discrete((value / WHEEL_NOTCH) as i32)
+                    } else {
+                        AxisValue::Continuous(event.scroll_value(axis) as f32)
+                    };
+                    let event = SeatEvent::Cursor(CursorEvent::Axis{source: AxisSource::Wheel,direction,value});
+                    events.push(crate::definitions::Event::Seat{time,id,event});
+                }
+            }
+            _=>()
+        }
+    }
+
+    /**
+    Compile the whole descriptor before replacing the keymap of the seats, so that invalid keymaps are rejected.
+    The keys held down are forgotten, the locks are kept and the first group is selected.
+    */
+    fn modify_layout(&mut self, descriptor: KeymapDescriptor) -> Result<(), RequestError> {
+        let keymap = XkbKeyboard::from_descriptor(&descriptor)?.keymap(descriptor.clone());
+        let time = EventTime::now();
+        for seat in self.seats.values_mut() {
+            let mut keyboard = XkbKeyboard::from_descriptor(&descriptor)?;
+            let (_,_,locked) = seat.keyboard.mods();
+            keyboard.update_mask(0,0,locked,0,0,0);
+            seat.keyboard = keyboard;
+            if seat.keyboards.is_empty() {continue;}
+
+            let id = seat.id;
+            let event = SeatEvent::Keyboard(KeyboardEvent::LayoutModified{keymap: descriptor.clone()});
+            self.pending_events.push(crate::definitions::Event::Seat{time,id,event});
+            let event = SeatEvent::Keyboard(KeyboardEvent::KeymapChanged{keymap: keymap.clone()});
+            self.pending_events.push(crate::definitions::Event::Seat{time,id,event});
+            let event = SeatEvent::Keyboard(seat.keyboard.modifiers_event());
+            self.pending_events.push(crate::definitions::Event::Seat{time,id,event});
+        }
+        self.keymap = keymap;
+        Ok(())
+    }
+
+    fn switch_group(&mut self, group: u32) -> Result<(), RequestError> {
+        let time = EventTime::now();
+        for seat in self.seats.values_mut() {
+            seat.keyboard.lock_group(group)?;
+            if seat.keyboards.is_empty() {continue;}
+            let event = SeatEvent::Keyboard(seat.keyboard.modifiers_event());
+            self.pending_events.push(crate::definitions::Event::Seat{time,id: seat.id,event});
+        }
+        Ok(())
+    }
+}
+
+impl AsRawFd for LibinputPlatform {
+    fn as_raw_fd(&self)->RawFd {self.libinput.as_raw_fd()}
+}
+
+impl PlatformBackend for LibinputPlatform {
+    fn platform_type(&self)->PlatformType {PlatformType::Direct}
+    fn events(&mut self) -> Vec<crate::definitions::Event> {
+        if let Err(err) = self.libinput.dispatch() {
+            error!("Failed to read the input devices: {}",err);
+        }
+
+        let mut events = std::mem::take(&mut self.pending_events);
+        while let Some(event) = self.libinput.next() {
+            match event {
+                LibinputEvent::Device(DeviceEvent::Added(event))=>{
+                    self.add_device(event.device(),EventTime::now(),&mut events);
+                }
+                LibinputEvent::Device(DeviceEvent::Removed(event))=>{
+                    self.remove_device(event.device(),EventTime::now(),&mut events);
+                }
+                LibinputEvent::Keyboard(LibinputKeyboardEvent::Key(event))=>self.handle_key(event,&mut events),
+                LibinputEvent::Pointer(event)=>self.handle_pointer(event,&mut events),
+                _=>()
+            }
+        }
+        events
+    }
+
+    fn requests(&mut self, requests: Vec<Request>) {
+        requests.into_iter().for_each(|request|{
+            let token = request.token();
+            let result = match request {
+                crate::definitions::Request::Seat{request: SeatRequest::Keyboard(keyboard_request),..}=>{
+                    match keyboard_request {
+                        KeyboardRequest::ModifyLayout{keymap}=>self.modify_layout(keymap),
+                        KeyboardRequest::SwitchGroup{group}=>self.switch_group(group),
+                        KeyboardRequest::SetAutoRepeat{..}=>Err(KeyRepeatError::Unsupported.into())
+                    }
+                }
+                crate::definitions::Request::Seat{request: SeatRequest::Cursor(cursor_request),..}=>{
+                    match cursor_request {
+                        CursorRequest::ChangeImage(_)=>Err(CursorImageError::Unsupported.into()),
+                        CursorRequest::ChangeMode(CursorMode::Relative)=>Ok(()),
+                        CursorRequest::ChangeMode(CursorMode::Absolute)=>Err(CursorModeError::Unsupported.into())
+                    }
+                }
+                crate::definitions::Request::Seat{request: SeatRequest::Touch(_touch_request),..}=>Ok(()),
+                crate::definitions::Request::Seat{request: SeatRequest::Gamepad(_gamepad_request),..}=>Ok(()),
+                crate::definitions::Request::Output{..}=>Ok(()),
+                crate::definitions::Request::Surface{request: SurfaceRequest::Create(_output),..}=>Err(SurfaceError::Unsupported.into()),
+                crate::definitions::Request::Surface{..}=>Err(SurfaceError::UnknownSurface.into())
+            };
+            match token {
+                Some(token)=>{
+                    let time = EventTime::now();
+                    let event = RequestEvent::from(result);
+                    self.pending_events.push(crate::definitions::Event::Request{time,token,event});
+                }
+                None=>{
+                    if let Err(err) = result {error!("Failed to handle request: {}",err);}
+                }
+            }
+        });
+    }
+}
@@ -9,6 +9,13 @@ use wayland::WaylandPlatform;
 mod xcb;
 #[cfg(feature = "xcb_platform")]
 use xcb::XcbPlatform;
+#[cfg(all(test,feature = "xcb_platform"))]
+pub(crate) use xcb::{XimProtocol,XimEvent};
+
+#[cfg(feature = "libinput_platform")]
+mod libinput;
+#[cfg(feature = "libinput_platform")]
+use libinput::LibinputPlatform;
 
 #[cfg(feature = "libinput_vulkano_platform")]
 mod libinput_vulkano;
@@ -21,6 +28,18 @@ mod libinput_wgpu;
 mod common;
 pub use common::*;
 
+pub(crate) mod keycodes;
+pub use keycodes::{evdev_to_w3c_code,evdev_to_button,w3c_code_location,keysym_to_logical_key};
+
+mod compose;
+pub use compose::XkbCompose;
+
+mod xkb_keymap;
+pub use xkb_keymap::XkbKeyboard;
+
+mod keyboard;
+pub use keyboard::handle_keyboard;
+
 use crate::definitions::*;
 use log::*;
 
@@ -32,21 +51,44 @@ pub enum LinuxPlatform {
     Wayland(WaylandPlatform),
     #[cfg(feature = "xcb_platform")]
     Xcb(XcbPlatform),
+    #[cfg(feature = "libinput_platform")]
+    Libinput(LibinputPlatform),
 }
 impl LinuxPlatform {
-    pub fn new(external_contexts: Vec<Box<dyn ExternalContext>>) -> Option<Self> {
-        #[cfg(feature = "wayland_platform")]
-        match WaylandPlatform::new() {
-            Ok(platform) => return Some(Self::Wayland(platform)),
-            Err(err) => info!("Failed to init wayland platform: {:#?}",err),
+    #[cfg(feature = "wayland_platform")]
+    /// Connect to the wayland compositor, the external contexts are only taken once connected.
+    pub fn new_wayland(external_contexts: &mut Option<Vec<Box<dyn ExternalContext>>>) -> Result<Self, PlatformError> {
+        match WaylandPlatform::new(external_contexts) {
+            Ok(platform) => Ok(Self::Wayland(platform)),
+            Err(err) => {
+                info!("Failed to init wayland platform: {:#?}",err);
+                Err(err)
+            }
         }
+    }
 
-        #[cfg(feature = "xcb_platform")]
+    #[cfg(feature = "xcb_platform")]
+    /// Connect to the X server.
+    pub fn new_xcb(external_contexts: Vec<Box<dyn ExternalContext>>) -> Result<Self, PlatformError> {
         match XcbPlatform::new(external_contexts) {
-            Ok(platform) => return Some(Self::Xcb(platform)),
-            Err(err) => info!("Failed to init wayland platform: {:#?}",err),
+            Ok(platform) => Ok(Self::Xcb(platform)),
+            Err(err) => {
+                info!("Failed to init xcb platform: {:#?}",err);
+                Err(err)
+            }
+        }
+    }
+
+    #[cfg(feature = "libinput_platform")]
+    /// Read the input devices of the first seat directly, without a display server.
+    pub fn new_libinput() -> Result<Self, PlatformError> {
+        match LibinputPlatform::new() {
+            Ok(platform) => Ok(Self::Libinput(platform)),
+            Err(err) => {
+                info!("Failed to init libinput platform: {:#?}",err);
+                Err(err)
+            }
         }
-        None
     }
 }
 
@@ -58,18 +100,31 @@ impl std::os::unix::io::AsRawFd for LinuxPlatform {
             Self::Wayland(platform) => platform.as_raw_fd(),
             #[cfg(feature = "xcb_platform")]
             Self::Xcb(platform) => platform.as_raw_fd(),
+            #[cfg(feature = "libinput_platform")]
+            Self::Libinput(platform) => platform.as_raw_fd(),
         }
     }
 }
 
 impl crate::definitions::PlatformBackend for LinuxPlatform {
-    fn platform_type(&self)->PlatformType {PlatformType::Compositor}
+    fn platform_type(&self)->PlatformType {
+        match self {
+            #[cfg(feature = "wayland_platform")]
+            Self::Wayland(_) => PlatformType::Compositor,
+            #[cfg(feature = "xcb_platform")]
+            Self::Xcb(_) => PlatformType::Compositor,
+            #[cfg(feature = "libinput_platform")]
+            Self::Libinput(platform) => platform.platform_type(),
+        }
+    }
     fn events(&mut self) -> Vec<crate::definitions::Event> {
         match self {
             #[cfg(feature = "wayland_platform")]
             Self::Wayland(platform) => platform.events(),
             #[cfg(feature = "xcb_platform")]
             Self::Xcb(platform) => platform.events(),
+            #[cfg(feature = "libinput_platform")]
+            Self::Libinput(platform) => platform.events(),
         }
     }
     fn requests(&mut self, requests: Vec<Request>) {
@@ -78,6 +133,8 @@ impl crate::definitions::PlatformBackend for LinuxPlatform {
             Self::Wayland(platform) => platform.requests(requests),
             #[cfg(feature = "xcb_platform")]
             Self::Xcb(platform) => platform.requests(requests),
+            #[cfg(feature = "libinput_platform")]
+            Self::Libinput(platform) => platform.requests(requests),
         }
     }
 }
@@ -1,32 +1,42 @@
-/*
-impl From<smithay_client_toolkit::output::OutputInfo> for crate::definitions::OutputInfo {
-    fn from(output_info: smithay_client_toolkit::output::OutputInfo) -> Self {
+use crate::definitions::{OutputInfo,Mode};
+
+/// Output informations, for the outputs having a mode.
+pub fn output_info(output_info: &smithay_client_toolkit::output::OutputInfo) -> Option<OutputInfo> {
+    let available_modes: Vec<Mode> = output_info.modes.iter().map(|mode|mode.into()).collect();
+    let selected_mode = output_info.modes.iter()
+        .position(|mode|mode.is_current)
+        .or_else(||output_info.modes.iter().position(|mode|mode.is_preferred))
+        .and_then(|index|available_modes.get(index))
+        .or_else(||available_modes.first())?
+        .clone();
+    Some(OutputInfo {
+        position: (output_info.location.0.max(0) as u32,output_info.location.1.max(0) as u32).into(),
+        selected_mode,
+        available_modes,
+        physical_size: (output_info.physical_size.0.max(0) as u32,output_info.physical_size.1.max(0) as u32).into(),
+        subpixel: output_info.subpixel.into(),
+    })
+}
+
+impl From<&smithay_client_toolkit::output::Mode> for crate::definitions::Mode {
+    fn from(mode: &smithay_client_toolkit::output::Mode) -> Self {
         Self {
-            id: output_info.id.into(),
-            model: output_info.model.clone(),
-            make: output_info.make.clone(),
-            location: output_info.location.clone(),
-            physical_size: output_info.physical_size.clone(),
-            subpixel: output_info.subpixel.into(),
-            scale_factor: output_info.scale_factor.clone(),
-            modes: output_info
-                .modes
-                .into_iter()
-                .map(|mode| mode.into())
-                .collect(),
+            resolution: (mode.dimensions.0.max(0) as u32,mode.dimensions.1.max(0) as u32).into(),
+            refresh_rate: mode.refresh_rate.max(0) as u32,
+            is_preferred: mode.is_preferred,
         }
     }
-}*/
+}
+
 impl From<smithay_client_toolkit::output::Subpixel> for crate::definitions::Subpixel {
     fn from(subpixel: smithay_client_toolkit::output::Subpixel) -> Self {
         match subpixel {
-            smithay_client_toolkit::output::Subpixel::Unknown => Self::Unknown,
             smithay_client_toolkit::output::Subpixel::None => Self::None,
             smithay_client_toolkit::output::Subpixel::HorizontalRgb => Self::HorizontalRgb,
             smithay_client_toolkit::output::Subpixel::HorizontalBgr => Self::HorizontalBgr,
             smithay_client_toolkit::output::Subpixel::VerticalRgb => Self::VerticalRgb,
             smithay_client_toolkit::output::Subpixel::VerticalBgr => Self::VerticalBgr,
-            _ => panic!(),
+            _ => Self::Unknown,
         }
     }
 }
@@ -34,7 +44,6 @@ impl From<smithay_client_toolkit::output::Subpixel> for crate::definitions::Subp
 impl From<smithay_client_toolkit::output::Transform> for crate::definitions::Transform {
     fn from(transform: smithay_client_toolkit::output::Transform) -> Self {
         match transform {
-            smithay_client_toolkit::output::Transform::Normal => Self::Normal,
             smithay_client_toolkit::output::Transform::_90 => Self::_90,
             smithay_client_toolkit::output::Transform::_180 => Self::_180,
             smithay_client_toolkit::output::Transform::_270 => Self::_270,
@@ -42,40 +51,7 @@ impl From<smithay_client_toolkit::output::Transform> for crate::definitions::Tra
             smithay_client_toolkit::output::Transform::Flipped90 => Self::Flipped90,
             smithay_client_toolkit::output::Transform::Flipped180 => Self::Flipped180,
             smithay_client_toolkit::output::Transform::Flipped270 => Self::Flipped270,
-            _ => panic!(),
-        }
-    }
-}
-/*
-impl From<smithay_client_toolkit::output::Mode> for crate::definitions::Mode {
-    fn from(mode_info: smithay_client_toolkit::output::Mode) -> Self {
-        Self {
-            dimensions: mode_info.dimensions,
-            refresh_rate: mode_info.refresh_rate,
-            is_current: mode_info.is_current,
-            is_preferred: mode_info.is_preferred,
-        }
-    }
-}
-*/
-impl From<(crate::definitions::SeatId, smithay_client_toolkit::seat::SeatData)> for crate::definitions::SeatInfo {
-    fn from(seat: (crate::definitions::SeatId, smithay_client_toolkit::seat::SeatData)) -> Self {
-        Self {
-            id: seat.0,
-            name: seat.1.name,
-            has_pointer: seat.1.has_pointer,
-            has_keyboard: seat.1.has_keyboard,
-            has_touch: seat.1.has_touch,
+            _ => Self::Normal,
         }
     }
 }
-
-/*
-use keyboard_types::Code;
-pub fn keysym_to_code(keysym: xkbcommon::xkb::Keysym)->Option<Code>{
-    match keysym {
-        keysyms::XKB_KEY_A=>Some(Code::KeyA),
-        _=>None
-    }
-
-}*/
@@ -1,47 +1,130 @@
 use super::DispatchContext;
-use crate::backends::linux::keysym_to_w3c_keycode;
+use crate::backends::linux::XkbKeyboard;
 use smithay_client_toolkit::reexports::client::{
     protocol::{wl_keyboard, wl_keyboard::WlKeyboard},
     Main,
 };
 
 use crate::definitions::*;
+use log::error;
+use std::os::unix::io::RawFd;
 
-pub fn handle_keyboard<S: 'static>(id: crate::definitions::SeatId, keyboard: Main<WlKeyboard>) {
-    keyboard.quick_assign(move |_handle, event, mut dispatch_data| {
-        let dispatch_context = dispatch_data.get::<DispatchContext<S>>().unwrap();
-        match event {
-            wl_keyboard::Event::Key {
-                serial: _,
-                time: _,
-                key,
-                state: _,
-            } => {
-                let keystrokes = dispatch_context.keystroke_decoder.decode(key);
-                for (keysym, direction) in keystrokes.as_keysyms() {
-                    let key = if let Some(key) = keysym_to_w3c_keycode(keysym) {
-                        key
-                    } else {
-                        continue;
-                    };
-
-                    let event = match direction {
-                        keystroke_decoder::KeyDirection::Up => SeatEvent::Keyboard(KeyboardEvent::KeyRelease{key}),
-                        keystroke_decoder::KeyDirection::Down => SeatEvent::Keyboard(KeyboardEvent::KeyPress{key}),
-                    };
+/**
+Read the keymap shared by the compositor.
+It has to be mapped privately, since the file may be shared with the other clients, and is closed afterwards.
+*/
+fn read_keymap(fd: RawFd, size: u32) -> Option<String> {
+    let size = size as usize;
+    let text = unsafe {
+        let address = libc::mmap(std::ptr::null_mut(), size, libc::PROT_READ, libc::MAP_PRIVATE, fd, 0);
+        libc::close(fd);
+        if address == libc::MAP_FAILED {return None;}
+        let bytes = std::slice::from_raw_parts(address as *const u8, size);
+        let text = String::from_utf8_lossy(bytes).into_owned();
+        libc::munmap(address, size);
+        text
+    };
+    Some(text)
+}
 
-                    dispatch_context.events.push(Event::Seat { id, event });
+pub fn handle_keyboard(id: SeatId, keyboard: &Main<WlKeyboard>) {
+    keyboard.quick_assign(move |_keyboard, event, mut dispatch_data| {
+        let dispatch_context = match dispatch_data.get::<DispatchContext>() {
+            Some(dispatch_context)=>dispatch_context,
+            None=>return
+        };
+        let seat_state = match dispatch_context.seats.get_mut(&id) {
+            Some(seat_state)=>seat_state,
+            None=>return
+        };
+        match event {
+            wl_keyboard::Event::Keymap{format, fd, size} => {
+                if format != wl_keyboard::KeymapFormat::XkbV1 {
+                    unsafe {libc::close(fd);}
+                    error!("Unsupported keymap format: {:?}",format);
+                    return;
+                }
+                let xkb_keyboard = match read_keymap(fd,size).map(|text|XkbKeyboard::from_string(&text)) {
+                    Some(Ok(xkb_keyboard))=>xkb_keyboard,
+                    Some(Err(err))=>{
+                        error!("Failed to compile the keymap of the compositor: {:?}",err);
+                        return;
+                    }
+                    None=>{
+                        error!("Failed to map the keymap of the compositor");
+                        return;
+                    }
+                };
+                // The compositor keymap replaces a local one, as for the X server.
+                let time = EventTime::now();
+                let keymap = xkb_keyboard.keymap(KeymapDescriptor::default());
+                seat_state.xkb_keyboard = Some(xkb_keyboard);
+                seat_state.local_keymap = false;
+                let event = SeatEvent::Keyboard(KeyboardEvent::KeymapChanged{keymap});
+                dispatch_context.events.push(Event::Seat{time,id,event});
+            }
+            wl_keyboard::Event::Enter{surface, ..} => {
+                let time = EventTime::now();
+                let id = SurfaceId::from(surface.as_ref().id());
+                let event = SurfaceEvent::Focused(true);
+                dispatch_context.events.push(Event::Surface{time,id,event});
+            }
+            wl_keyboard::Event::Leave{surface, ..} => {
+                dispatch_context.compose_state.reset();
+                let time = EventTime::now();
+                // The releases happening while unfocused are not reported, so the local state could keep modifiers held.
+                if seat_state.local_keymap {
+                    if let Some(xkb_keyboard) = &mut seat_state.xkb_keyboard {
+                        xkb_keyboard.release_keys();
+                        let event = SeatEvent::Keyboard(xkb_keyboard.modifiers_event());
+                        dispatch_context.events.push(Event::Seat{time,id,event});
+                    }
+                }
+                let id = SurfaceId::from(surface.as_ref().id());
+                let event = SurfaceEvent::Focused(false);
+                dispatch_context.events.push(Event::Surface{time,id,event});
+            }
+            wl_keyboard::Event::Key{serial, time, key, state} => {
+                let xkb_keyboard = match &mut seat_state.xkb_keyboard {
+                    Some(xkb_keyboard)=>xkb_keyboard,
+                    None=>return
+                };
+                let time = dispatch_context.timestamps.map(time);
+                let state = match state {
+                    wl_keyboard::KeyState::Pressed=>State::Down,
+                    _=>State::Up
+                };
+                let (depressed,latched,locked) = xkb_keyboard.mods();
+                let control = xkb_keyboard.modifiers(depressed | latched | locked).contains(Modifiers::CONTROL);
+                let event = crate::backends::linux::handle_keyboard(
+                    xkb_keyboard,
+                    &mut dispatch_context.compose_state,
+                    id,
+                    key,
+                    state,
+                    serial,
+                    time,
+                    false,
+                    control
+                );
+                dispatch_context.events.push(event);
+                if seat_state.local_keymap && xkb_keyboard.update_key(key,state) {
+                    let event = SeatEvent::Keyboard(xkb_keyboard.modifiers_event());
+                    dispatch_context.events.push(Event::Seat{time,id,event});
                 }
             }
-            /*
-            Event::Modifiers {
-                serial: u32,
-                mods_depressed: u32,
-                mods_latched: u32,
-                mods_locked: u32,
-                group: u32,
-            },
-            */
+            // A local keymap has its own state, driven by the key events.
+            wl_keyboard::Event::Modifiers{mods_depressed, mods_latched, mods_locked, group, ..} if !seat_state.local_keymap => {
+                if let Some(xkb_keyboard) = &mut seat_state.xkb_keyboard {
+                    xkb_keyboard.update_mask(mods_depressed,mods_latched,mods_locked,0,0,group);
+                    let event = SeatEvent::Keyboard(xkb_keyboard.modifiers_event());
+                    dispatch_context.events.push(Event::Seat{time: EventTime::now(),id,event});
+                }
+            }
+            wl_keyboard::Event::RepeatInfo{rate, delay} => {
+                let event = SeatEvent::Keyboard(KeyboardEvent::AutoRepeat{rate: rate.max(0) as u32,delay: delay.max(0) as u32});
+                dispatch_context.events.push(Event::Seat{time: EventTime::now(),id,event});
+            }
             _ => {}
         }
     });
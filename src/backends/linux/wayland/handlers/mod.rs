@@ -4,4 +4,7 @@ pub use keyboard::handle_keyboard;
 mod pointer;
 pub use pointer::handle_pointer;
 
+mod text_input;
+pub use text_input::{ImeState,TextInput};
+
 use super::DispatchContext;
@@ -1,5 +1,5 @@
 use super::DispatchContext;
-use crate::backends::linux::keysym_to_button;
+use crate::backends::linux::evdev_to_button;
 use keyboard_types::KeyState;
 use smithay_client_toolkit::reexports::client::{
     protocol::{
@@ -10,81 +10,93 @@ use smithay_client_toolkit::reexports::client::{
 };
 
 use crate::definitions::*;
-pub fn handle_pointer<S: 'static>(id: crate::definitions::SeatId, pointer: Main<WlPointer>) {
-    pointer.quick_assign(move |_handle, event, mut dispatch_data| {
-        let dispatch_context = dispatch_data.get::<DispatchContext<S>>().unwrap();
-        match event {
-            wl_pointer::Event::Enter {
-                serial: _,
-                surface,
-                surface_x,
-                surface_y,
-            } => {
-                let event = SeatEvent::Cursor(CursorEvent::Entered {
-                    surface_id: surface.as_ref().id().into(),
-                    position: (surface_x, surface_y),
-                });
-                dispatch_context.events.push(Event::Seat { id, event });
-            }
 
-            wl_pointer::Event::Leave { serial: _, surface } => {
-                let event = SeatEvent::Cursor(CursorEvent::Left {
-                    surface_id: surface.as_ref().id().into(),
-                });
-                dispatch_context.events.push(Event::Seat { id, event });
+/// Scroll values of a pointer frame, the discrete ones take the place of the continuous ones of the same axis.
+#[derive(Default)]
+struct PendingAxis {
+    time: Option<EventTime>,
+    continuous: [Option<f64>; 2],
+    discrete: [Option<i32>; 2],
+}
+
+fn axis_index(axis: Axis) -> Option<usize> {
+    match axis {
+        Axis::VerticalScroll => Some(0),
+        Axis::HorizontalScroll => Some(1),
+        _ => None,
+    }
+}
+
+pub fn handle_pointer(id: SeatId, pointer: &Main<WlPointer>) {
+    // Before the version 5 there are no frames, the scroll values are reported right away.
+    let framed = pointer.as_ref().version() >= 5;
+    let mut pending_axis = PendingAxis::default();
+    pointer.quick_assign(move |_pointer, event, mut dispatch_data| {
+        let dispatch_context = match dispatch_data.get::<DispatchContext>() {
+            Some(dispatch_context)=>dispatch_context,
+            None=>return
+        };
+        let flush_axis = match event {
+            wl_pointer::Event::Enter{surface, surface_x, surface_y, ..} => {
+                let time = EventTime::now();
+                let surface_id = SurfaceId::from(surface.as_ref().id());
+                let position = Position2D::from((surface_x as i32,surface_y as i32));
+                let event = SeatEvent::Cursor(CursorEvent::Entered{surface_id,position});
+                dispatch_context.events.push(Event::Seat{time,id,event});
+                false
             }
-            wl_pointer::Event::Motion {
-                time: _,
-                surface_x,
-                surface_y,
-            } => {
-                let event = SeatEvent::Cursor(CursorEvent::Moved {
-                    position: (surface_x, surface_y),
-                });
-                dispatch_context.events.push(Event::Seat { id, event });
+            wl_pointer::Event::Leave{surface, ..} => {
+                let time = EventTime::now();
+                let surface_id = SurfaceId::from(surface.as_ref().id());
+                let event = SeatEvent::Cursor(CursorEvent::Left{surface_id});
+                dispatch_context.events.push(Event::Seat{time,id,event});
+                false
             }
-            wl_pointer::Event::Button {
-                serial: _,
-                time: _,
-                button,
-                state: _,
-            } => {
-                let keystrokes = dispatch_context.keystroke_decoder.decode(button);
-                for (keysym, direction) in keystrokes.as_keysyms() {
-                    let key = if let Some(key) = keysym_to_button(keysym) {
-                        key
-                    } else {
-                        continue;
-                    };
-
-                    let state = match direction {
-                        keystroke_decoder::KeyDirection::Up => KeyState::Up,
-                        keystroke_decoder::KeyDirection::Down => KeyState::Down,
-                    };
-                    let event = SeatEvent::Cursor(CursorEvent::Button { key, state });
-                    dispatch_context.events.push(Event::Seat { id, event });
-                }
+            wl_pointer::Event::Motion{time, surface_x, surface_y} => {
+                let time = dispatch_context.timestamps.map(time);
+                let position = Position2D::from((surface_x as i32,surface_y as i32));
+                let event = SeatEvent::Cursor(CursorEvent::AbsoluteMovement{position});
+                dispatch_context.events.push(Event::Seat{time,id,event});
+                false
+            }
+            wl_pointer::Event::Button{time, button, state, ..} => {
+                let time = dispatch_context.timestamps.map(time);
+                let state = match state {
+                    wl_pointer::ButtonState::Pressed => KeyState::Down,
+                    _ => KeyState::Up,
+                };
+                let event = SeatEvent::Cursor(CursorEvent::Button{code: button,key: evdev_to_button(button),state});
+                dispatch_context.events.push(Event::Seat{time,id,event});
+                false
             }
-            wl_pointer::Event::Axis {
-                time: _,
-                axis,
-                value,
-            } => match axis {
-                Axis::VerticalScroll => {
-                    let event = SeatEvent::Cursor(CursorEvent::Axis {
-                        value: (value, 0.0),
-                    });
-                    dispatch_context.events.push(Event::Seat { id, event });
+            wl_pointer::Event::Axis{time, axis, value} => {
+                if let Some(index) = axis_index(axis) {
+                    pending_axis.time = Some(dispatch_context.timestamps.map(time));
+                    pending_axis.continuous[index] = Some(value);
                 }
-                Axis::HorizontalScroll => {
-                    let event = SeatEvent::Cursor(CursorEvent::Axis {
-                        value: (0.0, value),
-                    });
-                    dispatch_context.events.push(Event::Seat { id, event });
+                !framed
+            }
+            wl_pointer::Event::AxisDiscrete{axis, discrete} => {
+                if let Some(index) = axis_index(axis) {
+                    pending_axis.discrete[index] = Some(discrete);
                 }
-                _ => (),
-            },
-            _ => {}
+                !framed
+            }
+            wl_pointer::Event::Frame => true,
+            _ => false
+        };
+        if !flush_axis {return;}
+
+        let pending_axis = std::mem::take(&mut pending_axis);
+        let time = pending_axis.time.unwrap_or_else(EventTime::now);
+        for (index,direction) in [AxisDirection::Vertical,AxisDirection::Horizontal].iter().enumerate() {
+            let value = match (pending_axis.discrete[index],pending_axis.continuous[index]) {
+                (Some(discrete),_) => AxisValue::Discrete(discrete),
+                (None,Some(continuous)) => AxisValue::Continuous(continuous as f32),
+                (None,None) => continue
+            };
+            let event = SeatEvent::Cursor(CursorEvent::Axis{source: AxisSource::Wheel,direction: *direction,value});
+            dispatch_context.events.push(Event::Seat{time,id,event});
         }
     });
 }
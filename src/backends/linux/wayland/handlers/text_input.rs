@@ -0,0 +1,135 @@
+use super::DispatchContext;
+use smithay_client_toolkit::reexports::{
+    client::{protocol::wl_seat::WlSeat, Main},
+    protocols::unstable::text_input::v3::client::{
+        zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+        zwp_text_input_v3,
+        zwp_text_input_v3::{ContentHint, ContentPurpose, ZwpTextInputV3},
+    },
+};
+
+use crate::definitions::*;
+
+/// Input method state of a surface, kept while it is unfocused.
+#[derive(Default)]
+pub struct ImeState {
+    pub enabled: bool,
+    pub cursor_area: Option<Rectangle<i32,u32>>,
+}
+
+/**
+Text input of a seat.
+The compositor sends the changes in batches, which are only applied on `done`.
+*/
+pub struct TextInput {
+    id: SeatId,
+    text_input: Main<ZwpTextInputV3>,
+    /// Surface focused by the text input, whether the input method is enabled on it or not.
+    pub focus: Option<SurfaceId>,
+    pending_preedit: Option<(String,Option<(usize,usize)>)>,
+    pending_commit: Option<String>,
+    /// Whether a pre-edit text has been reported, so that it is cleared once the input method drops it.
+    preedit_shown: bool,
+}
+impl TextInput {
+    pub fn new(id: SeatId, manager: &ZwpTextInputManagerV3, seat: &WlSeat) -> Self {
+        let text_input = manager.get_text_input(seat);
+        handle_text_input(id,&text_input);
+        Self {
+            id,
+            text_input,
+            focus: None,
+            pending_preedit: None,
+            pending_commit: None,
+            preedit_shown: false
+        }
+    }
+
+    pub fn enable(&mut self, cursor_area: Option<&Rectangle<i32,u32>>) {
+        self.text_input.enable();
+        self.text_input.set_content_type(ContentHint::None,ContentPurpose::Normal);
+        if let Some(cursor_area) = cursor_area {self.set_cursor_rectangle(cursor_area);}
+        self.text_input.commit();
+    }
+
+    pub fn disable(&mut self, events: &mut Vec<Event>) {
+        self.text_input.disable();
+        self.text_input.commit();
+        self.pending_preedit = None;
+        self.pending_commit = None;
+        if std::mem::take(&mut self.preedit_shown) {
+            let event = SeatEvent::Keyboard(KeyboardEvent::Preedit{text: String::new(),cursor: None});
+            events.push(Event::Seat{time: EventTime::now(),id: self.id,event});
+        }
+    }
+
+    pub fn set_cursor_area(&mut self, cursor_area: &Rectangle<i32,u32>) {
+        self.set_cursor_rectangle(cursor_area);
+        self.text_input.commit();
+    }
+
+    pub fn destroy(&self) {
+        self.text_input.destroy();
+    }
+
+    fn set_cursor_rectangle(&self, cursor_area: &Rectangle<i32,u32>) {
+        self.text_input.set_cursor_rectangle(
+            cursor_area.position.x,
+            cursor_area.position.y,
+            cursor_area.size.width.min(i32::MAX as u32) as i32,
+            cursor_area.size.height.min(i32::MAX as u32) as i32
+        );
+    }
+
+    /// Apply a batch of changes, the committed text comes before the pre-edit text that follows it.
+    fn done(&mut self, events: &mut Vec<Event>) {
+        let time = EventTime::now();
+        if let Some(text) = self.pending_commit.take() {
+            let event = SeatEvent::Keyboard(KeyboardEvent::Commit{text});
+            events.push(Event::Seat{time,id: self.id,event});
+        }
+        // A batch without pre-edit clears the current one.
+        let (text,cursor) = self.pending_preedit.take().unwrap_or_default();
+        if text.is_empty() && !self.preedit_shown {return;}
+        self.preedit_shown = !text.is_empty();
+        let event = SeatEvent::Keyboard(KeyboardEvent::Preedit{text,cursor});
+        events.push(Event::Seat{time,id: self.id,event});
+    }
+}
+
+fn handle_text_input(id: SeatId, text_input: &Main<ZwpTextInputV3>) {
+    text_input.quick_assign(move |_text_input, event, mut dispatch_data| {
+        let DispatchContext{events,seats,ime_surfaces,..} = match dispatch_data.get::<DispatchContext>() {
+            Some(dispatch_context)=>dispatch_context,
+            None=>return
+        };
+        let text_input = match seats.get_mut(&id).and_then(|seat_state|seat_state.text_input.as_mut()) {
+            Some(text_input)=>text_input,
+            None=>return
+        };
+        match event {
+            zwp_text_input_v3::Event::Enter{surface} => {
+                let surface_id = SurfaceId::from(surface.as_ref().id());
+                text_input.focus = Some(surface_id);
+                if let Some(ime_state) = ime_surfaces.get(&surface_id).filter(|ime_state|ime_state.enabled) {
+                    text_input.enable(ime_state.cursor_area.as_ref());
+                }
+            }
+            zwp_text_input_v3::Event::Leave{..} => {
+                text_input.focus = None;
+                text_input.disable(events);
+            }
+            zwp_text_input_v3::Event::PreeditString{text, cursor_begin, cursor_end} => {
+                // Negative positions hide the cursor.
+                let cursor = if cursor_begin < 0 || cursor_end < 0 {None} else {Some((cursor_begin as usize,cursor_end as usize))};
+                text_input.pending_preedit = Some((text.unwrap_or_default(),cursor));
+            }
+            zwp_text_input_v3::Event::CommitString{text} => {
+                text_input.pending_commit = text;
+            }
+            zwp_text_input_v3::Event::Done{..} => text_input.done(events),
+            // The surrounding text is not tracked, so there is nothing to delete.
+            _ => {}
+        }
+    });
+}
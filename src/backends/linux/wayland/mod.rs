@@ -3,355 +3,538 @@ mod handlers;
 use handlers::*;
 
 use crate::definitions::*;
+use crate::backends::timestamp::TimestampMapper;
+use crate::backends::linux::{XkbCompose,XkbKeyboard};
 use std::collections::HashMap;
+use log::error;
 use smithay_client_toolkit::{
-    environment::Environment,
+    environment::{Environment, SimpleGlobal},
     output::OutputStatusListener,
     reexports::client::{
-        protocol::{wl_seat::WlSeat, wl_surface::WlSurface, wl_output::WlOutput},
-        ConnectError, Display, EventQueue,
+        protocol::{wl_compositor::WlCompositor, wl_subcompositor::WlSubcompositor, wl_shm::WlShm},
+        protocol::{wl_keyboard::WlKeyboard, wl_pointer::WlPointer, wl_output::WlOutput, wl_seat::WlSeat},
+        Attached, Display, EventQueue, Main,
     },
-    seat::SeatListener,
+    reexports::protocols::unstable::text_input::v3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+    seat::{SeatData, SeatListener},
     shm::{AutoMemPool, Format},
-    window::FallbackFrame,
+    window::{Event as WindowEvent, FallbackFrame, Window},
 };
 
-use keystroke_decoder::KeystrokeDecoder;
-
-
-smithay_client_toolkit::default_environment!(WaylandEnv, desktop, fields = [], singles = []);
-
-
-#[derive(Default)]
-pub struct EventListeners {
-    seat: Option<SeatListener>,
-    output: Option<OutputStatusListener>,
+smithay_client_toolkit::default_environment!(WaylandEnv, desktop,
+    fields=[text_input_manager: SimpleGlobal<ZwpTextInputManagerV3>],
+    singles=[ZwpTextInputManagerV3 => text_input_manager]
+);
+
+/// Devices of a seat, along with the keyboard state decoding its keys.
+pub struct SeatState {
+    pub keyboard: Option<Main<WlKeyboard>>,
+    pub pointer: Option<Main<WlPointer>>,
+    /// Keyboard state, available once the compositor has sent the keymap.
+    pub xkb_keyboard: Option<XkbKeyboard>,
+    /**
+    Whether the keymap has been replaced by `ModifyLayout`.
+    The state is then driven by the key events, otherwise it mirrors the compositor one.
+    */
+    pub local_keymap: bool,
+    /// Input method of the seat, when the compositor supports the text input protocol.
+    pub text_input: Option<TextInput>,
 }
 
-pub struct DispatchContext<S> {
-    pub events: Vec<Event<S>>,
-    pub outputs: HashMap<OutputId, WlOutput>,
-    pub seats: HashMap<SeatId, WlSeat>,
-    pub surfaces: HashMap<SurfaceId, WlSurface>,
-    pub keystroke_decoder: KeystrokeDecoder,
+/// State shared with the callbacks of the wayland objects, through the dispatch data.
+pub struct DispatchContext {
+    pub events: Vec<crate::definitions::Event>,
+    pub seats: HashMap<SeatId,SeatState>,
+    pub outputs: HashMap<OutputId,(WlOutput,OutputInfo)>,
+    pub compose_state: XkbCompose,
+    pub timestamps: TimestampMapper,
+    /// Window events, handled once the dispatch is over since they need the windows.
+    pub window_events: Vec<(SurfaceId,WindowEvent)>,
+    pub text_input_manager: Option<Attached<ZwpTextInputManagerV3>>,
+    /// Input method state of the surfaces, applied to the text inputs entering them.
+    pub ime_surfaces: HashMap<SurfaceId,ImeState>,
 }
 
-pub struct WaylandPlatform<S> {
+pub struct WaylandPlatform {
     environment: Environment<WaylandEnv>,
-    event_listeners: EventListeners,
+    display: Display,
     event_queue: EventQueue,
-
+    _seat_listener: SeatListener,
+    _output_listener: OutputStatusListener,
     shm_memory_pool: AutoMemPool,
-    dispatch_context: DispatchContext<S>,
-    display: Display,
+    windows: HashMap<SurfaceId,Window<FallbackFrame>>,
+    dispatch_context: DispatchContext,
+    external_contexts: Vec<Box<dyn ExternalContext>>,
 }
 
-impl<S: 'static> WaylandPlatform<S> {
-    pub fn new() -> Result<Self, ConnectError> {
-        let wayland_environment =
-            smithay_client_toolkit::new_default_environment!(WaylandEnv, desktop);
-        let (environment, display, event_queue) = match wayland_environment {
-            Ok(wayland_environment) => wayland_environment,
-            Err(err) => return Err(err),
-        };
+impl WaylandPlatform {
+    /**
+    Connect to the compositor, checking the globals needed to create the windows.
+    The external contexts are only taken once connected, so that they are left to the next backend otherwise.
+    */
+    pub fn new(external_contexts: &mut Option<Vec<Box<dyn ExternalContext>>>) -> Result<Self, PlatformError> {
+        let display = Display::connect_to_env().map_err(|err|PlatformError::Connection(err.to_string()))?;
+        let mut event_queue = display.create_event_queue();
+        let environment = smithay_client_toolkit::new_default_environment!(WaylandEnv, desktop,
+            with=(display, event_queue),
+            fields=[text_input_manager: SimpleGlobal::new()]
+        )
+            .map_err(|err|match display.protocol_error() {
+                Some(protocol_error)=>PlatformError::Protocol(protocol_error.to_string()),
+                None=>PlatformError::Connection(err.to_string())
+            })?;
+
+        if environment.get_global::<WlCompositor>().is_none() {
+            return Err(PlatformError::MissingExtension(String::from("wl_compositor")));
+        }
+        if environment.get_global::<WlSubcompositor>().is_none() {
+            return Err(PlatformError::MissingExtension(String::from("wl_subcompositor")));
+        }
+        if environment.get_global::<WlShm>().is_none() {
+            return Err(PlatformError::MissingExtension(String::from("wl_shm")));
+        }
+        if environment.get_shell().is_none() {
+            return Err(PlatformError::MissingExtension(String::from("xdg_wm_base")));
+        }
+        let shm_memory_pool = environment.create_auto_pool()?;
 
-        let dispatch_context = DispatchContext {
+        let mut dispatch_context = DispatchContext {
             events: Vec::new(),
-            outputs: HashMap::new(),
             seats: HashMap::new(),
-            surfaces: HashMap::new(),
-            keystroke_decoder: KeystrokeDecoder::new(),
+            outputs: HashMap::new(),
+            compose_state: XkbCompose::from_locale(),
+            timestamps: TimestampMapper::new(),
+            window_events: Vec::new(),
+            text_input_manager: environment.get_global::<ZwpTextInputManagerV3>(),
+            ime_surfaces: HashMap::new(),
         };
 
-        let event_listeners = EventListeners::default();
+        for seat in environment.get_all_seats() {
+            if let Some(seat_data) = smithay_client_toolkit::seat::with_seat_data(&seat, |seat_data|seat_data.clone()) {
+                update_seat(&mut dispatch_context, &seat, &seat_data);
+            }
+        }
+        let seat_listener = environment.listen_for_seats(|seat, seat_data, mut dispatch_data|{
+            if let Some(dispatch_context) = dispatch_data.get::<DispatchContext>() {
+                update_seat(dispatch_context, &seat, seat_data);
+            }
+        });
 
-        let shm_memory_pool = environment.create_auto_pool().unwrap();
+        for output in environment.get_all_outputs() {
+            if let Some(Some(output_info)) = smithay_client_toolkit::output::with_output_info(&output, |output_info|{
+                if output_info.obsolete {None} else {conv::output_info(output_info)}
+            }) {
+                add_output(&mut dispatch_context, output, output_info);
+            }
+        }
+        let output_listener = environment.listen_for_outputs(|output, output_info, mut dispatch_data|{
+            if let Some(dispatch_context) = dispatch_data.get::<DispatchContext>() {
+                update_output(dispatch_context, output, output_info);
+            }
+        });
 
-        let mut backend = Self {
+        Ok(Self {
             environment,
             display,
             event_queue,
-            event_listeners,
+            _seat_listener: seat_listener,
+            _output_listener: output_listener,
             shm_memory_pool,
+            windows: HashMap::new(),
             dispatch_context,
-        };
-
-        backend.init_seat_listener();
-        backend.init_output_listener();
-
-        Ok(backend)
+            external_contexts: external_contexts.take().unwrap_or_default(),
+        })
     }
 
-    fn init_seat_listener(&mut self) {
-        if self.event_listeners.seat.is_none() {
-            for seat in self.environment.get_all_seats() {
-                let id = seat.as_ref().id().into();
-                smithay_client_toolkit::seat::with_seat_data(&seat, |seat_data| {
-                    let seat = seat.detach();
-                    if !seat_data.defunct {
-                        self.dispatch_context.seats.insert(id, seat);
-
-                        let event = SeatEvent::Added((id, seat_data.clone()).into());
-                        self.dispatch_context.events.push(Event::Seat { id, event });
-                    }
-                });
+    /// Read the pending events from the socket, without blocking, and run their callbacks.
+    fn dispatch(&mut self) -> Result<(), PlatformError> {
+        if let Err(err) = self.display.flush() {
+            if err.kind() != std::io::ErrorKind::WouldBlock {return Err(err.into());}
+        }
+        if let Some(guard) = self.event_queue.prepare_read() {
+            if let Err(err) = guard.read_events() {
+                if err.kind() != std::io::ErrorKind::WouldBlock {return Err(err.into());}
             }
-            self.event_listeners.seat = Some(self.environment.listen_for_seats(
-                |seat, data, mut dispatch_data| {
-                    let dispatch_context = dispatch_data.get::<DispatchContext<S>>().unwrap();
-                    let seat = seat.detach();
-                    let id = seat.as_ref().id().into();
-
-                    if data.defunct {
-                        seat.release();
-                        dispatch_context.seats.remove(&id);
-                        let event = SeatEvent::Removed;
-                        dispatch_context.events.push(Event::Seat { id, event });
-                    } else if dispatch_context.seats.contains_key(&id) {
-                        let current_data: SeatInfo =
-                            smithay_client_toolkit::seat::with_seat_data(&seat, |seat_data| {
-                                (id, seat_data.clone()).into()
-                            })
-                            .unwrap();
-                        match (current_data.has_pointer, data.has_pointer) {
-                            (false, true) => {
-                                let event = SeatEvent::Changed(SeatCapability::PointerAdded);
-                                dispatch_context.events.push(Event::Seat { id, event });
-                            }
-                            (true, false) => {
-                                let event = SeatEvent::Changed(SeatCapability::PointerRemoved);
-                                dispatch_context.events.push(Event::Seat { id, event });
-                            }
-                            _ => (),
-                        }
-                        match (current_data.has_keyboard, data.has_keyboard) {
-                            (false, true) => {
-                                let keyboard = seat.get_keyboard();
-                                handle_keyboard::<S>(id, keyboard);
-                                let event = SeatEvent::Changed(SeatCapability::KeyboardAdded);
-                                dispatch_context.events.push(Event::Seat { id, event });
-                            }
-                            (true, false) => {
-                                let event = SeatEvent::Changed(SeatCapability::KeyboardRemoved);
-                                dispatch_context.events.push(Event::Seat { id, event });
-                            }
-                            _ => (),
-                        }
-                        match (current_data.has_touch, data.has_touch) {
-                            (false, true) => {
-                                let event = SeatEvent::Changed(SeatCapability::TouchAdded);
-                                dispatch_context.events.push(Event::Seat { id, event });
-                            }
-                            (true, false) => {
-                                let event = SeatEvent::Changed(SeatCapability::TouchRemoved);
-                                dispatch_context.events.push(Event::Seat { id, event });
-                            }
-                            _ => (),
+        }
+        self.event_queue.dispatch_pending(&mut self.dispatch_context, |event, object, _dispatch_data|{
+            error!("Unhandled event {}@{}.{}",object.as_ref().id(),event.interface,event.name);
+        }).map_err(|err|match self.display.protocol_error() {
+            Some(protocol_error)=>PlatformError::Protocol(protocol_error.to_string()),
+            None=>PlatformError::from(err)
+        })?;
+        Ok(())
+    }
+
+    fn handle_window_events(&mut self) {
+        for (id,event) in std::mem::take(&mut self.dispatch_context.window_events) {
+            match event {
+                WindowEvent::Configure{new_size,..}=>{
+                    if let Some(window) = self.windows.get_mut(&id) {
+                        if let Some((width,height)) = new_size {
+                            window.resize(width,height);
+                            let event = SurfaceEvent::Resized(Size2D{width,height});
+                            self.dispatch_context.events.push(crate::definitions::Event::Surface{time: EventTime::now(),id,event});
                         }
-                    } else {
-                        dispatch_context.seats.insert(id, seat);
+                        window.refresh();
                     }
-                },
-            ));
+                }
+                WindowEvent::Close=>{
+                    if let Err(err) = self.destroy_surface(id) {error!("Failed to close the surface: {}",err);}
+                }
+                WindowEvent::Refresh=>{
+                    if let Some(window) = self.windows.get_mut(&id) {window.refresh();}
+                }
+            }
         }
     }
+}
 
-    fn init_output_listener(&mut self) {
-        if self.event_listeners.output.is_none() {
-            for output in self.environment.get_all_outputs() {
-                smithay_client_toolkit::output::with_output_info(&output, |output_info| {
-                    if !output_info.obsolete {
-                        unimplemented!();
-                        /*
-                        let id = output_info.id.into();
-                        let event = OutputEvent::Added {};
-                        self.dispatch_context.events.push(Event::Output{id,event});
-                        */
-                    }
-                });
+/// Report the seat and the devices it gained or lost.
+fn update_seat(dispatch_context: &mut DispatchContext, seat: &Attached<WlSeat>, seat_data: &SeatData) {
+    let time = EventTime::now();
+    let id = SeatId::from(seat.as_ref().id());
+    if seat_data.defunct {
+        if let Some(seat_state) = dispatch_context.seats.remove(&id) {
+            if let Some(keyboard) = seat_state.keyboard {
+                release_keyboard(&keyboard);
+                let event = SeatEvent::Keyboard(KeyboardEvent::Removed);
+                dispatch_context.events.push(crate::definitions::Event::Seat{time,id,event});
+            }
+            if let Some(pointer) = seat_state.pointer {
+                release_pointer(&pointer);
+                let event = SeatEvent::Cursor(CursorEvent::Removed);
+                dispatch_context.events.push(crate::definitions::Event::Seat{time,id,event});
+            }
+            if let Some(text_input) = seat_state.text_input {text_input.destroy();}
+            dispatch_context.events.push(crate::definitions::Event::Seat{time,id,event: SeatEvent::Removed});
+        }
+        return;
+    }
+
+    if !dispatch_context.seats.contains_key(&id) {
+        let event = SeatEvent::Added{name: seat_data.name.clone()};
+        dispatch_context.events.push(crate::definitions::Event::Seat{time,id,event});
+        let text_input = dispatch_context.text_input_manager.as_ref().map(|manager|TextInput::new(id,manager,seat));
+        dispatch_context.seats.insert(id,SeatState{keyboard: None,pointer: None,xkb_keyboard: None,local_keymap: false,text_input});
+    }
+    let seat_state = match dispatch_context.seats.get_mut(&id) {
+        Some(seat_state)=>seat_state,
+        None=>return
+    };
+
+    match (seat_state.keyboard.take(),seat_data.has_keyboard) {
+        (None,true)=>{
+            let keyboard = seat.get_keyboard();
+            handle_keyboard(id,&keyboard);
+            seat_state.keyboard = Some(keyboard);
+            let keyboard_info = KeyboardInfo {
+                keymap: KeymapDescriptor::default(),
+                // The compositor only sends the repeat parameters, the keys are repeated by the clients.
+                autorepeat: false,
+                encoding: KeyEncoding::XkbV1
+            };
+            let event = SeatEvent::Keyboard(KeyboardEvent::Added(keyboard_info));
+            dispatch_context.events.push(crate::definitions::Event::Seat{time,id,event});
+        }
+        (Some(keyboard),false)=>{
+            release_keyboard(&keyboard);
+            seat_state.xkb_keyboard = None;
+            seat_state.local_keymap = false;
+            let event = SeatEvent::Keyboard(KeyboardEvent::Removed);
+            dispatch_context.events.push(crate::definitions::Event::Seat{time,id,event});
+        }
+        (keyboard,_)=>seat_state.keyboard = keyboard
+    }
+
+    match (seat_state.pointer.take(),seat_data.has_pointer) {
+        (None,true)=>{
+            let pointer = seat.get_pointer();
+            handle_pointer(id,&pointer);
+            seat_state.pointer = Some(pointer);
+            let cursor_info = CursorInfo {
+                mode: CursorMode::Absolute,
+                theme: CursorImage::Default,
+                visible: true
+            };
+            let event = SeatEvent::Cursor(CursorEvent::Added(cursor_info));
+            dispatch_context.events.push(crate::definitions::Event::Seat{time,id,event});
+        }
+        (Some(pointer),false)=>{
+            release_pointer(&pointer);
+            let event = SeatEvent::Cursor(CursorEvent::Removed);
+            dispatch_context.events.push(crate::definitions::Event::Seat{time,id,event});
+        }
+        (pointer,_)=>seat_state.pointer = pointer
+    }
+}
+
+/// The release requests only exist since the version 3 of the seat.
+fn release_keyboard(keyboard: &Main<WlKeyboard>) {
+    if keyboard.as_ref().version() >= 3 {keyboard.release();}
+}
+fn release_pointer(pointer: &Main<WlPointer>) {
+    if pointer.as_ref().version() >= 3 {pointer.release();}
+}
+
+fn add_output(dispatch_context: &mut DispatchContext, output: WlOutput, output_info: OutputInfo) {
+    let id = OutputId::from(output.as_ref().id());
+    let event = OutputEvent::Added(output_info.clone());
+    dispatch_context.events.push(crate::definitions::Event::Output{time: EventTime::now(),id,event});
+    dispatch_context.outputs.insert(id,(output,output_info));
+}
+
+/// Report the outputs added or removed, and the position and mode changes of the known ones.
+fn update_output(dispatch_context: &mut DispatchContext, output: WlOutput, output_info: &smithay_client_toolkit::output::OutputInfo) {
+    let time = EventTime::now();
+    let id = OutputId::from(output.as_ref().id());
+    if output_info.obsolete {
+        if dispatch_context.outputs.remove(&id).is_some() {
+            if output.as_ref().version() >= 3 {output.release();}
+            dispatch_context.events.push(crate::definitions::Event::Output{time,id,event: OutputEvent::Removed});
+        }
+        return;
+    }
+    let output_info = match conv::output_info(output_info) {
+        Some(output_info)=>output_info,
+        None=>return
+    };
+    match dispatch_context.outputs.get_mut(&id) {
+        Some((_,current_info))=>{
+            if current_info.position != output_info.position {
+                let event = OutputEvent::Moved(output_info.position.clone());
+                dispatch_context.events.push(crate::definitions::Event::Output{time,id,event});
+            }
+            if current_info.selected_mode != output_info.selected_mode {
+                let event = OutputEvent::ModeChanged(output_info.selected_mode.clone());
+                dispatch_context.events.push(crate::definitions::Event::Output{time,id,event});
             }
-            self.event_listeners.output = Some(self.environment.listen_for_outputs(
-                |_handle, _data, mut _dispatch_data| {
-                    unimplemented!();
-                    /*
-                    let id = handle.as_ref().id().into();
-                    let event = if data.obsolete {
-                        handle.release();
-                        OutputEvent::Removed{}
-                    } else {
-                        OutputEvent::Added{}
-                    };
-                    dispatch_data
-                        .get::<super::DispatchContext>()
-                        .unwrap()
-                        .events
-                        .push(Event::Output{id,event});
-                    */
-                },
-            ));
+            *current_info = output_info;
         }
+        None=>add_output(dispatch_context,output,output_info)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl std::os::unix::io::AsRawFd for WaylandPlatform {
+    fn as_raw_fd(&self)->std::os::unix::io::RawFd {
+        self.display.get_connection_fd()
     }
 }
 
-impl<S> crate::definitions::InputBackend<S> for WaylandPlatform<S> {
-    fn events(&mut self) -> Vec<Event<S>> {
-        self.event_queue
-            .events(&mut (), |raw_event, anonymus_object, _events_data| {
-                panic!(
-                    "Unhandled event received:\n{:#?}\n{:#?}",
-                    raw_event, anonymus_object
-                );
-            })
-            .expect("An error occurred during event dispatching!");
+impl PlatformBackend for WaylandPlatform {
+    fn platform_type(&self)->PlatformType {PlatformType::Compositor}
+    fn events(&mut self) -> Vec<crate::definitions::Event> {
+        if let Err(err) = self.dispatch() {
+            error!("Failed to dispatch the wayland events: {}",err);
+        }
+        self.handle_window_events();
         self.dispatch_context.events.drain(..).collect()
     }
 
-    fn set_keyboard_layout(&mut self, layout: String) {
-        self.dispatch_context.keystroke_decoder.set_layout(layout);
+    fn requests(&mut self, requests: Vec<Request>) {
+        requests.into_iter().for_each(|request|{
+            let token = request.token();
+            let result = match request {
+                crate::definitions::Request::Seat{request: SeatRequest::Keyboard(keyboard_request),..}=>{
+                    match keyboard_request {
+                        KeyboardRequest::ModifyLayout{keymap}=>self.modify_layout(keymap),
+                        KeyboardRequest::SwitchGroup{group}=>self.switch_group(group),
+                        // The repeat parameters are chosen by the compositor.
+                        KeyboardRequest::SetAutoRepeat{..}=>Err(KeyRepeatError::Unsupported.into())
+                    }
+                }
+                crate::definitions::Request::Seat{request: SeatRequest::Cursor(cursor_request),..}=>{
+                    match cursor_request {
+                        CursorRequest::ChangeImage(_)=>Err(CursorImageError::Unsupported.into()),
+                        CursorRequest::ChangeMode(CursorMode::Absolute)=>Ok(()),
+                        CursorRequest::ChangeMode(CursorMode::Relative)=>Err(CursorModeError::Unsupported.into())
+                    }
+                }
+                crate::definitions::Request::Seat{request: SeatRequest::Touch(_touch_request),..}=>Ok(()),
+                crate::definitions::Request::Seat{request: SeatRequest::Gamepad(_gamepad_request),..}=>Ok(()),
+                crate::definitions::Request::Output{..}=>Ok(()),
+                crate::definitions::Request::Surface{request: SurfaceRequest::Create(output),..}=>self.create_surface(output),
+                crate::definitions::Request::Surface{request: SurfaceRequest::Destroy(surface_id),..}=>self.destroy_surface(surface_id),
+                crate::definitions::Request::Surface{request: SurfaceRequest::Commit(surface_id),..}=>{
+                    if self.windows.contains_key(&surface_id) {Ok(())} else {Err(SurfaceError::UnknownSurface.into())}
+                }
+                crate::definitions::Request::Surface{request: SurfaceRequest::SetImeEnabled{id,enabled},..}=>self.set_ime_enabled(id,enabled),
+                crate::definitions::Request::Surface{request: SurfaceRequest::SetImeCursorArea{id,area},..}=>self.set_ime_cursor_area(id,area)
+            };
+            match token {
+                Some(token)=>{
+                    let time = EventTime::now();
+                    let event = RequestEvent::from(result);
+                    self.dispatch_context.events.push(crate::definitions::Event::Request{time,token,event});
+                }
+                None=>{
+                    if let Err(err) = result {error!("Failed to handle request: {}",err);}
+                }
+            }
+        });
+        if let Err(err) = self.display.flush() {
+            if err.kind() != std::io::ErrorKind::WouldBlock {error!("Failed to flush the wayland requests: {}",err);}
+        }
     }
 }
 
-impl<S: 'static> crate::definitions::GraphicBackend for WaylandPlatform<S> {
-    fn create_surface(
-        &mut self,
-        output_id: Option<crate::definitions::OutputId>,
-    ) {
-        let width = 800;
-        let height = 600;
-
-        let buffer = self
-            .shm_memory_pool
-            .try_draw::<_, std::io::Error>(width, height, width * 4, Format::Argb8888, |bytes| {
-                Ok(bytes.iter_mut().for_each(|byte| *byte = 255))
-            })
-            .unwrap();
-        let surface = self.environment.create_surface();
-        let surface = surface.detach();
-        let surface_id = surface.as_ref().id().into();
-
-
-        let (x, y) = match output_id {
-            Some(output_id)=>{
-                let output_info = match self.dispatch_context.outputs.get(&output_id){
-                    Some(output)=>smithay_client_toolkit::output::with_output_info(&output, |output_info|output_info.clone()),
-                    None=>return
-                };
-
-                match output_info {
-                    Some(output)=>output.location,
-                    None=>(0,0)
+impl WaylandPlatform {
+    /**
+    Compile the whole descriptor before replacing the keymap of the seats, so that invalid keymaps are rejected.
+    From then on the state is tracked locally, starting from the current locks and the first group.
+    */
+    fn modify_layout(&mut self, descriptor: KeymapDescriptor) -> Result<(), RequestError> {
+        let keymap = XkbKeyboard::from_descriptor(&descriptor)?.keymap(descriptor.clone());
+        let time = EventTime::now();
+        for (id,seat_state) in self.dispatch_context.seats.iter_mut() {
+            if seat_state.keyboard.is_none() {continue;}
+            let mut xkb_keyboard = XkbKeyboard::from_descriptor(&descriptor)?;
+            if let Some(current) = &seat_state.xkb_keyboard {
+                let (_,_,locked) = current.mods();
+                xkb_keyboard.update_mask(0,0,locked,0,0,0);
+            }
+            let modifiers_event = xkb_keyboard.modifiers_event();
+            seat_state.xkb_keyboard = Some(xkb_keyboard);
+            seat_state.local_keymap = true;
+
+            let id = *id;
+            let event = SeatEvent::Keyboard(KeyboardEvent::LayoutModified{keymap: descriptor.clone()});
+            self.dispatch_context.events.push(crate::definitions::Event::Seat{time,id,event});
+            let event = SeatEvent::Keyboard(KeyboardEvent::KeymapChanged{keymap: keymap.clone()});
+            self.dispatch_context.events.push(crate::definitions::Event::Seat{time,id,event});
+            let event = SeatEvent::Keyboard(modifiers_event);
+            self.dispatch_context.events.push(crate::definitions::Event::Seat{time,id,event});
+        }
+        Ok(())
+    }
+
+    /// Lock the layout group, which is only possible with a local keymap since the compositor owns the group of its own.
+    fn switch_group(&mut self, group: u32) -> Result<(), RequestError> {
+        let time = EventTime::now();
+        for (id,seat_state) in self.dispatch_context.seats.iter_mut() {
+            let xkb_keyboard = match &mut seat_state.xkb_keyboard {
+                Some(xkb_keyboard) if seat_state.local_keymap=>xkb_keyboard,
+                Some(_)=>return Err(KeyboardLayoutError::Unsupported.into()),
+                None=>continue
+            };
+            xkb_keyboard.lock_group(group)?;
+            let event = SeatEvent::Keyboard(xkb_keyboard.modifiers_event());
+            self.dispatch_context.events.push(crate::definitions::Event::Seat{time,id: *id,event});
+        }
+        Ok(())
+    }
+
+    /**
+    Create a window, filled with white until a renderer attaches its own buffers.
+    Wayland surfaces have no global position, the compositor places them, so the output is only checked.
+    */
+    fn create_surface(&mut self, output: Option<OutputId>) -> Result<(), RequestError> {
+        if let Some(output) = output {
+            if !self.dispatch_context.outputs.contains_key(&output) {
+                return Err(SurfaceError::InvalidOutput.into());
+            }
+        }
+
+        let width = 400u32;
+        let height = 400u32;
+
+        let surface = self.environment.create_surface().detach();
+        let id = SurfaceId::from(surface.as_ref().id());
+        let mut window = self.environment.create_window::<FallbackFrame, _>(
+            surface,
+            None,
+            (width,height),
+            move |event, mut dispatch_data|{
+                if let Some(dispatch_context) = dispatch_data.get::<DispatchContext>() {
+                    dispatch_context.window_events.push((id,event));
                 }
             }
-            None=>(0,0)
+        ).map_err(PlatformError::from)?;
+
+        let buffer = self.shm_memory_pool.try_draw::<_, std::io::Error>(
+            width as i32,
+            height as i32,
+            width as i32 * 4,
+            Format::Argb8888,
+            |bytes|{
+                bytes.iter_mut().for_each(|byte|*byte = 255);
+                Ok(())
+            }
+        ).map_err(PlatformError::from)?;
+        window.surface().attach(Some(&buffer),0,0);
+
+        let wayland_handle = raw_window_handle::unix::WaylandHandle {
+            surface: window.surface().as_ref().c_ptr() as *mut std::ffi::c_void,
+            display: self.display.get_display_ptr() as *mut std::ffi::c_void,
+            ..raw_window_handle::unix::WaylandHandle::empty()
         };
+        let raw_surface_handle = RawSurfaceHandle::Wayland(wayland_handle);
+
+        let mut surface = Err(PlatformError::SurfaceCreation(SurfaceError::Unsupported));
+        for context in &self.external_contexts {
+            surface = context.create_surface(&raw_surface_handle);
+            if surface.is_ok() {break;}
+        }
+        let surface = surface?;
 
-        surface.attach(Some(&buffer), x, y);
-
-        let mut window = self
-            .environment
-            .create_window::<FallbackFrame, _>(
-                surface,
-                None,
-                (800, 600),
-                move |event, mut dispatch_data| {
-                    match event {
-                        smithay_client_toolkit::window::Event::Configure {
-                            new_size,
-                            states: _,
-                        } => {
-                            if let Some(size) = new_size {
-                                let event = SurfaceEvent::Resized(Size2D<u32>::from((size.0,size.1)));
-                                let id = surface_id;
-                                dispatch_data
-                                    .get::<DispatchContext<S>>()
-                                    .unwrap()
-                                    .events
-                                    .push(Event::Surface { id, event })
-                            }
-                            /*
-                                                states.iter().for_each(|state|{
-                                                    match state {
-                                                        smithay_client_toolkit::window::State::Maximized,
-                                                        smithay_client_toolkit::window::State::Fullscreen,
-                                                        smithay_client_toolkit::window::State::Resizing,
-                                                        smithay_client_toolkit::window::State::Activated,
-                                                        smithay_client_toolkit::window::State::TiledLeft=>,
-                                                        smithay_client_toolkit::window::State::TiledRight,
-                                                        smithay_client_toolkit::window::State::TiledTop,
-                                                        smithay_client_toolkit::window::State::TiledBottom,
-                                                    }
-                                                    dispatch_data.get::<super::DispatchContext>().unwrap().events.push(SurfaceEventInner::TilingChanged.into())
-                                                })
-
-                                                dispatch_data.get::<super::DispatchContext>().unwrap().events.push(SurfaceEventInner::Destroyed.into())
-                            */
-                        }
-                        smithay_client_toolkit::window::Event::Close => {
-                            let id = surface_id;
-                            let event = SurfaceEvent::Destroyed;
-                            dispatch_data
-                                .get::<DispatchContext<S>>()
-                                .unwrap()
-                                .events
-                                .push(Event::Surface { id, event })
-                        }
-                        smithay_client_toolkit::window::Event::Refresh => (),
-                    }
-                },
-            )
-            .unwrap();
         window.surface().commit();
         window.refresh();
-        unimplemented!();
-/*
-        let id = output_id;
-        let event = OutputEvent::Added{};
-        self.dispatch_context.events.push(Event::Output{id,event});
-        */
-
-/*
-        let surface = super::WaylandSurface { id: surface_id };
-        let linux_surface = super::LinuxSurface::from(surface);
-        self.dispatch_context
-            .surfaces
-            .insert(surface_id, window.surface().clone());
-
-        Ok(linux_surface.into())
-        */
+        self.windows.insert(id,window);
+
+        let time = EventTime::now();
+        let position = Position2D{x: 0,y: 0};
+        let size = Size2D{width,height};
+        let surface_info = SurfaceInfo{position,size,surface};
+        let event = SurfaceEvent::Added(surface_info);
+        self.dispatch_context.events.push(crate::definitions::Event::Surface{time,id,event});
+        Ok(())
     }
 
-/*
-    fn output_info(&self, output_id: OutputId) -> Option<OutputInfo> {
-        for output in self.environment.get_all_outputs() {
-            let output_info =
-                smithay_client_toolkit::output::with_output_info(&output, |output_info| {
-                    if output_id == output_info.id.into() {
-                        Some(output_info.clone().into())
-                    } else {
-                        None
-                    }
-                })
-                .unwrap();
-            if output_info.is_some() {
-                return output_info;
+    fn destroy_surface(&mut self, surface_id: SurfaceId) -> Result<(), RequestError> {
+        match self.windows.remove(&surface_id) {
+            Some(window)=>{
+                let surface = window.surface().clone();
+                drop(window);
+                surface.destroy();
+                self.dispatch_context.ime_surfaces.remove(&surface_id);
+                let event = SurfaceEvent::Removed;
+                self.dispatch_context.events.push(crate::definitions::Event::Surface{time: EventTime::now(),id: surface_id,event});
+                Ok(())
             }
+            None=>Err(SurfaceError::UnknownSurface.into())
         }
-        return None;
     }
-    */
-    /*
-    fn list_outputs(&self) -> Vec<crate::Output> {
-        self.environment
-            .get_all_outputs()
-            .into_iter()
-            .map(|wayland_output| {
-                smithay_client_toolkit::output::with_output_info(&wayland_output, |output_info| {
-
-                    output_info.clone().into()
-                })
-                .unwrap()
-            })
-            .collect()
+
+    /// Enable or disable the input method of the text inputs focused on the surface, the others pick it up on enter.
+    fn set_ime_enabled(&mut self, surface_id: SurfaceId, enabled: bool) -> Result<(), RequestError> {
+        if !self.windows.contains_key(&surface_id) {return Err(ImeError::UnknownSurface.into());}
+        if self.dispatch_context.text_input_manager.is_none() {return Err(ImeError::Unsupported.into());}
+        let ime_state = self.dispatch_context.ime_surfaces.entry(surface_id).or_default();
+        if ime_state.enabled == enabled {return Ok(());}
+        ime_state.enabled = enabled;
+        let cursor_area = ime_state.cursor_area.clone();
+        for seat_state in self.dispatch_context.seats.values_mut() {
+            let text_input = match &mut seat_state.text_input {
+                Some(text_input) if text_input.focus == Some(surface_id)=>text_input,
+                _=>continue
+            };
+            if enabled {text_input.enable(cursor_area.as_ref());} else {text_input.disable(&mut self.dispatch_context.events);}
+        }
+        Ok(())
+    }
+
+    fn set_ime_cursor_area(&mut self, surface_id: SurfaceId, area: Rectangle<i32,u32>) -> Result<(), RequestError> {
+        if !self.windows.contains_key(&surface_id) {return Err(ImeError::UnknownSurface.into());}
+        if self.dispatch_context.text_input_manager.is_none() {return Err(ImeError::Unsupported.into());}
+        let ime_state = self.dispatch_context.ime_surfaces.entry(surface_id).or_default();
+        ime_state.cursor_area = Some(area.clone());
+        if !ime_state.enabled {return Ok(());}
+        for seat_state in self.dispatch_context.seats.values_mut() {
+            match &mut seat_state.text_input {
+                Some(text_input) if text_input.focus == Some(surface_id)=>text_input.set_cursor_area(&area),
+                _=>continue
+            }
+        }
+        Ok(())
     }
-    */
 }
@@ -1,2 +0,0 @@
-mod keyboard;
-pub use keyboard::handle_keyboard;
@@ -1,11 +1,19 @@
-mod handlers;
-use handlers::*;
+mod xkb_device;
+use xkb_device::core_keyboard_keymap;
+
+mod xim;
+use xim::XimClient;
+pub(crate) use xim::XimEvent;
+#[cfg(test)]
+pub(crate) use xim::XimProtocol;
 
 use std::sync::Arc;
 use crate::definitions::*;
-use keystroke_decoder::KeystrokeDecoder;
+use log::error;
+use crate::backends::timestamp::TimestampMapper;
+use crate::backends::linux::{XkbCompose,XkbKeyboard,handle_keyboard};
 
-use x11rb::connection::Connection;
+use x11rb::connection::{Connection,RequestConnection};
 use x11rb::protocol::xproto::*;
 use x11rb::protocol::Event;
 use x11rb::xcb_ffi::XCBConnection;
@@ -15,7 +23,17 @@ use x11rb::protocol::xproto::ConnectionExt as XProtoConnectionExt;
 //use x11rb::resource_manager::Database as CursorDatabase;
 
 pub struct XcbPlatform {
-    keystroke_decoder: KeystrokeDecoder,
+    keyboard: XkbKeyboard,
+    /**
+    Whether the keymap has been replaced by `ModifyLayout`.
+    The state is then driven by the key events, otherwise it mirrors the server one.
+    */
+    local_keymap: bool,
+    /// Keymap in use, the one of `ModifyLayout` or the server one.
+    keymap: Keymap,
+    /// Last keymap loaded from the server, to tell whether the notifications actually changed it.
+    server_keymap: Keymap,
+    compose_state: XkbCompose,
     connection: Arc<XCBConnection>,
     //cursor_database: CursorDatabase,
     //cursor_handle: CursorHandle,
@@ -24,21 +42,33 @@ pub struct XcbPlatform {
     wm_protocols: u32,
     wm_delete_window: u32,
     windows: Vec<u32>,
+    /// Whether the cursor has been hidden, since XFixes rejects showing a cursor that is not hidden.
+    cursor_hidden: bool,
     pending_events: Vec<crate::definitions::Event>,
+    timestamps: TimestampMapper,
+    pressed_keys: std::collections::HashSet<u32>,
+    /// Input method client, if an input method server is running.
+    xim: Option<XimClient>,
+    focused_window: Option<u32>,
     external_contexts: Vec<Box<dyn ExternalContext>>
 }
 impl XcbPlatform {
-    pub fn new(external_contexts: Vec<Box<dyn ExternalContext>>) -> Result<Self, ()> {
-        let (connection, preferred_screen) = XCBConnection::connect(None).unwrap();
+    pub fn new(external_contexts: Vec<Box<dyn ExternalContext>>) -> Result<Self, PlatformError> {
+        let (connection, preferred_screen) = XCBConnection::connect(None)?;
         let connection = Arc::new(connection);
-        let wm_protocols = connection.intern_atom(false, b"WM_PROTOCOLS").unwrap().reply().unwrap().atom;
-        let wm_delete_window = connection.intern_atom(false, b"WM_DELETE_WINDOW").unwrap().reply().unwrap().atom;
 
-        let keystroke_decoder = KeystrokeDecoder::new();
+        Self::init_extensions(connection.as_ref())?;
+
+        let wm_protocols = connection.intern_atom(false, b"WM_PROTOCOLS")?.reply()?.atom;
+        let wm_delete_window = connection.intern_atom(false, b"WM_DELETE_WINDOW")?.reply()?.atom;
+
+        let (keyboard,keymap) = Self::load_keymap(connection.as_ref(), preferred_screen)
+            .ok_or_else(||PlatformError::Protocol(String::from("Failed to load the keymap of the core keyboard")))?;
+        let compose_state = XkbCompose::from_locale();
 
         let setup = connection.setup();
         let screen = &setup.roots[preferred_screen as usize];
-        let dummy_window = connection.generate_id().unwrap();
+        let dummy_window = connection.generate_id()?;
 
         connection
             .create_window(
@@ -53,8 +83,8 @@ impl XcbPlatform {
                 WindowClass::INPUT_OUTPUT,
                 0,
                 &CreateWindowAux::new(),
-            )
-            .unwrap();
+            )?
+            .check()?;
 
         let pending_events = Vec::new();
 
@@ -64,9 +94,22 @@ impl XcbPlatform {
         //let cursor_database = CursorDatabase::new_from_default(connection.as_ref()).unwrap();
         //let cursor_handle = CursorHandle::new(connection.as_ref(),preferred_screen,&cursor_database).unwrap().reply().unwrap();
 
+        // The input method is optional, the text is still reported through the key events without it.
+        let xim = match XimClient::new(connection.clone(), preferred_screen) {
+            Ok(xim)=>xim,
+            Err(err)=>{
+                error!("Failed to connect to the input method: {}",err);
+                None
+            }
+        };
+
         let windows = Vec::new();
         let mut platform = Self {
-            keystroke_decoder,
+            keyboard,
+            local_keymap: false,
+            server_keymap: keymap.clone(),
+            keymap,
+            compose_state,
             connection,
             //cursor_database,
             //cursor_handle,
@@ -75,27 +118,68 @@ impl XcbPlatform {
             wm_protocols,
             wm_delete_window,
             windows,
+            cursor_hidden: false,
             pending_events,
+            timestamps: TimestampMapper::new(),
+            pressed_keys: std::collections::HashSet::new(),
+            xim,
+            focused_window: None,
             external_contexts,
         };
 
         platform.init_seats();
-        platform.detect_monitors();
+        platform.detect_monitors()?;
         //platform.set_cursor_mode(0u32.into(),CursorMode::Absolute).unwrap();
         //platform.set_key_repeat(0u32.into(),true).unwrap();
 
         Ok(platform)
     }
 
+    /// Check the presence of the required extensions and negotiate their versions.
+    fn init_extensions(connection: &XCBConnection) -> Result<(), PlatformError> {
+        use x11rb::protocol::{randr,xkb,xfixes};
+
+        if connection.extension_information(randr::X11_EXTENSION_NAME)?.is_none() {
+            return Err(PlatformError::MissingExtension(String::from("RandR")));
+        }
+        let version = randr::query_version(connection, 1, 5)?.reply()?;
+        if (version.major_version, version.minor_version) < (1, 5) {
+            return Err(PlatformError::MissingExtension(String::from("RandR 1.5")));
+        }
+
+        if connection.extension_information(xkb::X11_EXTENSION_NAME)?.is_none() {
+            return Err(PlatformError::MissingExtension(String::from("XKB")));
+        }
+        if !xkb::use_extension(connection, 1, 0)?.reply()?.supported {
+            return Err(PlatformError::MissingExtension(String::from("XKB 1.0")));
+        }
+        // Report auto repeats as consecutive presses, instead of fake release and press pairs.
+        let detectable = xkb::PerClientFlag::DETECTABLE_AUTO_REPEAT;
+        xkb::per_client_flags(connection, xkb::ID::USE_CORE_KBD.into(), detectable, detectable, 0u32, 0u32, 0u32)?.reply()?;
+        // Modifier and group changes are reported regardless of the focus, as well as the keymap replacements.
+        let events = xkb::EventType::STATE_NOTIFY | xkb::EventType::NEW_KEYBOARD_NOTIFY | xkb::EventType::MAP_NOTIFY;
+        xkb::select_events(connection, xkb::ID::USE_CORE_KBD.into(), 0u16, events, 0u16, 0u16, &xkb::SelectEventsAux::new())?.check()?;
+
+        if connection.extension_information(xfixes::X11_EXTENSION_NAME)?.is_none() {
+            return Err(PlatformError::MissingExtension(String::from("XFixes")));
+        }
+        let version = xfixes::query_version(connection, 4, 0)?.reply()?;
+        if version.major_version < 4 {
+            return Err(PlatformError::MissingExtension(String::from("XFixes 4.0")));
+        }
+
+        Ok(())
+    }
+
     fn init_seats(&mut self){
-        let time = 0;
+        let time = EventTime::now();
         let id = 0u32.into();
         let name = String::from("seat-0");
         let event = SeatEvent::Added{name};
         self.pending_events.push(crate::definitions::Event::Seat{time,id,event});
 
         let keyboard_info = KeyboardInfo {
-            layout: self.keystroke_decoder.layout().clone(),
+            keymap: self.keymap.descriptor.clone(),
             autorepeat: true,
             encoding: KeyEncoding::XkbV1
         };
@@ -103,6 +187,17 @@ impl XcbPlatform {
         let event = SeatEvent::Keyboard(keyboard_event);
         self.pending_events.push(crate::definitions::Event::Seat{time,id,event});
 
+        let event = SeatEvent::Keyboard(KeyboardEvent::KeymapChanged{keymap: self.keymap.clone()});
+        self.pending_events.push(crate::definitions::Event::Seat{time,id,event});
+
+        match self.sync_state() {
+            Ok(keyboard_event)=>{
+                let event = SeatEvent::Keyboard(keyboard_event);
+                self.pending_events.push(crate::definitions::Event::Seat{time,id,event});
+            }
+            Err(err)=>error!("Failed to query the keyboard state: {}",err)
+        }
+
         let cursor_info = CursorInfo {
             mode: CursorMode::Absolute,
             theme: CursorImage::Default,
@@ -113,14 +208,149 @@ impl XcbPlatform {
         self.pending_events.push(crate::definitions::Event::Seat{time,id,event});
     }
 
-    fn detect_monitors(&mut self){
-        let resources = x11rb::protocol::randr::get_screen_resources(self.connection.as_ref(), self.dummy_window).unwrap().reply().unwrap();
-        let monitors = x11rb::protocol::randr::get_monitors(self.connection.as_ref(), self.dummy_window,false).unwrap().reply().unwrap().monitors;
+    /**
+    Keymap configured on the server, from the `_XKB_RULES_NAMES` property of the root window set by the X server and setxkbmap.
+    It holds the rules, model, layouts, variants and options, separated by NUL bytes.
+    */
+    fn query_keymap(connection: &XCBConnection, root: Window) -> Option<KeymapDescriptor> {
+        let atom = connection.intern_atom(true, b"_XKB_RULES_NAMES").ok()?.reply().ok()?.atom;
+        if atom == x11rb::NONE {return None;}
+        let property = connection.get_property(false, root, atom, AtomEnum::STRING, 0, 1024).ok()?.reply().ok()?;
+        let mut names = property.value.split(|byte|*byte == 0).map(|name|String::from_utf8_lossy(name).into_owned());
+        let rules = names.next().filter(|rules|!rules.is_empty())?;
+        let model = names.next().unwrap_or_default();
+        let layouts = names.next().unwrap_or_default();
+        let variants = names.next().unwrap_or_default();
+        let options = names.next().unwrap_or_default();
+        Some(KeymapDescriptor {rules,model,..KeymapDescriptor::from_layouts(&layouts).with_variants(&variants).with_options(&options)})
+    }
+
+    /// Keymap of the core keyboard, along with the descriptor it has been configured with.
+    fn load_keymap(connection: &XCBConnection, screen: usize) -> Option<(XkbKeyboard,Keymap)> {
+        let descriptor = Self::query_keymap(connection, connection.setup().roots[screen].root).unwrap_or_default();
+        let keyboard = XkbKeyboard::from_keymap(core_keyboard_keymap(connection)?);
+        let keymap = keyboard.keymap(descriptor);
+        Some((keyboard,keymap))
+    }
+
+    /**
+    Reload the keymap after a server side change, like the ones of setxkbmap, replacing the one of `ModifyLayout` if any.
+    The changes are reported only if the keymap differs, since switching between keyboards triggers notifications as well.
+    */
+    fn reload_keymap(&mut self, time: EventTime, events: &mut Vec<crate::definitions::Event>) {
+        let (keyboard,keymap) = match Self::load_keymap(self.connection.as_ref(), self.preferred_screen) {
+            Some(keymap)=>keymap,
+            None=>{
+                error!("Failed to load the keymap of the core keyboard");
+                return;
+            }
+        };
+        if keymap == self.server_keymap {return;}
+        self.keyboard = keyboard;
+        self.local_keymap = false;
+        self.server_keymap = keymap.clone();
+        if keymap.descriptor != self.keymap.descriptor {
+            let event = SeatEvent::Keyboard(KeyboardEvent::LayoutModified{keymap: keymap.descriptor.clone()});
+            events.push(crate::definitions::Event::Seat{time,id: 0u32.into(),event});
+        }
+        if keymap != self.keymap {
+            self.keymap = keymap.clone();
+            let event = SeatEvent::Keyboard(KeyboardEvent::KeymapChanged{keymap});
+            events.push(crate::definitions::Event::Seat{time,id: 0u32.into(),event});
+        }
+        match self.sync_state() {
+            Ok(keyboard_event)=>events.push(crate::definitions::Event::Seat{time,id: 0u32.into(),event: SeatEvent::Keyboard(keyboard_event)}),
+            Err(err)=>error!("Failed to query the keyboard state: {}",err)
+        }
+    }
+
+    /// Mirror the server modifiers and group, for the ones set before the first state notification.
+    fn sync_state(&mut self) -> Result<KeyboardEvent, PlatformError> {
+        use x11rb::protocol::xkb;
+        let state = xkb::get_state(self.connection.as_ref(), xkb::ID::USE_CORE_KBD.into())?.reply()?;
+        self.keyboard.update_mask(
+            state.base_mods.into(),
+            state.latched_mods.into(),
+            state.locked_mods.into(),
+            state.base_group as u32,
+            state.latched_group as u32,
+            u8::from(state.locked_group).into()
+        );
+        Ok(self.keyboard.modifiers_event())
+    }
+
+    /// Apply a key to the local state, reporting the modifiers if they changed.
+    fn update_local_state(&mut self, code: u32, state: State, time: EventTime, events: &mut Vec<crate::definitions::Event>) {
+        if self.keyboard.update_key(code,state) {
+            let event = SeatEvent::Keyboard(self.keyboard.modifiers_event());
+            events.push(crate::definitions::Event::Seat{time,id: 0u32.into(),event});
+        }
+    }
+
+    /// Report a key event, the ones forwarded back by the input method included.
+    fn handle_key(&mut self, response_type: u8, detail: u8, time: u32, sequence: u16, modifiers: u16, events: &mut Vec<crate::definitions::Event>) {
+        let time = self.timestamps.map(time);
+        let code = detail as u32-8;
+        let state = if response_type & 0x7f == KEY_PRESS_EVENT {State::Down} else {State::Up};
+        let repeat = match state {
+            State::Down=>!self.pressed_keys.insert(code),
+            State::Up=>{
+                self.pressed_keys.remove(&code);
+                false
+            }
+        };
+        let control = state == State::Down && modifiers & u16::from(KeyButMask::CONTROL) != 0;
+        events.push(handle_keyboard(
+            &self.keyboard,
+            &mut self.compose_state,
+            0.into(),
+            code,
+            state,
+            sequence as u32,
+            time,
+            repeat,
+            control
+        ));
+        if self.local_keymap && !repeat {self.update_local_state(code,state,time,events);}
+    }
+
+    fn handle_xim_events(&mut self, xim_events: Vec<XimEvent>, events: &mut Vec<crate::definitions::Event>) {
+        for xim_event in xim_events {
+            let event = match xim_event {
+                XimEvent::Preedit{text,cursor}=>KeyboardEvent::Preedit{text,cursor},
+                XimEvent::Commit{text}=>KeyboardEvent::Commit{text},
+                XimEvent::Key(event)=>{
+                    self.handle_key(event.response_type,event.detail,event.time,event.sequence,event.state,events);
+                    continue;
+                }
+            };
+            let time = EventTime::now();
+            events.push(crate::definitions::Event::Seat{time,id: 0u32.into(),event: SeatEvent::Keyboard(event)});
+        }
+    }
+
+    /// Track the focused window, so that the input contexts enabled while focused start focused.
+    fn set_focused_window(&mut self, window: u32, focused: bool) {
+        match focused {
+            true=>self.focused_window = Some(window),
+            false=>if self.focused_window == Some(window) {self.focused_window = None;}
+        }
+        if let Some(xim) = &mut self.xim {
+            if let Err(err) = xim.set_focus(window,focused) {error!("Failed to update the input method focus: {}",err);}
+        }
+    }
+
+    fn detect_monitors(&mut self) -> Result<(), PlatformError> {
+        let resources = x11rb::protocol::randr::get_screen_resources(self.connection.as_ref(), self.dummy_window)?.reply()?;
+        let monitors = x11rb::protocol::randr::get_monitors(self.connection.as_ref(), self.dummy_window,false)?.reply()?.monitors;
 
         for (id,monitor) in monitors.iter().enumerate() {
-            let output = monitor.outputs[0];
+            let output = match monitor.outputs.first() {
+                Some(output)=>*output,
+                None=>continue
+            };
 
-            let output_info = x11rb::protocol::randr::get_output_info(self.connection.as_ref(),output,0).unwrap().reply().unwrap();
+            let output_info = x11rb::protocol::randr::get_output_info(self.connection.as_ref(),output,0)?.reply()?;
             if output_info.connection != x11rb::protocol::randr::Connection::CONNECTED {continue;}
 
             let position = (monitor.x as u32,monitor.y as u32).into();
@@ -140,7 +370,10 @@ impl XcbPlatform {
                 mode.is_preferred = true;
             }
 
-            let selected_mode = available_modes[0].clone();
+            let selected_mode = match available_modes.first() {
+                Some(mode)=>mode.clone(),
+                None=>continue
+            };
 
             let subpixel = match output_info.subpixel_order {
                 x11rb::protocol::render::SubPixel::HORIZONTAL_RGB => Subpixel::HorizontalRgb,
@@ -160,10 +393,11 @@ impl XcbPlatform {
                 subpixel
             };
 
-            let time = 0;
+            let time = EventTime::now();
             let event = OutputEvent::Added(output_info);
             self.pending_events.push(crate::definitions::Event::Output{time,id,event});
         }
+        Ok(())
     }
 }
 
@@ -178,29 +412,36 @@ impl PlatformBackend for XcbPlatform {
     fn platform_type(&self)->PlatformType {PlatformType::Compositor}
     fn events(&mut self) -> Vec<crate::definitions::Event> {
         let mut events: Vec<crate::definitions::Event> = self.pending_events.drain(..).collect();
-
-        while let Ok(Some(event)) = self.connection.poll_for_event() {
-            match event {
-                Event::KeyPress(event) => {
-                    events.append(&mut handle_keyboard(
-                        &mut self.keystroke_decoder,
-                        0.into(),
-                        event.detail as u32-8,
-                        event.sequence as u32,
-                        event.time
-                    ));
+        // A keymap change comes with a burst of notifications, it is reloaded once before the next key event.
+        let mut keymap_changed: Option<EventTime> = None;
+
+        loop {
+            let event = match self.connection.poll_for_event() {
+                Ok(Some(event))=>event,
+                Ok(None)=>break,
+                Err(err)=>{
+                    error!("Failed to poll the X server: {}",err);
+                    break;
+                }
+            };
+            if let Some(xim) = &mut self.xim {
+                if xim.handle_event(&event) {
+                    let xim_events = xim.take_events();
+                    self.handle_xim_events(xim_events,&mut events);
+                    continue;
                 }
-                Event::KeyRelease(event) => {
-                    events.append(&mut handle_keyboard(
-                        &mut self.keystroke_decoder,
-                        0.into(),
-                        event.detail as u32-8,
-                        event.sequence as u32,
-                        event.time
-                    ));
+            }
+            match event {
+                Event::KeyPress(event) | Event::KeyRelease(event) => {
+                    if let Some(time) = keymap_changed.take() {self.reload_keymap(time,&mut events);}
+                    // The input method gets the key events of the windows it is enabled on, and forwards back the ones it doesn't consume.
+                    if let Some(xim) = &mut self.xim {
+                        if xim.filter_key(&event) {continue;}
+                    }
+                    self.handle_key(event.response_type,event.detail,event.time,event.sequence,event.state,&mut events);
                 }
                 Event::ButtonPress(event) => {
-                    let time = event.time;
+                    let time = self.timestamps.map(event.time);
                     let id = 0u32.into();
                     let state = State::Down;
                     // Key codes taken from https://sources.debian.org/src/xserver-xorg-input-libinput/1.2.0-1/src/xf86libinput.c/#L249-L256
@@ -236,7 +477,7 @@ impl PlatformBackend for XcbPlatform {
                     }
                 }
                 Event::ButtonRelease(event) => {
-                    let time = event.time;
+                    let time = self.timestamps.map(event.time);
                     let id = 0u32.into();
                     let state = State::Up;
                     // Key codes taken from https://sources.debian.org/src/xserver-xorg-input-libinput/1.2.0-1/src/xf86libinput.c/#L249-L256
@@ -286,7 +527,7 @@ impl PlatformBackend for XcbPlatform {
                     }
                 }
                 Event::EnterNotify(event)=>{
-                    let time = event.time;
+                    let time = self.timestamps.map(event.time);
                     let id = 0.into();
                     let surface_id = SurfaceId::from(event.event);
                     let position = Position2D::from((event.event_x as i32,event.event_y as i32));
@@ -294,14 +535,14 @@ impl PlatformBackend for XcbPlatform {
                     events.push(crate::definitions::Event::Seat{time,id,event});
                 }
                 Event::LeaveNotify(event)=>{
-                    let time = event.time;
+                    let time = self.timestamps.map(event.time);
                     let id = 0.into();
                     let surface_id = SurfaceId::from(event.event);
                     let event = SeatEvent::Cursor(CursorEvent::Left {surface_id});
                     events.push(crate::definitions::Event::Seat{time,id,event});
                 }
                 Event::MotionNotify(event)=>{
-                    let time = event.time;
+                    let time = self.timestamps.map(event.time);
                     let id = 0.into();
                     let position = Position2D::from((event.event_x as i32,event.event_y as i32));
                     let event = SeatEvent::Cursor(CursorEvent::AbsoluteMovement{position});
@@ -309,229 +550,390 @@ impl PlatformBackend for XcbPlatform {
                 }
                 Event::ConfigureNotify(event) => {
                     if event.response_type == x11rb::protocol::xproto::CONFIGURE_NOTIFY_EVENT {
-                        let time = 0;
+                        let time = EventTime::now();
                         let id = SurfaceId::from(event.window);
                         let event = SurfaceEvent::Resized(Size2D::from((event.width as u32,event.height as u32)));
                         events.push(crate::definitions::Event::Surface{time,id,event});
                     }
                 }
                 Event::DestroyNotify(event)=>{
-                    let time = 0;
+                    let time = EventTime::now();
                     let id = SurfaceId::from(event.window);
                     let event = SurfaceEvent::Removed;
                     events.push(crate::definitions::Event::Surface{time,id,event});
                 }
+                Event::FocusIn(event) if event.detail != NotifyDetail::POINTER=>{
+                    self.set_focused_window(event.event,true);
+                    let time = EventTime::now();
+                    let id = SurfaceId::from(event.event);
+                    let event = SurfaceEvent::Focused(true);
+                    events.push(crate::definitions::Event::Surface{time,id,event});
+                }
+                // A local keymap has its own state, driven by the key events.
+                Event::XkbStateNotify(event) if !self.local_keymap=>{
+                    use x11rb::protocol::xkb::StatePart;
+                    let parts = [
+                        StatePart::MODIFIER_BASE,StatePart::MODIFIER_LATCH,StatePart::MODIFIER_LOCK,
+                        StatePart::GROUP_STATE,StatePart::GROUP_BASE,StatePart::GROUP_LATCH,StatePart::GROUP_LOCK
+                    ];
+                    // Grab and compatibility state changes leave the modifiers untouched.
+                    if parts.iter().any(|part|event.changed & u16::from(*part) != 0) {
+                        self.keyboard.update_mask(
+                            event.base_mods.into(),
+                            event.latched_mods.into(),
+                            event.locked_mods.into(),
+                            event.base_group as u32,
+                            event.latched_group as u32,
+                            u8::from(event.locked_group).into()
+                        );
+                        let time = self.timestamps.map(event.time);
+                        let event = SeatEvent::Keyboard(self.keyboard.modifiers_event());
+                        events.push(crate::definitions::Event::Seat{time,id: 0u32.into(),event});
+                    }
+                }
+                Event::XkbNewKeyboardNotify(event)=>{
+                    keymap_changed = keymap_changed.or_else(||Some(self.timestamps.map(event.time)));
+                }
+                Event::XkbMapNotify(event)=>{
+                    keymap_changed = keymap_changed.or_else(||Some(self.timestamps.map(event.time)));
+                }
+                Event::FocusOut(event) if event.detail != NotifyDetail::POINTER=>{
+                    self.set_focused_window(event.event,false);
+                    self.pressed_keys.clear();
+                    self.compose_state.reset();
+                    let time = EventTime::now();
+                    // The releases happening while unfocused are not reported, so the local state could keep modifiers held.
+                    if self.local_keymap {
+                        self.keyboard.release_keys();
+                        let event = SeatEvent::Keyboard(self.keyboard.modifiers_event());
+                        events.push(crate::definitions::Event::Seat{time,id: 0u32.into(),event});
+                    }
+                    let id = SurfaceId::from(event.event);
+                    let event = SurfaceEvent::Focused(false);
+                    events.push(crate::definitions::Event::Surface{time,id,event});
+                }
                 Event::ClientMessage(event) => {
                     let data = event.data.as_data32();
                     if event.format == 32 && data[0] == self.wm_delete_window {
-                        self.requests(vec![Request::Surface{request: SurfaceRequest::Destroy(event.window.into())}]);
+                        self.requests(vec![SurfaceRequest::Destroy(event.window.into()).into()]);
                     }
                 }
                 _ => {}
             }
         }
+        if let Some(time) = keymap_changed {self.reload_keymap(time,&mut events);}
         events
     }
 
     fn requests(&mut self, requests: Vec<Request>) {
         requests.into_iter().for_each(|request|{
-            match request {
-                crate::definitions::Request::Seat{request: SeatRequest::Keyboard(keyboard_request)}=>{
+            let token = request.token();
+            let result = match request {
+                crate::definitions::Request::Seat{request: SeatRequest::Keyboard(keyboard_request),..}=>{
                     match keyboard_request {
-                        KeyboardRequest::ModifyLayout{layout}=>{
-                            self.keystroke_decoder.set_layout(layout);
-                        }
-                        KeyboardRequest::SetAutoRepeat{rate,delay}=>{
-                            let device_spec = 0;
-                            let current_controls = x11rb::protocol::xkb::get_controls(self.connection.as_ref(),device_spec)
-                            .unwrap()
-                            .reply()
-                            .unwrap();
-
-                            match x11rb::protocol::xkb::set_controls(
-                                self.connection.as_ref(),
-                                device_spec,
-                                0,//affect_internal_real_mods
-                                0,//internal_real_mods
-                                0,//affect_ignore_lock_real_mods
-                                0,//ignore_lock_real_mods
-                                0 as u16,//affect_internal_virtual_mods
-                                0 as u16,//internal_virtual_mods
-                                0 as u16,//affect_ignore_lock_virtual_mods
-                                0 as u16,//ignore_lock_virtual_mods
-                                current_controls.mouse_keys_dflt_btn,
-                                current_controls.groups_wrap,
-                                0 as u16,//access_x_options
-                                0 as u32,//affect_enabled_controls
-                                current_controls.enabled_controls,
-                                0 as u32,//change_controls
-                                delay as u16,
-                                rate as u16,
-                                current_controls.slow_keys_delay,
-                                current_controls.debounce_delay,
-                                current_controls.mouse_keys_delay,
-                                current_controls.mouse_keys_interval,
-                                current_controls.mouse_keys_time_to_max,
-                                current_controls.mouse_keys_max_speed,
-                                current_controls.mouse_keys_curve,
-                                current_controls.access_x_timeout,
-                                current_controls.access_x_timeout_mask,
-                                current_controls.access_x_timeout_values,
-                                current_controls.access_x_timeout_options_mask,
-                                current_controls.access_x_timeout_options_values,
-                                &current_controls.per_key_repeat
-                            ){
-                                Ok(_)=>{
-                                    let time = 0;
-                                    let keyboard_event = KeyboardEvent::AutoRepeat{rate,delay};
-                                    let event = SeatEvent::Keyboard(keyboard_event);
-                                    let id = 0u32.into();
-                                    self.pending_events.push(crate::definitions::Event::Seat{time,id,event});
-                                }
-                                Err(_)=>{println!("Failed to set repeat mode");}
-                            }
-                        }
+                        // The keymap is only applied to the local decoding, the server keymap is left untouched.
+                        KeyboardRequest::ModifyLayout{keymap}=>self.modify_layout(keymap),
+                        KeyboardRequest::SwitchGroup{group}=>self.switch_group(group),
+                        KeyboardRequest::SetAutoRepeat{rate,delay}=>self.set_auto_repeat(rate,delay)
                     }
-
                 }
-                crate::definitions::Request::Seat{request: SeatRequest::Cursor(cursor_request)}=>{
+                crate::definitions::Request::Seat{request: SeatRequest::Cursor(cursor_request),..}=>{
                     match cursor_request {
-                        CursorRequest::ChangeImage(theme)=>{
-                            match theme {
-                                CursorImage::Custom(_data)=>{
-                                    self.windows.iter().cloned().for_each(|window|{
-                                        match x11rb::protocol::xfixes::show_cursor(self.connection.as_ref(),window){
-                                            Ok(cookie)=>cookie.check().unwrap(),//cookie.ignore_error(),
-                                            Err(err)=>eprint!("Error: {}",err)
-                                        }
-                                    });
-                                }
-                                CursorImage::Default=>{
-                                    self.windows.iter().cloned().for_each(|window|{
-                                        match x11rb::protocol::xfixes::show_cursor(self.connection.as_ref(),window){
-                                            Ok(cookie)=>cookie.check().unwrap(),
-                                            Err(err)=>eprint!("Error: {}",err)
-                                        }
-                                    });
-                                }
-                                CursorImage::Hidden=>{
-                                    self.windows.iter().cloned().for_each(|window|{
-                                        match x11rb::protocol::xfixes::hide_cursor(self.connection.as_ref(),window){
-                                            Ok(cookie)=>cookie.check().unwrap(),//cookie.ignore_error(),
-                                            Err(err)=>eprint!("Error: {}",err)
-                                        }
-                                    });
-                                }
-                            }
-                        }
-                        _=>{}
+                        CursorRequest::ChangeImage(image)=>self.change_cursor_image(image),
+                        CursorRequest::ChangeMode(CursorMode::Absolute)=>Ok(()),
+                        CursorRequest::ChangeMode(CursorMode::Relative)=>Err(CursorModeError::Unsupported.into())
                     }
                 }
-                crate::definitions::Request::Seat{request: SeatRequest::Touch(_touch_request)}=>{
+                crate::definitions::Request::Seat{request: SeatRequest::Touch(_touch_request),..}=>Ok(()),
+                crate::definitions::Request::Seat{request: SeatRequest::Gamepad(_gamepad_request),..}=>Ok(()),
+                crate::definitions::Request::Output{..}=>Ok(()),
+                crate::definitions::Request::Surface{request:SurfaceRequest::Create(output),..}=>self.create_surface(output),
+                crate::definitions::Request::Surface{request: SurfaceRequest::Destroy(surface_id),..}=>self.destroy_surface(surface_id),
+                crate::definitions::Request::Surface{request: SurfaceRequest::Commit(_surface_id),..}=>Ok(()),
+                crate::definitions::Request::Surface{request: SurfaceRequest::SetImeEnabled{id,enabled},..}=>self.set_ime_enabled(id,enabled),
+                crate::definitions::Request::Surface{request: SurfaceRequest::SetImeCursorArea{id,area},..}=>self.set_ime_cursor_area(id,area)
+            };
+            match token {
+                Some(token)=>{
+                    let time = EventTime::now();
+                    let event = RequestEvent::from(result);
+                    self.pending_events.push(crate::definitions::Event::Request{time,token,event});
                 }
-                crate::definitions::Request::Seat{request: SeatRequest::Gamepad(_gamepad_request)}=>{
+                None=>{
+                    if let Err(err) = result {error!("Failed to handle request: {}",err);}
                 }
-                crate::definitions::Request::Output{request:_}=>{
+            }
+        });
+    }
+}
+
+impl XcbPlatform {
+    /**
+    Compile the whole descriptor before replacing the current keymap, so that invalid keymaps are rejected.
+    From then on the state is tracked locally, starting from the current locks and the first group.
+    */
+    fn modify_layout(&mut self, keymap: KeymapDescriptor) -> Result<(), RequestError> {
+        let mut keyboard = XkbKeyboard::from_descriptor(&keymap)?;
+        let (_,_,locked) = self.keyboard.mods();
+        keyboard.update_mask(0,0,locked,0,0,0);
+        let compiled_keymap = keyboard.keymap(keymap.clone());
+        self.keyboard = keyboard;
+        self.local_keymap = true;
+        self.keymap = compiled_keymap.clone();
+
+        let time = EventTime::now();
+        let event = SeatEvent::Keyboard(KeyboardEvent::LayoutModified{keymap});
+        self.pending_events.push(crate::definitions::Event::Seat{time,id: 0u32.into(),event});
+        let event = SeatEvent::Keyboard(KeyboardEvent::KeymapChanged{keymap: compiled_keymap});
+        self.pending_events.push(crate::definitions::Event::Seat{time,id: 0u32.into(),event});
+        let event = SeatEvent::Keyboard(self.keyboard.modifiers_event());
+        self.pending_events.push(crate::definitions::Event::Seat{time,id: 0u32.into(),event});
+        Ok(())
+    }
+
+    /**
+    Lock the layout group.
+    With a local keymap the group is switched locally, otherwise it is locked on the server and reported back by the XKB state notification.
+    */
+    fn switch_group(&mut self, group: u32) -> Result<(), RequestError> {
+        use x11rb::protocol::xkb;
+        if self.local_keymap {
+            self.keyboard.lock_group(group)?;
+            let event = SeatEvent::Keyboard(self.keyboard.modifiers_event());
+            self.pending_events.push(crate::definitions::Event::Seat{time: EventTime::now(),id: 0u32.into(),event});
+            return Ok(());
+        }
+        if group >= self.keyboard.num_layouts() {
+            return Err(KeyboardLayoutError::InvalidGroup.into());
+        }
+        xkb::latch_lock_state(self.connection.as_ref(), xkb::ID::USE_CORE_KBD.into(), 0u8, 0u8, true, xkb::Group::from(group as u8), 0u8, false, 0)?.check()?;
+        Ok(())
+    }
+
+    fn set_auto_repeat(&mut self, rate: u32, delay: u32) -> Result<(), RequestError> {
+        let device_spec = x11rb::protocol::xkb::ID::USE_CORE_KBD.into();
+        let current_controls = x11rb::protocol::xkb::get_controls(self.connection.as_ref(),device_spec)?.reply()?;
+
+        x11rb::protocol::xkb::set_controls(
+            self.connection.as_ref(),
+            device_spec,
+            0,//affect_internal_real_mods
+            0,//internal_real_mods
+            0,//affect_ignore_lock_real_mods
+            0,//ignore_lock_real_mods
+            0 as u16,//affect_internal_virtual_mods
+            0 as u16,//internal_virtual_mods
+            0 as u16,//affect_ignore_lock_virtual_mods
+            0 as u16,//ignore_lock_virtual_mods
+            current_controls.mouse_keys_dflt_btn,
+            current_controls.groups_wrap,
+            0 as u16,//access_x_options
+            0 as u32,//affect_enabled_controls
+            current_controls.enabled_controls,
+            0 as u32,//change_controls
+            delay as u16,
+            rate as u16,
+            current_controls.slow_keys_delay,
+            current_controls.debounce_delay,
+            current_controls.mouse_keys_delay,
+            current_controls.mouse_keys_interval,
+            current_controls.mouse_keys_time_to_max,
+            current_controls.mouse_keys_max_speed,
+            current_controls.mouse_keys_curve,
+            current_controls.access_x_timeout,
+            current_controls.access_x_timeout_mask,
+            current_controls.access_x_timeout_values,
+            current_controls.access_x_timeout_options_mask,
+            current_controls.access_x_timeout_options_values,
+            &current_controls.per_key_repeat
+        )?.check()?;
+
+        let time = EventTime::now();
+        let keyboard_event = KeyboardEvent::AutoRepeat{rate,delay};
+        let event = SeatEvent::Keyboard(keyboard_event);
+        let id = 0u32.into();
+        self.pending_events.push(crate::definitions::Event::Seat{time,id,event});
+        Ok(())
+    }
+
+    fn change_cursor_image(&mut self, image: CursorImage) -> Result<(), RequestError> {
+        let hidden = match image {
+            CursorImage::Default=>false,
+            CursorImage::Hidden=>true,
+            CursorImage::Custom(_)=>return Err(CursorImageError::Unsupported.into())
+        };
+        if hidden == self.cursor_hidden {return Ok(());}
+        for window in self.windows.iter().cloned() {
+            match hidden {
+                true=>x11rb::protocol::xfixes::hide_cursor(self.connection.as_ref(),window)?.check()?,
+                false=>x11rb::protocol::xfixes::show_cursor(self.connection.as_ref(),window)?.check()?
+            }
+        }
+        self.cursor_hidden = hidden;
+        Ok(())
+    }
 
+    fn create_surface(&mut self, output: Option<OutputId>) -> Result<(), RequestError> {
+        let setup = self.connection.setup();
+        let screen = &setup.roots[self.preferred_screen];
+
+        let (x,y) = match output {
+            Some(output)=>{
+                let monitors = x11rb::protocol::randr::get_monitors(self.connection.as_ref(), self.dummy_window,false)?.reply()?.monitors;
+                let output_index: usize = output.into();
+                match monitors.get(output_index) {
+                    Some(monitor)=>(monitor.x as u32,monitor.y as u32),
+                    None=>return Err(SurfaceError::InvalidOutput.into())
                 }
-                crate::definitions::Request::Surface{request:SurfaceRequest::Create(output)}=>{
-                    let setup = self.connection.setup();
-                    let screen = &setup.roots[0];
-
-                    let monitors = x11rb::protocol::randr::get_monitors(self.connection.as_ref(), self.dummy_window,false).unwrap().reply().unwrap().monitors;
-
-                    let window = self.connection.generate_id().unwrap();
-                    let win_aux = CreateWindowAux::new()
-                        .event_mask(
-                            EventMask::EXPOSURE
-                                | EventMask::STRUCTURE_NOTIFY
-                                | EventMask::NO_EVENT
-                                | EventMask::KEY_PRESS
-                                | EventMask::KEY_RELEASE
-                                | EventMask::BUTTON_PRESS
-                                | EventMask::BUTTON_RELEASE
-                                | EventMask::ENTER_WINDOW
-                                | EventMask::LEAVE_WINDOW
-                                | EventMask::PROPERTY_CHANGE
-                                | EventMask::POINTER_MOTION,
-                        )
-                        .background_pixel(screen.black_pixel);
-
-                    let (x,y) = match output {
-                        Some(output)=>{
-
-                            let output_index: usize = output.clone().into();
-                            let monitor = &monitors[output_index];
-                            (monitor.x as u32,monitor.y as u32)
-                        }
-                        None=>(0,0)
-                    };
+            }
+            None=>(0,0)
+        };
 
-                    let width = 400u32;
-                    let height = 400u32;
-
-                    self.connection
-                        .create_window(
-                            screen.root_depth,
-                            window,
-                            screen.root,
-                            x as i16,y as i16,
-                            width as u16,height as u16,
-                            0,
-                            WindowClass::INPUT_OUTPUT,
-                            0,
-                            &win_aux,
-                        )
-                        .unwrap();
-
-                    self.connection.change_property32(
-                        PropMode::APPEND,
-                        window,
-                        self.wm_protocols,
-                        AtomEnum::ATOM,
-                        &[self.wm_delete_window],
-                    ).unwrap();
-
-                    self.connection.map_window(window).unwrap();
-                    self.connection.flush().unwrap();
-
-                    let xcb_handle = raw_window_handle::unix::XcbHandle {
-                        window: window.clone().into(),
-                        connection: self.connection.get_raw_xcb_connection(),
-                        ..raw_window_handle::unix::XcbHandle::empty()
-                    };
+        let window = self.connection.generate_id()?;
+        let win_aux = CreateWindowAux::new()
+            .event_mask(
+                EventMask::EXPOSURE
+                    | EventMask::STRUCTURE_NOTIFY
+                    | EventMask::NO_EVENT
+                    | EventMask::KEY_PRESS
+                    | EventMask::KEY_RELEASE
+                    | EventMask::BUTTON_PRESS
+                    | EventMask::BUTTON_RELEASE
+                    | EventMask::ENTER_WINDOW
+                    | EventMask::LEAVE_WINDOW
+                    | EventMask::PROPERTY_CHANGE
+                    | EventMask::POINTER_MOTION
+                    | EventMask::FOCUS_CHANGE,
+            )
+            .background_pixel(screen.black_pixel);
 
-                    let raw_surface_handle = RawSurfaceHandle::Xcb(xcb_handle);
+        let width = 400u32;
+        let height = 400u32;
 
-                    let surface = match self.external_contexts.iter().find_map(|context|context.create_surface(&raw_surface_handle).ok()){
-                        Some(surface)=>surface,
-                        None=>{
-                            unimplemented!();
-                        }
-                    };
+        self.connection
+            .create_window(
+                screen.root_depth,
+                window,
+                screen.root,
+                x as i16,y as i16,
+                width as u16,height as u16,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                0,
+                &win_aux,
+            )?
+            .check()?;
+
+        let xcb_handle = raw_window_handle::unix::XcbHandle {
+            window: window.clone().into(),
+            connection: self.connection.get_raw_xcb_connection(),
+            ..raw_window_handle::unix::XcbHandle::empty()
+        };
+        let raw_surface_handle = RawSurfaceHandle::Xcb(xcb_handle);
 
-                    self.windows.push(window);
+        let mut surface = Err(PlatformError::SurfaceCreation(SurfaceError::Unsupported));
+        for context in &self.external_contexts {
+            surface = context.create_surface(&raw_surface_handle);
+            if surface.is_ok() {break;}
+        }
+        let surface = match surface {
+            Ok(surface)=>surface,
+            Err(err)=>{
+                self.connection.destroy_window(window)?;
+                self.connection.flush()?;
+                return Err(err.into());
+            }
+        };
 
-                    let time = 0;
-                    let id = window.into();
-                    let position = Position2D{x,y};
-                    let size = Size2D{width,height};
-                    let surface_info = SurfaceInfo{position,size,surface};
-                    let event = SurfaceEvent::Added(surface_info);
-                    self.pending_events.push(crate::definitions::Event::Surface{time,id,event});
-                }
-                crate::definitions::Request::Surface{request: SurfaceRequest::Destroy(surface_id)}=>{
-                    let id: usize = surface_id.into();
-                    let id = id as u32;
-                    if let Some(position) = self.windows.iter().position(|window|window == &id){
-                        self.connection.destroy_window(id).unwrap();
-                        self.connection.flush().unwrap();
-                        self.windows.remove(position);
-                    }
-                }
-                crate::definitions::Request::Surface{request: SurfaceRequest::Commit(_surface_id)}=>{
-                }
+        self.connection.change_property32(
+            PropMode::APPEND,
+            window,
+            self.wm_protocols,
+            AtomEnum::ATOM,
+            &[self.wm_delete_window],
+        )?;
+
+        self.connection.map_window(window)?;
+        self.connection.flush()?;
+
+        self.windows.push(window);
+
+        let time = EventTime::now();
+        let id = window.into();
+        let position = Position2D{x,y};
+        let size = Size2D{width,height};
+        let surface_info = SurfaceInfo{position,size,surface};
+        let event = SurfaceEvent::Added(surface_info);
+        self.pending_events.push(crate::definitions::Event::Surface{time,id,event});
+        Ok(())
+    }
+
+    fn destroy_surface(&mut self, surface_id: SurfaceId) -> Result<(), RequestError> {
+        let id: usize = surface_id.into();
+        let id = id as u32;
+        match self.windows.iter().position(|window|window == &id){
+            Some(position)=>{
+                self.windows.remove(position);
+                if let Some(xim) = &mut self.xim {xim.set_enabled(id,false)?;}
+                self.connection.destroy_window(id)?;
+                self.connection.flush()?;
+                Ok(())
             }
-        });
+            None=>Err(SurfaceError::UnknownSurface.into())
+        }
+    }
+
+    fn surface_window(&self, surface_id: SurfaceId) -> Option<u32> {
+        let id: usize = surface_id.into();
+        let id = id as u32;
+        self.windows.contains(&id).then_some(id)
     }
+
+    fn set_ime_enabled(&mut self, surface_id: SurfaceId, enabled: bool) -> Result<(), RequestError> {
+        let window = self.surface_window(surface_id).ok_or(ImeError::UnknownSurface)?;
+        let xim = self.xim.as_mut().ok_or(ImeError::Unsupported)?;
+        xim.set_enabled(window,enabled)?;
+        if enabled && self.focused_window == Some(window) {xim.set_focus(window,true)?;}
+        Ok(())
+    }
+
+    fn set_ime_cursor_area(&mut self, surface_id: SurfaceId, area: crate::definitions::Rectangle<i32,u32>) -> Result<(), RequestError> {
+        let window = self.surface_window(surface_id).ok_or(ImeError::UnknownSurface)?;
+        let xim = self.xim.as_mut().ok_or(ImeError::Unsupported)?;
+        xim.set_cursor_area(window,&area)?;
+        Ok(())
+    }
+}
+
+impl From<x11rb::errors::ConnectError> for PlatformError {
+    fn from(error: x11rb::errors::ConnectError)->Self {Self::Connection(error.to_string())}
+}
+impl From<x11rb::errors::ConnectionError> for PlatformError {
+    fn from(error: x11rb::errors::ConnectionError)->Self {Self::Connection(error.to_string())}
+}
+impl From<x11rb::errors::ReplyError> for PlatformError {
+    fn from(error: x11rb::errors::ReplyError)->Self {
+        match error {
+            x11rb::errors::ReplyError::ConnectionError(error)=>error.into(),
+            x11rb::errors::ReplyError::X11Error(error)=>Self::Protocol(format!("{:?}",error))
+        }
+    }
+}
+impl From<x11rb::errors::ReplyOrIdError> for PlatformError {
+    fn from(error: x11rb::errors::ReplyOrIdError)->Self {
+        match error {
+            x11rb::errors::ReplyOrIdError::IdsExhausted=>Self::Protocol(String::from("X11 ids exhausted")),
+            x11rb::errors::ReplyOrIdError::ConnectionError(error)=>error.into(),
+            x11rb::errors::ReplyOrIdError::X11Error(error)=>Self::Protocol(format!("{:?}",error))
+        }
+    }
+}
+impl From<x11rb::errors::ConnectionError> for RequestError {
+    fn from(error: x11rb::errors::ConnectionError)->Self {PlatformError::from(error).into()}
+}
+impl From<x11rb::errors::ReplyError> for RequestError {
+    fn from(error: x11rb::errors::ReplyError)->Self {PlatformError::from(error).into()}
+}
+impl From<x11rb::errors::ReplyOrIdError> for RequestError {
+    fn from(error: x11rb::errors::ReplyOrIdError)->Self {PlatformError::from(error).into()}
 }
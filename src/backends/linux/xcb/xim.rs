@@ -0,0 +1,621 @@
+/*!
+Client side of the X Input Method protocol.
+The protocol state is kept apart from the X transport, so that it can be driven by any server connection.
+*/
+
+use std::collections::{HashMap,VecDeque};
+use std::sync::Arc;
+use log::{error,warn};
+use xim_parser::{
+    Attr,Attribute,AttributeName,CaretDirection,CaretStyle,CommitData,ForwardEventFlag,InputStyle,
+    InputStyleList,Point,PreeditDrawStatus,Request,XEvent
+};
+
+use crate::definitions::{PlatformError,Rectangle};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::protocol::Event;
+use x11rb::xcb_ffi::XCBConnection;
+use x11rb::wrapper::ConnectionExt as WrapperConnectionExt;
+
+/// Messages up to this size fit in a single client message, the longer ones are sent through a property.
+const CLIENT_MESSAGE_SIZE: usize = 20;
+
+/// Styles in order of preference, the pre-edit is drawn by the application whenever the server allows it.
+const INPUT_STYLES: [InputStyle; 4] = [
+    InputStyle::PREEDIT_CALLBACKS.union(InputStyle::STATUS_NOTHING),
+    InputStyle::PREEDIT_POSITION.union(InputStyle::STATUS_NOTHING),
+    InputStyle::PREEDIT_NOTHING.union(InputStyle::STATUS_NOTHING),
+    InputStyle::PREEDIT_NONE.union(InputStyle::STATUS_NONE),
+];
+
+#[derive(Debug,Clone,PartialEq)]
+/// Outcome of the messages of the input method server.
+pub enum XimEvent {
+    /// Pre-edit text of the focused input context, with the cursor as a byte range.
+    Preedit{text: String, cursor: Option<(usize,usize)>},
+    Commit{text: String},
+    /// Key event forwarded back by the server, since it didn't consume it.
+    Key(XEvent)
+}
+
+/// Input context of a window with the input method enabled.
+#[derive(Default)]
+struct InputContext {
+    /// Id assigned by the server, `None` until the context is created.
+    id: Option<u16>,
+    focused: bool,
+    spot: Option<Point>,
+    forward_event_mask: u32,
+    synchronous_event_mask: u32,
+    preedit: Vec<char>,
+    caret: usize,
+    caret_visible: bool,
+}
+impl InputContext {
+    fn preedit_event(&self) -> XimEvent {
+        let text: String = self.preedit.iter().collect();
+        let cursor = if self.caret_visible && !text.is_empty() {
+            let offset = self.preedit.iter().take(self.caret).map(|character|character.len_utf8()).sum();
+            Some((offset,offset))
+        } else {None};
+        XimEvent::Preedit{text,cursor}
+    }
+}
+
+/**
+State of the connection to the input method server, from the connection request to the input contexts of the windows.
+The requests are queued as encoded messages, to be sent by the transport with `take_messages`.
+*/
+pub struct XimProtocol {
+    locale: String,
+    /// Input method id, assigned once the input method is opened.
+    input_method_id: Option<u16>,
+    im_attributes: HashMap<AttributeName,u16>,
+    ic_attributes: HashMap<AttributeName,u16>,
+    /// Style of the input contexts, chosen once the server reported the supported ones.
+    input_style: Option<InputStyle>,
+    contexts: HashMap<u32,InputContext>,
+    /// Windows waiting for their input context, in the order of the creation requests.
+    creating: VecDeque<u32>,
+    messages: Vec<Vec<u8>>,
+    events: Vec<XimEvent>
+}
+impl XimProtocol {
+    /// Start the connection, the locale is the one the input method is opened with.
+    pub fn new(locale: String) -> Self {
+        let mut protocol = Self {
+            locale,
+            input_method_id: None,
+            im_attributes: HashMap::new(),
+            ic_attributes: HashMap::new(),
+            input_style: None,
+            contexts: HashMap::new(),
+            creating: VecDeque::new(),
+            messages: Vec::new(),
+            events: Vec::new()
+        };
+        protocol.send(Request::Connect{
+            endian: xim_parser::Endian::Native,
+            client_major_protocol_version: 1,
+            client_minor_protocol_version: 0,
+            client_auth_protocol_names: Vec::new()
+        });
+        protocol
+    }
+
+    /// Locale of the environment, without the encoding, as the input method servers list them.
+    pub fn default_locale() -> String {
+        ["LC_ALL","LC_CTYPE","LANG"].iter()
+            .filter_map(|name|std::env::var(name).ok())
+            .find(|locale|!locale.is_empty())
+            .map(|locale|locale.split(['.','@']).next().unwrap_or_default().to_string())
+            .filter(|locale|locale != "C" && locale != "POSIX")
+            .unwrap_or_else(||String::from("en_US"))
+    }
+
+    /// Encoded messages to send to the server.
+    pub fn take_messages(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.messages)
+    }
+
+    pub fn take_events(&mut self) -> Vec<XimEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /**
+    Create or destroy the input context of the window.
+    Contexts enabled before the input method is open are created as soon as it is.
+    */
+    pub fn set_enabled(&mut self, window: u32, enabled: bool) {
+        if enabled {
+            if self.contexts.contains_key(&window) {return;}
+            self.contexts.insert(window,InputContext{caret_visible: true,..Default::default()});
+            self.create_context(window);
+        }
+        else if let Some(context) = self.contexts.remove(&window) {
+            if context.focused && !context.preedit.is_empty() {
+                self.events.push(XimEvent::Preedit{text: String::new(),cursor: None});
+            }
+            if let (Some(input_method_id),Some(input_context_id)) = (self.input_method_id,context.id) {
+                self.send(Request::DestroyIc{input_method_id,input_context_id});
+            }
+        }
+    }
+
+    pub fn set_focus(&mut self, window: u32, focused: bool) {
+        let input_method_id = match self.input_method_id {
+            Some(input_method_id)=>input_method_id,
+            None=>{
+                if let Some(context) = self.contexts.get_mut(&window) {context.focused = focused;}
+                return;
+            }
+        };
+        let context = match self.contexts.get_mut(&window) {
+            Some(context)=>context,
+            None=>return
+        };
+        if context.focused == focused {return;}
+        context.focused = focused;
+        if let Some(input_context_id) = context.id {
+            match focused {
+                true=>self.send(Request::SetIcFocus{input_method_id,input_context_id}),
+                false=>self.send(Request::UnsetIcFocus{input_method_id,input_context_id})
+            }
+        }
+    }
+
+    /// Move the spot location under the cursor area, where the candidate window is placed.
+    pub fn set_cursor_area(&mut self, window: u32, area: &Rectangle<i32,u32>) {
+        let spot = Point {
+            x: area.position.x.clamp(i16::MIN as i32,i16::MAX as i32) as i16,
+            y: (area.position.y + area.size.height as i32).clamp(i16::MIN as i32,i16::MAX as i32) as i16,
+        };
+        let context = match self.contexts.get_mut(&window) {
+            Some(context)=>context,
+            None=>return
+        };
+        if context.spot.as_ref() == Some(&spot) {return;}
+        context.spot = Some(spot);
+        if let (Some(input_method_id),Some(input_context_id)) = (self.input_method_id,context.id) {
+            let ic_attributes = self.preedit_attributes(window).into_iter().collect();
+            self.send(Request::SetIcValues{input_method_id,input_context_id,ic_attributes});
+        }
+    }
+
+    /**
+    Forward the key event to the server, if the focused input context of its window asks for it.
+    Returns whether the event has been forwarded, in which case it comes back through `XimEvent::Key` unless consumed.
+    */
+    pub fn filter_key(&mut self, event: XEvent) -> bool {
+        let input_method_id = match self.input_method_id {
+            Some(input_method_id)=>input_method_id,
+            None=>return false
+        };
+        let context = match self.contexts.get(&event.event) {
+            Some(context) if context.focused=>context,
+            _=>return false
+        };
+        let input_context_id = match context.id {
+            Some(input_context_id)=>input_context_id,
+            None=>return false
+        };
+        let mask = match event.response_type & 0x7f {
+            KEY_PRESS_EVENT=>u32::from(EventMask::KEY_PRESS),
+            KEY_RELEASE_EVENT=>u32::from(EventMask::KEY_RELEASE),
+            _=>return false
+        };
+        if context.forward_event_mask & mask == 0 {return false;}
+        let flag = if context.synchronous_event_mask & mask != 0 {ForwardEventFlag::SYNCHRONOUS} else {ForwardEventFlag::empty()};
+        self.send(Request::ForwardEvent{input_method_id,input_context_id,flag,serial_number: event.sequence,xev: event});
+        true
+    }
+
+    /// Handle a message of the server.
+    pub fn handle_message(&mut self, message: &[u8]) -> Result<(),xim_parser::ReadError> {
+        let request: Request = xim_parser::read(message)?;
+        self.handle_request(request);
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: Request) {
+        match request {
+            Request::ConnectReply{..}=>{
+                let locale = self.locale.clone();
+                self.send(Request::Open{locale});
+            }
+            Request::OpenReply{input_method_id,im_attrs,ic_attrs}=>{
+                self.input_method_id = Some(input_method_id);
+                self.im_attributes = Self::attribute_ids(im_attrs);
+                self.ic_attributes = Self::attribute_ids(ic_attrs);
+                // Some servers expect the negotiation even for the default encoding.
+                self.send(Request::EncodingNegotiation{
+                    input_method_id,
+                    encodings: vec![String::from("COMPOUND_TEXT")],
+                    encoding_infos: Vec::new()
+                });
+            }
+            Request::EncodingNegotiationReply{input_method_id,..}=>{
+                match self.im_attributes.get(&AttributeName::QueryInputStyle) {
+                    Some(id)=>self.send(Request::GetImValues{input_method_id,im_attributes: vec![*id]}),
+                    None=>self.set_input_style(Vec::new())
+                }
+            }
+            Request::GetImValuesReply{im_attributes,..}=>{
+                let id = self.im_attributes.get(&AttributeName::QueryInputStyle).copied();
+                let styles = im_attributes.into_iter()
+                    .find(|attribute|Some(attribute.id) == id)
+                    .and_then(|attribute|xim_parser::read::<InputStyleList>(&attribute.value).ok())
+                    .map(|list|list.styles)
+                    .unwrap_or_default();
+                self.set_input_style(styles);
+            }
+            Request::CreateIcReply{input_method_id,input_context_id}=>{
+                let window = match self.creating.pop_front() {
+                    Some(window)=>window,
+                    None=>return
+                };
+                match self.contexts.get_mut(&window) {
+                    Some(context)=>{
+                        context.id = Some(input_context_id);
+                        if context.focused {self.send(Request::SetIcFocus{input_method_id,input_context_id});}
+                    }
+                    // Disabled in the meantime.
+                    None=>self.send(Request::DestroyIc{input_method_id,input_context_id})
+                }
+            }
+            Request::SetEventMask{input_context_id,forward_event_mask,synchronous_event_mask,..}=>{
+                if let Some(context) = self.context_mut(input_context_id) {
+                    context.forward_event_mask = forward_event_mask;
+                    context.synchronous_event_mask = synchronous_event_mask;
+                }
+            }
+            Request::ForwardEvent{input_method_id,input_context_id,flag,xev,..}=>{
+                self.events.push(XimEvent::Key(xev));
+                if flag.contains(ForwardEventFlag::SYNCHRONOUS) {
+                    self.send(Request::SyncReply{input_method_id,input_context_id});
+                }
+            }
+            Request::Commit{input_method_id,input_context_id,data}=>{
+                let (text,synchronous) = match data {
+                    CommitData::Chars{commited,syncronous}=>(commited,syncronous),
+                    CommitData::Both{commited,syncronous,..}=>(commited,syncronous),
+                    CommitData::Keysym{syncronous,..}=>{
+                        warn!("Keysym commits from the input method are not supported");
+                        (Vec::new(),syncronous)
+                    }
+                };
+                match xim_ctext::compound_text_to_utf8(&text) {
+                    Ok(text) if !text.is_empty()=>{
+                        if let Some(context) = self.context_mut(input_context_id) {
+                            context.preedit.clear();
+                            context.caret = 0;
+                        }
+                        self.events.push(XimEvent::Commit{text});
+                    }
+                    Ok(_)=>{}
+                    Err(err)=>error!("Failed to decode the text committed by the input method: {}",err)
+                }
+                if synchronous {self.send(Request::SyncReply{input_method_id,input_context_id});}
+            }
+            Request::Sync{input_method_id,input_context_id}=>{
+                self.send(Request::SyncReply{input_method_id,input_context_id});
+            }
+            Request::PreeditStart{input_method_id,input_context_id}=>{
+                // No limit on the pre-edit length.
+                self.send(Request::PreeditStartReply{input_method_id,input_context_id,return_value: -1});
+            }
+            Request::PreeditDraw{input_context_id,caret,chg_first,chg_length,status,preedit_string,..}=>{
+                let text = match status.contains(PreeditDrawStatus::NO_STRING) {
+                    true=>String::new(),
+                    false=>match xim_ctext::compound_text_to_utf8(&preedit_string) {
+                        Ok(text)=>text,
+                        Err(err)=>{
+                            error!("Failed to decode the pre-edit text of the input method: {}",err);
+                            return;
+                        }
+                    }
+                };
+                if let Some(context) = self.context_mut(input_context_id) {
+                    let first = (chg_first.max(0) as usize).min(context.preedit.len());
+                    let last = (first + chg_length.max(0) as usize).min(context.preedit.len());
+                    context.preedit.splice(first..last,text.chars());
+                    context.caret = (caret.max(0) as usize).min(context.preedit.len());
+                    let event = context.preedit_event();
+                    self.events.push(event);
+                }
+            }
+            Request::PreeditCaret{input_method_id,input_context_id,position,direction,style}=>{
+                let position = match self.context_mut(input_context_id) {
+                    Some(context)=>{
+                        context.caret = match direction {
+                            CaretDirection::AbsolutePosition=>position.max(0) as usize,
+                            CaretDirection::ForwardChar=>context.caret + 1,
+                            CaretDirection::BackwardChar=>context.caret.saturating_sub(1),
+                            CaretDirection::LineStart=>0,
+                            CaretDirection::LineEnd=>context.preedit.len(),
+                            _=>context.caret
+                        }.min(context.preedit.len());
+                        context.caret_visible = style != CaretStyle::Invisible;
+                        let event = context.preedit_event();
+                        let caret = context.caret as i32;
+                        self.events.push(event);
+                        caret
+                    }
+                    None=>position
+                };
+                self.send(Request::PreeditCaretReply{input_method_id,input_context_id,position});
+            }
+            Request::PreeditDone{input_context_id,..}=>{
+                if let Some(context) = self.context_mut(input_context_id) {
+                    let cleared = !context.preedit.is_empty();
+                    context.preedit.clear();
+                    context.caret = 0;
+                    if cleared {self.events.push(XimEvent::Preedit{text: String::new(),cursor: None});}
+                }
+            }
+            Request::Error{input_context_id,code,detail,..}=>{
+                error!("Input method error on the context {}: {:?} {}",input_context_id,code,detail);
+            }
+            _=>{}
+        }
+    }
+
+    fn attribute_ids(attributes: Vec<Attr>) -> HashMap<AttributeName,u16> {
+        attributes.into_iter().map(|attribute|(attribute.name,attribute.id)).collect()
+    }
+
+    fn set_input_style(&mut self, styles: Vec<InputStyle>) {
+        let input_style = INPUT_STYLES.iter()
+            .find(|style|styles.contains(style))
+            .copied()
+            .unwrap_or(InputStyle::PREEDIT_NOTHING | InputStyle::STATUS_NOTHING);
+        self.input_style = Some(input_style);
+        let windows: Vec<u32> = self.contexts.keys().copied().collect();
+        for window in windows {self.create_context(window);}
+    }
+
+    fn create_context(&mut self, window: u32) {
+        let (input_method_id,input_style) = match (self.input_method_id,self.input_style) {
+            (Some(input_method_id),Some(input_style))=>(input_method_id,input_style),
+            _=>return
+        };
+        let mut ic_attributes = Vec::new();
+        for (name,value) in [(AttributeName::InputStyle,input_style.bits()),(AttributeName::ClientWindow,window),(AttributeName::FocusWindow,window)].iter() {
+            if let Some(id) = self.ic_attributes.get(name) {
+                ic_attributes.push(Attribute{id: *id,value: xim_parser::write_to_vec(value)});
+            }
+        }
+        ic_attributes.extend(self.preedit_attributes(window));
+        self.creating.push_back(window);
+        self.send(Request::CreateIc{input_method_id,ic_attributes});
+    }
+
+    /// Nested pre-edit attributes, holding the spot location for the styles placing the candidate window.
+    fn preedit_attributes(&self, window: u32) -> Option<Attribute> {
+        let input_style = self.input_style?;
+        if !input_style.intersects(InputStyle::PREEDIT_CALLBACKS | InputStyle::PREEDIT_POSITION) {return None;}
+        let spot = self.contexts.get(&window)?.spot.as_ref()?;
+        let id = *self.ic_attributes.get(&AttributeName::PreeditAttributes)?;
+        let spot_id = *self.ic_attributes.get(&AttributeName::SpotLocation)?;
+        let mut value = Vec::new();
+        xim_parser::write_extend_vec(Attribute{id: spot_id,value: xim_parser::write_to_vec(spot)},&mut value);
+        Some(Attribute{id,value})
+    }
+
+    fn context_mut(&mut self, input_context_id: u16) -> Option<&mut InputContext> {
+        self.contexts.values_mut().find(|context|context.id == Some(input_context_id))
+    }
+
+    fn send(&mut self, request: Request) {
+        self.messages.push(xim_parser::write_to_vec(request));
+    }
+}
+
+fn client_message(format: u8, window: Window, type_: Atom, data: impl Into<ClientMessageData>) -> ClientMessageEvent {
+    ClientMessageEvent {response_type: CLIENT_MESSAGE_EVENT, format, sequence: 0, window, type_, data: data.into()}
+}
+
+struct XimAtoms {
+    xim_servers: Atom,
+    xconnect: Atom,
+    protocol: Atom,
+    moredata: Atom,
+    transport: Atom,
+}
+
+/**
+Connection to the input method server named by `XMODIFIERS`, or to the first registered one.
+The messages go through client messages and window properties, as for the Xlib clients.
+*/
+pub struct XimClient {
+    connection: Arc<XCBConnection>,
+    atoms: XimAtoms,
+    client_window: Window,
+    server_atom: Atom,
+    /// Window of the server to send the messages to, known once the transport is connected.
+    server_window: Option<Window>,
+    /// Start of a message split over multiple client messages.
+    partial_message: Vec<u8>,
+    property_serial: u32,
+    protocol: XimProtocol,
+}
+impl XimClient {
+    /// Look for the input method server and start the connection, `None` if there is no server.
+    pub fn new(connection: Arc<XCBConnection>, screen: usize) -> Result<Option<Self>, PlatformError> {
+        let intern = |name: &[u8]| -> Result<Atom,PlatformError> {Ok(connection.intern_atom(false, name)?.reply()?.atom)};
+        let atoms = XimAtoms {
+            xim_servers: intern(b"XIM_SERVERS")?,
+            xconnect: intern(b"_XIM_XCONNECT")?,
+            protocol: intern(b"_XIM_PROTOCOL")?,
+            moredata: intern(b"_XIM_MOREDATA")?,
+            transport: intern(b"TRANSPORT")?,
+        };
+        let root = connection.setup().roots[screen].root;
+
+        let modifiers = std::env::var("XMODIFIERS").unwrap_or_default();
+        let name = modifiers.split('@')
+            .find_map(|modifier|modifier.strip_prefix("im="))
+            .map(|name|name.trim().to_string())
+            .filter(|name|!name.is_empty() && name != "none");
+
+        let servers = connection.get_property(false, root, atoms.xim_servers, AtomEnum::ATOM, 0, u32::MAX)?.reply()?;
+        let servers: Vec<Atom> = match servers.value32() {
+            Some(servers)=>servers.collect(),
+            None=>return Ok(None)
+        };
+        let mut server_atom = None;
+        for atom in servers {
+            if connection.get_selection_owner(atom)?.reply()?.owner == x11rb::NONE {continue;}
+            let server_name = connection.get_atom_name(atom)?.reply()?.name;
+            let matches = match &name {
+                Some(name)=>server_name.strip_prefix(b"@server=") == Some(name.as_bytes()),
+                None=>true
+            };
+            if matches {
+                server_atom = Some(atom);
+                break;
+            }
+        }
+        let server_atom = match server_atom {
+            Some(server_atom)=>server_atom,
+            None=>return Ok(None)
+        };
+
+        let client_window = connection.generate_id()?;
+        connection.create_window(0, client_window, root, 0, 0, 1, 1, 0, WindowClass::INPUT_ONLY, 0, &CreateWindowAux::new())?.check()?;
+        // The transport is checked first, the server window is then obtained through `_XIM_XCONNECT`.
+        connection.convert_selection(client_window, server_atom, atoms.transport, atoms.transport, x11rb::CURRENT_TIME)?;
+        connection.flush()?;
+
+        Ok(Some(Self {
+            connection,
+            atoms,
+            client_window,
+            server_atom,
+            server_window: None,
+            partial_message: Vec::new(),
+            property_serial: 0,
+            protocol: XimProtocol::new(XimProtocol::default_locale()),
+        }))
+    }
+
+    /// Handle the events addressed to the input method client, returning whether the event was one of them.
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        let result = match event {
+            Event::SelectionNotify(event) if event.requestor == self.client_window=>self.handle_transport(event),
+            Event::ClientMessage(event) if event.window == self.client_window=>self.handle_client_message(event),
+            _=>return false
+        };
+        if let Err(err) = result.and_then(|_|self.flush()) {
+            error!("Input method connection failed: {}",err);
+        }
+        true
+    }
+
+    pub fn set_enabled(&mut self, window: u32, enabled: bool) -> Result<(), PlatformError> {
+        self.protocol.set_enabled(window,enabled);
+        self.flush()
+    }
+
+    pub fn set_focus(&mut self, window: u32, focused: bool) -> Result<(), PlatformError> {
+        self.protocol.set_focus(window,focused);
+        self.flush()
+    }
+
+    pub fn set_cursor_area(&mut self, window: u32, area: &Rectangle<i32,u32>) -> Result<(), PlatformError> {
+        self.protocol.set_cursor_area(window,area);
+        self.flush()
+    }
+
+    /// Forward the key event to the server, returning whether it has been.
+    pub fn filter_key(&mut self, event: &KeyPressEvent) -> bool {
+        let forwarded = self.protocol.filter_key(XEvent {
+            response_type: event.response_type,
+            detail: event.detail,
+            sequence: event.sequence,
+            time: event.time,
+            root: event.root,
+            event: event.event,
+            child: event.child,
+            root_x: event.root_x,
+            root_y: event.root_y,
+            event_x: event.event_x,
+            event_y: event.event_y,
+            state: event.state,
+            same_screen: event.same_screen,
+        });
+        if let Err(err) = self.flush() {
+            error!("Failed to forward the key event to the input method: {}",err);
+            return false;
+        }
+        forwarded
+    }
+
+    pub fn take_events(&mut self) -> Vec<XimEvent> {
+        self.protocol.take_events()
+    }
+
+    fn handle_transport(&mut self, event: &SelectionNotifyEvent) -> Result<(), PlatformError> {
+        if event.selection != self.server_atom || event.property != self.atoms.transport {return Ok(());}
+        let transport = self.connection.get_property(true, self.client_window, self.atoms.transport, AtomEnum::ANY, 0, u32::MAX)?.reply()?;
+        if !transport.value.starts_with(b"@transport=X/") {
+            return Err(PlatformError::Protocol(String::from("The input method server doesn't support the X transport")));
+        }
+        let owner = self.connection.get_selection_owner(self.server_atom)?.reply()?.owner;
+        let event = client_message(32, owner, self.atoms.xconnect, [self.client_window, 0, 0, 0, 0]);
+        self.connection.send_event(false, owner, EventMask::NO_EVENT, event)?;
+        Ok(())
+    }
+
+    fn handle_client_message(&mut self, event: &ClientMessageEvent) -> Result<(), PlatformError> {
+        if event.type_ == self.atoms.xconnect {
+            self.server_window = Some(event.data.as_data32()[0]);
+            return Ok(());
+        }
+        let message = if event.type_ == self.atoms.moredata && event.format == 8 {
+            self.partial_message.extend_from_slice(&event.data.as_data8());
+            return Ok(());
+        }
+        else if event.type_ == self.atoms.protocol && event.format == 8 {
+            let mut message = std::mem::take(&mut self.partial_message);
+            message.extend_from_slice(&event.data.as_data8());
+            message
+        }
+        else if event.type_ == self.atoms.protocol && event.format == 32 {
+            let [length, property, ..] = event.data.as_data32();
+            self.connection.get_property(true, self.client_window, property, AtomEnum::ANY, 0, length)?.reply()?.value
+        }
+        else {return Ok(());};
+        self.protocol.handle_message(&message)
+            .map_err(|err|PlatformError::Protocol(format!("Invalid input method message: {}",err)))
+    }
+
+    /// Send the pending messages, once the server window is known.
+    fn flush(&mut self) -> Result<(), PlatformError> {
+        let server_window = match self.server_window {
+            Some(server_window)=>server_window,
+            None=>return Ok(())
+        };
+        for message in self.protocol.take_messages() {
+            if message.len() <= CLIENT_MESSAGE_SIZE {
+                let mut data = [0u8; CLIENT_MESSAGE_SIZE];
+                data[..message.len()].copy_from_slice(&message);
+                let event = client_message(8, server_window, self.atoms.protocol, data);
+                self.connection.send_event(false, server_window, EventMask::NO_EVENT, event)?;
+            }
+            else {
+                // The properties are reused in turn, the server deletes them once read.
+                let name = format!("_XIM_DATA_{}",self.property_serial % 20);
+                self.property_serial = self.property_serial.wrapping_add(1);
+                let property = self.connection.intern_atom(false, name.as_bytes())?.reply()?.atom;
+                self.connection.change_property8(PropMode::APPEND, server_window, property, AtomEnum::STRING, &message)?;
+                let event = client_message(32, server_window, self.atoms.protocol, [message.len() as u32, property, 0, 0, 0]);
+                self.connection.send_event(false, server_window, EventMask::NO_EVENT, event)?;
+            }
+        }
+        self.connection.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+use std::os::raw::c_void;
+use xkbcommon::xkb;
+use x11rb::xcb_ffi::XCBConnection;
+
+#[link(name = "xkbcommon-x11")]
+extern "C" {
+    fn xkb_x11_get_core_keyboard_device_id(connection: *mut c_void) -> i32;
+    fn xkb_x11_keymap_new_from_device(context: *mut xkb::ffi::xkb_context, connection: *mut c_void, device_id: i32, flags: u32) -> *mut xkb::ffi::xkb_keymap;
+}
+
+/**
+Keymap of the core keyboard as the server has it, changes applied directly to it, like the ones of xmodmap, included.
+The XKB extension has to be initialized on the connection.
+*/
+pub fn core_keyboard_keymap(connection: &XCBConnection) -> Option<xkb::Keymap> {
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    let raw_connection = connection.get_raw_xcb_connection();
+    unsafe {
+        let device_id = xkb_x11_get_core_keyboard_device_id(raw_connection);
+        if device_id < 0 {return None;}
+        let keymap = xkb_x11_keymap_new_from_device(context.get_raw_ptr(), raw_connection, device_id, xkb::KEYMAP_COMPILE_NO_FLAGS);
+        if keymap.is_null() {None} else {Some(xkb::Keymap::from_raw_ptr(keymap))}
+    }
+}
@@ -0,0 +1,172 @@
+use xkbcommon::xkb;
+use crate::definitions::{Keymap,KeymapDescriptor,KeyboardEvent,KeyboardLayoutError,State,Modifiers};
+
+/// Offset between the evdev codes and the XKB keycodes.
+const EVDEV_OFFSET: u32 = 8;
+
+/**
+Keysyms of the modifier keys and the modifiers they stand for.
+When keys of different modifiers set the same modifier bit, the first one listed wins.
+*/
+static MODIFIER_KEYSYMS: &[(u32,Modifiers)] = &[
+    (0xffe9,Modifiers::ALT), // Alt_L
+    (0xffea,Modifiers::ALT), // Alt_R
+    (0xffeb,Modifiers::SUPER), // Super_L
+    (0xffec,Modifiers::SUPER), // Super_R
+    (0xfe03,Modifiers::ALT_GRAPH), // ISO_Level3_Shift
+    (0xff7e,Modifiers::ALT_GRAPH), // Mode_switch
+    (0xff7f,Modifiers::NUM_LOCK), // Num_Lock
+    (0xff14,Modifiers::SCROLL_LOCK), // Scroll_Lock
+    (0xffe7,Modifiers::META), // Meta_L
+    (0xffe8,Modifiers::META), // Meta_R
+    (0xffed,Modifiers::HYPER), // Hyper_L
+    (0xffee,Modifiers::HYPER), // Hyper_R
+];
+
+/**
+Modifier bits of a keymap and the modifiers they stand for.
+Shift, Lock and Control have fixed bits, while the others are found by pressing the modifier keys of the keymap,
+since they are assigned by its modifier map, like Alt to Mod1 and Super to Mod4 in the usual ones.
+*/
+fn modifier_mapping(keymap: &xkb::Keymap)->Vec<(u32,Modifiers)> {
+    let mut mapping = vec![(1 << 0,Modifiers::SHIFT),(1 << 1,Modifiers::CAPS_LOCK),(1 << 2,Modifiers::CONTROL)];
+    let mut claims = Vec::new();
+    for keycode in keymap.min_keycode()..=keymap.max_keycode() {
+        let mut state = xkb::State::new(keymap);
+        let keysym = state.key_get_one_sym(keycode);
+        if let Some(priority) = MODIFIER_KEYSYMS.iter().position(|(modifier_keysym,_)|*modifier_keysym == keysym) {
+            state.update_key(keycode,xkb::KeyDirection::Down);
+            claims.push((priority,state.serialize_mods(xkb::STATE_MODS_EFFECTIVE)));
+        }
+    }
+    claims.sort_by_key(|(priority,_)|*priority);
+    for (priority,mask) in claims {
+        for bit in (0..32).map(|index|1u32 << index).filter(|bit|mask & bit != 0) {
+            if mapping.iter().all(|(mapped,_)|*mapped != bit) {mapping.push((bit,MODIFIER_KEYSYMS[priority].1));}
+        }
+    }
+    mapping
+}
+
+/**
+Keymap and state of a keyboard, decoding the keys with xkbcommon.
+The state is either driven by the key presses and releases, or mirrored from a state tracked elsewhere, like the one of the X server.
+*/
+pub struct XkbKeyboard {
+    keymap: xkb::Keymap,
+    state: xkb::State,
+    modifiers: Vec<(u32,Modifiers)>,
+}
+impl XkbKeyboard {
+    /**
+    Compile the keymap of a descriptor, resolving its rules, model, layouts, variants and options against the XKB data of the system.
+    Changes applied directly to a server keymap, like the ones of xmodmap, are not part of it.
+    */
+    pub fn from_descriptor(descriptor: &KeymapDescriptor)->Result<Self,KeyboardLayoutError> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let options = Some(descriptor.options_string()).filter(|options|!options.is_empty());
+        let keymap = xkb::Keymap::new_from_names(
+            &context,
+            &descriptor.rules,
+            &descriptor.model,
+            &descriptor.layouts_string(),
+            &descriptor.variants_string(),
+            options,
+            xkb::KEYMAP_COMPILE_NO_FLAGS
+        ).ok_or(KeyboardLayoutError::InvalidKeymap)?;
+        Ok(Self::from_keymap(keymap))
+    }
+
+    /// Compile a keymap in the XKB text format, like the ones sent by the Wayland compositors.
+    pub fn from_string(text: &str)->Result<Self,KeyboardLayoutError> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        // The text is sent NUL terminated.
+        let text = String::from(text.trim_end_matches('\0'));
+        let keymap = xkb::Keymap::new_from_string(&context,text,xkb::KEYMAP_FORMAT_TEXT_V1,xkb::KEYMAP_COMPILE_NO_FLAGS)
+            .ok_or(KeyboardLayoutError::InvalidKeymap)?;
+        Ok(Self::from_keymap(keymap))
+    }
+
+    pub fn from_keymap(keymap: xkb::Keymap)->Self {
+        let state = xkb::State::new(&keymap);
+        let modifiers = modifier_mapping(&keymap);
+        Self {keymap,state,modifiers}
+    }
+
+    /// Keymap in the XKB text format, along with the descriptor it comes from.
+    pub fn keymap(&self, descriptor: KeymapDescriptor)->Keymap {
+        Keymap {descriptor,text: self.keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1)}
+    }
+
+    pub fn num_layouts(&self)->u32 {self.keymap.num_layouts()}
+
+    /// Keysym of the key with the given evdev code, in the current state.
+    pub fn key_sym(&self, code: u32)->u32 {
+        self.state.key_get_one_sym(code + EVDEV_OFFSET)
+    }
+
+    /// Printable text of the key with the given evdev code, in the current state, control characters excluded.
+    pub fn key_text(&self, code: u32)->Option<String> {
+        Some(self.state.key_get_utf8(code + EVDEV_OFFSET)).filter(|text|!text.is_empty() && !text.chars().any(char::is_control))
+    }
+
+    /// Apply a key press or release to the state, returning whether the modifiers or the group changed.
+    pub fn update_key(&mut self, code: u32, state: State)->bool {
+        let direction = match state {
+            State::Down=>xkb::KeyDirection::Down,
+            State::Up=>xkb::KeyDirection::Up
+        };
+        self.state.update_key(code + EVDEV_OFFSET,direction) != 0
+    }
+
+    /// Replace the modifiers and the group with the ones of a state tracked elsewhere.
+    pub fn update_mask(&mut self, depressed: u32, latched: u32, locked: u32, depressed_group: u32, latched_group: u32, locked_group: u32) {
+        self.state.update_mask(depressed,latched,locked,depressed_group,latched_group,locked_group);
+    }
+
+    /// Lock a layout group, keeping the modifiers.
+    pub fn lock_group(&mut self, group: u32)->Result<(),KeyboardLayoutError> {
+        if group >= self.num_layouts() {return Err(KeyboardLayoutError::InvalidGroup);}
+        let (depressed,latched,locked) = self.mods();
+        self.state.update_mask(depressed,latched,locked,0,0,group);
+        Ok(())
+    }
+
+    /// Forget the keys held down, keeping the locks, for when their releases cannot be seen.
+    pub fn release_keys(&mut self) {
+        let (_,_,locked) = self.mods();
+        let group = self.state.serialize_layout(xkb::STATE_LAYOUT_LOCKED);
+        self.state = xkb::State::new(&self.keymap);
+        self.state.update_mask(0,0,locked,0,0,group);
+    }
+
+    /// Depressed, latched and locked modifier masks.
+    pub fn mods(&self)->(u32,u32,u32) {
+        (
+            self.state.serialize_mods(xkb::STATE_MODS_DEPRESSED),
+            self.state.serialize_mods(xkb::STATE_MODS_LATCHED),
+            self.state.serialize_mods(xkb::STATE_MODS_LOCKED)
+        )
+    }
+
+    /// Effective layout group.
+    pub fn group(&self)->u32 {
+        self.state.serialize_layout(xkb::STATE_LAYOUT_EFFECTIVE)
+    }
+
+    /// Modifiers of a mask of the keymap, the bits without a known modifier are left out.
+    pub fn modifiers(&self, mask: u32)->Modifiers {
+        self.modifiers.iter().filter(|(bit,_)|mask & bit != 0).fold(Modifiers::empty(),|modifiers,(_,modifier)|modifiers | *modifier)
+    }
+
+    /// Modifiers and group of the current state.
+    pub fn modifiers_event(&self)->KeyboardEvent {
+        let (depressed,latched,locked) = self.mods();
+        KeyboardEvent::Modifiers{
+            depressed: self.modifiers(depressed),
+            latched: self.modifiers(latched),
+            locked: self.modifiers(locked),
+            group: self.group()
+        }
+    }
+}
@@ -1,79 +1,444 @@
 pub mod partial_backends;
 
+mod builder;
+pub use builder::{PlatformBuilder,BACKEND_ENV_VAR};
+
+#[cfg(target_os = "linux")]
+mod poller;
+#[cfg(target_os = "linux")]
+use poller::{Poller,EventFd,TimerFd};
+
+#[cfg(target_os = "linux")]
+mod timestamp;
+
+#[cfg(target_os = "linux")]
+mod key_repeater;
+#[cfg(target_os = "linux")]
+use key_repeater::KeyRepeater;
+#[cfg(target_os = "linux")]
+pub use key_repeater::{DEFAULT_REPEAT_RATE,DEFAULT_REPEAT_DELAY};
+
+#[cfg(target_os = "linux")]
+mod handle;
+#[cfg(target_os = "linux")]
+pub use handle::PlatformHandle;
+
+#[cfg(target_os = "linux")]
+mod calloop_source;
+#[cfg(target_os = "linux")]
+pub use calloop_source::PlatformSource;
+
+#[cfg(target_os = "linux")]
+mod async_platform;
+#[cfg(target_os = "linux")]
+pub use async_platform::{AsyncPlatform,NextEvent,RequestSink};
+
+#[cfg(all(target_os = "linux",feature="linux_platform"))]
+mod xkb_registry;
+#[cfg(all(target_os = "linux",feature="linux_platform"))]
+pub use xkb_registry::{XkbRegistry,XkbModel,XkbLayout,XkbVariant,XkbOptionGroup,XkbOption};
+
+#[cfg(target_os = "linux")]
+mod keymap_file;
+#[cfg(target_os = "linux")]
+pub use keymap_file::KeymapFile;
+
 #[cfg(all(target_os = "linux",feature="linux_platform"))]
-mod linux;
+pub(crate) mod linux;
+
+#[cfg(feature="virtual_platform")]
+mod virtual_platform;
+#[cfg(feature="virtual_platform")]
+pub use virtual_platform::VirtualPlatform;
+
+#[cfg(feature="record_replay")]
+mod recorder;
+#[cfg(feature="record_replay")]
+pub use recorder::{Recorder,RecordEntry};
+
+#[cfg(feature="record_replay")]
+mod replay_platform;
+#[cfg(feature="record_replay")]
+pub use replay_platform::{ReplayPlatform,ReplayMode};
 
 #[cfg(feature="state_tracker")]
 mod state_tracker;
 #[cfg(feature="state_tracker")]
-use state_tracker::StateTracker;
+pub use state_tracker::{StateTracker,SeatState,SurfaceState,KeyboardState};
 
-#[cfg(feature="post_processing")]
-mod post_processing;
-#[cfg(feature="post_processing")]
-use post_processing::PostProcessing;
+mod processing;
+pub use processing::{EventProcessor,EventPipeline,ResizeCoalescer,Deduplicator,Filter,MotionCoalescer,MotionHistory,GestureRecognizer,GestureConfig};
 
 use crate::definitions::*;
 
+/**
+Backends the platform can be driven by.
+*/
+// A single backend lives for the whole platform, so the size of the largest one is not worth a box.
+#[allow(clippy::large_enum_variant)]
+enum Backend {
+    #[cfg(all(target_os = "linux",feature="linux_platform"))]
+    Linux(linux::LinuxPlatform),
+    #[cfg(feature="virtual_platform")]
+    Virtual(VirtualPlatform),
+    #[cfg(feature="record_replay")]
+    Replay(ReplayPlatform),
+}
+
+#[cfg(target_os = "linux")]
+impl std::os::unix::io::AsRawFd for Backend {
+    fn as_raw_fd(&self)->std::os::unix::io::RawFd {
+        match self {
+            #[cfg(all(target_os = "linux",feature="linux_platform"))]
+            Self::Linux(platform) => platform.as_raw_fd(),
+            #[cfg(feature="virtual_platform")]
+            Self::Virtual(platform) => platform.as_raw_fd(),
+            #[cfg(feature="record_replay")]
+            Self::Replay(platform) => platform.as_raw_fd(),
+            // Without any platform feature the enum is empty and the match needs an arm.
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl PlatformBackend for Backend {
+    fn platform_type(&self)->PlatformType {
+        match self {
+            #[cfg(all(target_os = "linux",feature="linux_platform"))]
+            Self::Linux(platform) => platform.platform_type(),
+            #[cfg(feature="virtual_platform")]
+            Self::Virtual(platform) => platform.platform_type(),
+            #[cfg(feature="record_replay")]
+            Self::Replay(platform) => platform.platform_type(),
+            // Without any platform feature the enum is empty and the match needs an arm.
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    }
+    fn events(&mut self) -> Vec<Event> {
+        match self {
+            #[cfg(all(target_os = "linux",feature="linux_platform"))]
+            Self::Linux(platform) => platform.events(),
+            #[cfg(feature="virtual_platform")]
+            Self::Virtual(platform) => platform.events(),
+            #[cfg(feature="record_replay")]
+            Self::Replay(platform) => platform.events(),
+            // Without any platform feature the enum is empty and the match needs an arm.
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    }
+    #[allow(unused_variables)]
+    fn requests(&mut self, requests: Vec<Request>) {
+        match self {
+            #[cfg(all(target_os = "linux",feature="linux_platform"))]
+            Self::Linux(platform) => platform.requests(requests),
+            #[cfg(feature="virtual_platform")]
+            Self::Virtual(platform) => platform.requests(requests),
+            #[cfg(feature="record_replay")]
+            Self::Replay(platform) => platform.requests(requests),
+            // Without any platform feature the enum is empty and the match needs an arm.
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    }
+}
+
 /**
 Entry point of the library, it allows the user to manage input events,
 monitor outputs and surface creations under a unified abstraction.
 */
 pub struct Platform {
-    #[cfg(all(target_os = "linux",feature="linux_platform"))]
-    backend: linux::LinuxPlatform,
+    backend: Backend,
+
+    #[cfg(target_os = "linux")]
+    poller: Poller,
+    #[cfg(target_os = "linux")]
+    waker: std::sync::Arc<EventFd>,
+    #[cfg(target_os = "linux")]
+    timer: TimerFd,
+    #[cfg(target_os = "linux")]
+    key_repeater: Option<KeyRepeater>,
+    /// Events generated by the platform itself, reported on the next `events` call.
+    pending_events: Vec<Event>,
+    #[cfg(target_os = "linux")]
+    handle_sender: std::sync::mpsc::Sender<Request>,
+    #[cfg(target_os = "linux")]
+    handle_receiver: std::sync::mpsc::Receiver<Request>,
 
     #[cfg(feature="state_tracker")]
     state_tracker: StateTracker,
 
-    #[cfg(feature="post_processing")]
-    post_processing: PostProcessing
+    pipeline: EventPipeline
 }
 impl Platform {
+    /**
+    Create a platform using the first available backend.
+    Panics if no backend is available, use `Platform::builder` to handle the failure.
+    */
     pub fn new(external_contexts: Vec<Box<dyn ExternalContext>>) -> Self {
-        #[cfg(all(target_os = "linux",feature="linux_platform"))]
-        let backend = linux::LinuxPlatform::new(external_contexts).unwrap();
+        match Self::builder().external_contexts(external_contexts).build() {
+            Ok(platform)=>platform,
+            Err(err)=>panic!("{}",err)
+        }
+    }
+
+    /// Create a builder to customize the backend selection.
+    pub fn builder() -> PlatformBuilder {
+        PlatformBuilder::new()
+    }
+
+    fn from_backend(backend: Backend) -> Result<Self,PlatformError> {
+        #[cfg(target_os = "linux")]
+        let (poller,waker,timer) = {
+            use std::os::unix::io::AsRawFd;
+            let poller = Poller::new()?;
+            let waker = EventFd::new()?;
+            let timer = TimerFd::new()?;
+            poller.add(backend.as_raw_fd())?;
+            poller.add(waker.as_raw_fd())?;
+            poller.add(timer.as_raw_fd())?;
+            (poller,std::sync::Arc::new(waker),timer)
+        };
+        #[cfg(target_os = "linux")]
+        let (handle_sender,handle_receiver) = std::sync::mpsc::channel();
 
         #[cfg(feature="state_tracker")]
         let state_tracker = StateTracker::new();
 
+        #[allow(unused_mut)]
+        let mut pipeline = EventPipeline::new();
         #[cfg(feature="post_processing")]
-        let post_processing = PostProcessing::new();
+        {
+            pipeline.push(ResizeCoalescer::new());
+            pipeline.push(Deduplicator::new());
+        }
 
-        Self {
-            #[cfg(feature="any_platform")]
+        Ok(Self {
             backend,
+            #[cfg(target_os = "linux")]
+            poller,
+            #[cfg(target_os = "linux")]
+            waker,
+            #[cfg(target_os = "linux")]
+            timer,
+            #[cfg(target_os = "linux")]
+            key_repeater: None,
+            pending_events: Vec::new(),
+            #[cfg(target_os = "linux")]
+            handle_sender,
+            #[cfg(target_os = "linux")]
+            handle_receiver,
             #[cfg(feature="state_tracker")]
             state_tracker,
-            #[cfg(feature="post_processing")]
-            post_processing,
+            pipeline,
+        })
+    }
+
+    /**
+    Processors applied to the events before they are returned.
+    With the `post_processing` feature, it starts with a `ResizeCoalescer` and a `Deduplicator`.
+    */
+    pub fn pipeline(&self) -> &EventPipeline {
+        &self.pipeline
+    }
+    pub fn pipeline_mut(&mut self) -> &mut EventPipeline {
+        &mut self.pipeline
+    }
+
+    #[cfg(feature="state_tracker")]
+    /// Current state of the platform, as of the last events returned.
+    pub fn state(&self) -> &StateTracker {
+        &self.state_tracker
+    }
+
+    #[cfg(feature="state_tracker")]
+    /**
+    Events reflecting the current state, as a late subscriber would have received them from startup.
+    They are not fed to the state tracker, since they don't change the state.
+    */
+    pub fn synthetic_events(&self) -> Vec<Event> {
+        self.state_tracker.synthetic_events()
+    }
+
+    #[cfg(target_os = "linux")]
+    /// Create a handle to send requests from other threads.
+    pub fn handle(&self) -> PlatformHandle {
+        PlatformHandle::new(self.handle_sender.clone(),self.waker.clone())
+    }
+
+    #[cfg(target_os = "linux")]
+    /**
+    Block until some events are available or the timeout expires, then return them.
+    A `None` timeout waits indefinitely.
+    */
+    pub fn wait_events(&mut self, timeout: Option<std::time::Duration>) -> Vec<Event> {
+        let deadline = timeout.map(|timeout|std::time::Instant::now() + timeout);
+        loop {
+            let events = self.events();
+            if !events.is_empty() {return events;}
+
+            let timeout = match deadline {
+                Some(deadline)=>{
+                    let now = std::time::Instant::now();
+                    if now >= deadline {return events;}
+                    Some(deadline - now)
+                }
+                None=>None
+            };
+            match self.poller.wait(timeout) {
+                Ok(_)=>{}
+                Err(err)=>{
+                    log::error!("Failed to wait for events: {}",err);
+                    return events;
+                }
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    /**
+    Generate the key repeats in software, instead of relying on the backend.
+    Repeats are reported as `KeyboardEvent::Key` events with `repeat` set,
+    while `KeyboardRequest::SetAutoRepeat` requests configure the software repeat, taking the rate in repeats per second.
+    */
+    pub fn set_software_repeat(&mut self, enabled: bool) {
+        match (enabled,self.key_repeater.is_some()) {
+            (true,false)=>self.key_repeater = Some(KeyRepeater::new(DEFAULT_REPEAT_RATE,DEFAULT_REPEAT_DELAY)),
+            (false,true)=>self.key_repeater = None,
+            _=>{}
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    /// Wake up the platform at the earliest deadline of the pipeline or of the key repeat.
+    fn arm_timer(&mut self) {
+        self.timer.clear();
+        let repeat_deadline = self.key_repeater.as_ref().and_then(KeyRepeater::deadline);
+        let deadline = match (self.pipeline.deadline(),repeat_deadline) {
+            (Some(deadline1),Some(deadline2))=>Some(std::cmp::min(deadline1,deadline2)),
+            (deadline1,deadline2)=>deadline1.or(deadline2)
+        };
+        let delay = deadline.map(|deadline|deadline.duration_since(EventTime::now()));
+        if let Err(err) = self.timer.set(delay) {
+            log::error!("Failed to set the platform timer: {}",err);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn process_handle_requests(&mut self) {
+        self.waker.clear();
+        let requests: Vec<Request> = self.handle_receiver.try_iter().collect();
+        if !requests.is_empty() {
+            PlatformBackend::requests(self,requests);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    /// Apply the auto repeat requests to the software repeat, if enabled, returning the other requests.
+    fn handle_repeat_requests(&mut self, requests: Vec<Request>) -> Vec<Request> {
+        let key_repeater = match &mut self.key_repeater {
+            Some(key_repeater)=>key_repeater,
+            None=>return requests
+        };
+
+        let mut remaining = Vec::with_capacity(requests.len());
+        for request in requests {
+            match request {
+                Request::Seat{token,request: SeatRequest::Keyboard(KeyboardRequest::SetAutoRepeat{rate,delay})}=>{
+                    key_repeater.configure(rate,delay);
+                    let time = EventTime::now();
+                    for id in key_repeater.keyboards() {
+                        let event = KeyboardEvent::AutoRepeat{rate,delay}.into();
+                        self.pending_events.push(Event::Seat{time,id,event});
+                    }
+                    if let Some(token) = token {
+                        self.pending_events.push(Event::Request{time,token,event: RequestEvent::Completed});
+                    }
+                    self.waker.notify();
+                }
+                request=>remaining.push(request)
+            }
+        }
+        remaining
+    }
+
+    #[cfg(feature="virtual_platform")]
+    /// Access the underlying virtual platform, if the platform is driven by one.
+    pub fn as_virtual_mut(&mut self) -> Option<&mut VirtualPlatform> {
+        match &mut self.backend {
+            Backend::Virtual(platform) => Some(platform),
+            #[allow(unreachable_patterns)]
+            _ => None
+        }
+    }
+}
+
+#[cfg(feature="virtual_platform")]
+impl From<VirtualPlatform> for Platform {
+    /// Panics if the platform fds can't be created, use `Platform::builder` to handle the failure.
+    fn from(platform: VirtualPlatform) -> Self {
+        match Self::from_backend(Backend::Virtual(platform)) {
+            Ok(platform)=>platform,
+            Err(err)=>panic!("{}",err)
+        }
+    }
+}
+
+#[cfg(feature="record_replay")]
+impl From<ReplayPlatform> for Platform {
+    /// Panics if the platform fds can't be created.
+    fn from(platform: ReplayPlatform) -> Self {
+        match Self::from_backend(Backend::Replay(platform)) {
+            Ok(platform)=>platform,
+            Err(err)=>panic!("{}",err)
         }
     }
 }
 
 impl PlatformBackend for Platform {
-    fn platform_type(&self)->PlatformType {PlatformType::Compositor}
+    fn platform_type(&self)->PlatformType {self.backend.platform_type()}
     fn events(&mut self) -> Vec<Event> {
-        #[cfg(not(feature="any_platform"))]
-        let events = Vec::new();
+        #[cfg(target_os = "linux")]
+        self.process_handle_requests();
 
-        #[cfg(feature="any_platform")]
-        let events = self.backend.events();
+        let mut events: Vec<Event> = self.pending_events.drain(..).collect();
+        events.append(&mut self.backend.events());
 
-        #[cfg(feature="post_processing")]
-        let events = self.post_processing.process(events);
+        #[cfg(target_os = "linux")]
+        let events = match &mut self.key_repeater {
+            Some(key_repeater)=>key_repeater.process(events),
+            None=>events
+        };
+
+        let events = self.pipeline.process(events);
+
+        #[cfg(target_os = "linux")]
+        self.arm_timer();
 
         #[cfg(feature="state_tracker")]
         self.state_tracker.update(&events);
 
         events
     }
-    fn requests(&mut self, requests: Vec<Request>) {self.backend.requests(requests);}
+    fn requests(&mut self, requests: Vec<Request>) {
+        #[cfg(target_os = "linux")]
+        let requests = self.handle_repeat_requests(requests);
+
+        if !requests.is_empty() {
+            self.backend.requests(requests);
+            // The backend may have queued events without making its fd readable.
+            #[cfg(target_os = "linux")]
+            self.waker.notify();
+        }
+    }
 }
 
 #[cfg(target_os = "linux")]
+/// The returned fd merges the backend fd, the one woken up by the `PlatformHandle`s and the timer of the pipeline and key repeat.
 impl std::os::unix::io::AsRawFd for Platform {
     fn as_raw_fd(&self)->std::os::unix::io::RawFd {
-        self.backend.as_raw_fd()
+        self.poller.as_raw_fd()
     }
 }
@@ -0,0 +1,131 @@
+use std::io;
+use std::os::unix::io::{AsRawFd,RawFd};
+use std::time::Duration;
+
+fn check(result: libc::c_int)->io::Result<libc::c_int> {
+    if result < 0 {Err(io::Error::last_os_error())}
+    else {Ok(result)}
+}
+
+/**
+Epoll instance merging several fds into a single one.
+The epoll fd is readable as long as any of the registered fds is readable,
+so it can be handed to an external event loop in place of them.
+*/
+pub(crate) struct Poller {
+    epoll: RawFd
+}
+impl Poller {
+    pub fn new()->io::Result<Self> {
+        let epoll = check(unsafe{libc::epoll_create1(libc::EPOLL_CLOEXEC)})?;
+        Ok(Self {epoll})
+    }
+
+    /// Register a fd, level triggered, for read readiness.
+    pub fn add(&self, fd: RawFd)->io::Result<()> {
+        let mut event = libc::epoll_event {events: libc::EPOLLIN as u32, u64: fd as u64};
+        check(unsafe{libc::epoll_ctl(self.epoll,libc::EPOLL_CTL_ADD,fd,&mut event)}).map(|_|())
+    }
+
+    /**
+    Block until one of the registered fds is readable or the timeout expires.
+    Returns whether a fd became readable.
+    */
+    pub fn wait(&self, timeout: Option<Duration>)->io::Result<bool> {
+        let timeout = match timeout {
+            // Round up, so that the wait never returns before the timeout.
+            Some(timeout)=>{
+                let millis = timeout.as_millis() + if timeout.subsec_nanos() % 1_000_000 > 0 {1} else {0};
+                std::cmp::min(millis,libc::c_int::MAX as u128) as libc::c_int
+            }
+            None=>-1
+        };
+        let mut event = libc::epoll_event {events: 0, u64: 0};
+        loop {
+            match check(unsafe{libc::epoll_wait(self.epoll,&mut event,1,timeout)}) {
+                Ok(count)=>return Ok(count > 0),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted=>continue,
+                Err(err)=>return Err(err)
+            }
+        }
+    }
+}
+impl AsRawFd for Poller {
+    fn as_raw_fd(&self)->RawFd {self.epoll}
+}
+impl Drop for Poller {
+    fn drop(&mut self) {
+        unsafe{libc::close(self.epoll);}
+    }
+}
+
+/// Non blocking eventfd used to wake up the platform from other threads.
+pub(crate) struct EventFd {
+    fd: RawFd
+}
+impl EventFd {
+    pub fn new()->io::Result<Self> {
+        let fd = check(unsafe{libc::eventfd(0,libc::EFD_CLOEXEC | libc::EFD_NONBLOCK)})?;
+        Ok(Self {fd})
+    }
+
+    /// Make the fd readable.
+    pub fn notify(&self) {
+        let value: u64 = 1;
+        // The only possible failure is a counter overflow, which still leaves the fd readable.
+        unsafe{libc::write(self.fd,&value as *const u64 as *const libc::c_void,std::mem::size_of::<u64>());}
+    }
+
+    /// Reset the fd to not readable.
+    pub fn clear(&self) {
+        let mut value: u64 = 0;
+        unsafe{libc::read(self.fd,&mut value as *mut u64 as *mut libc::c_void,std::mem::size_of::<u64>());}
+    }
+}
+impl AsRawFd for EventFd {
+    fn as_raw_fd(&self)->RawFd {self.fd}
+}
+impl Drop for EventFd {
+    fn drop(&mut self) {
+        unsafe{libc::close(self.fd);}
+    }
+}
+
+/// Non blocking timerfd on the monotonic clock.
+pub(crate) struct TimerFd {
+    fd: RawFd
+}
+impl TimerFd {
+    pub fn new()->io::Result<Self> {
+        let fd = check(unsafe{libc::timerfd_create(libc::CLOCK_MONOTONIC,libc::TFD_CLOEXEC | libc::TFD_NONBLOCK)})?;
+        Ok(Self {fd})
+    }
+
+    /// Make the fd readable after the delay, or disarm it with `None`.
+    pub fn set(&self, delay: Option<Duration>)->io::Result<()> {
+        let value = match delay {
+            // A zero value would disarm the timer, so expire as soon as possible instead.
+            Some(delay)=>libc::timespec {
+                tv_sec: delay.as_secs() as libc::time_t,
+                tv_nsec: std::cmp::max(delay.subsec_nanos(),if delay.as_secs() == 0 {1} else {0}) as libc::c_long
+            },
+            None=>libc::timespec {tv_sec: 0, tv_nsec: 0}
+        };
+        let spec = libc::itimerspec {it_interval: libc::timespec {tv_sec: 0, tv_nsec: 0}, it_value: value};
+        check(unsafe{libc::timerfd_settime(self.fd,0,&spec,std::ptr::null_mut())}).map(|_|())
+    }
+
+    /// Reset the fd to not readable, until the timer expires again.
+    pub fn clear(&self) {
+        let mut value: u64 = 0;
+        unsafe{libc::read(self.fd,&mut value as *mut u64 as *mut libc::c_void,std::mem::size_of::<u64>());}
+    }
+}
+impl AsRawFd for TimerFd {
+    fn as_raw_fd(&self)->RawFd {self.fd}
+}
+impl Drop for TimerFd {
+    fn drop(&mut self) {
+        unsafe{libc::close(self.fd);}
+    }
+}
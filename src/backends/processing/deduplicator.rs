@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use crate::definitions::*;
+use super::EventProcessor;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+enum Target {
+    Seat(SeatId),
+    Output(OutputId),
+    Surface(SurfaceId),
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+enum Property {
+    CursorPosition,
+    CursorMode,
+    CursorVisibility,
+    KeyboardModifiers,
+    OutputMode,
+    OutputPosition,
+    SurfaceSize,
+    SurfaceMode,
+    SurfaceFocus,
+}
+
+/**
+Drop the events reporting a value equal to the last one reported, like a resize to the current size.
+Only events carrying a state are considered, so repeated inputs like scrolls and key presses are never dropped.
+*/
+#[derive(Default)]
+pub struct Deduplicator {
+    last_values: HashMap<(Target,Property),Event>
+}
+impl Deduplicator {
+    pub fn new()->Self {
+        Self {last_values: HashMap::new()}
+    }
+
+    /// Whether the event must be forwarded, updating the last known values.
+    fn check(&mut self, event: &Event)->bool {
+        let (target,property) = match event {
+            Event::Seat{id,event,..}=>{
+                let target = Target::Seat(*id);
+                match event {
+                    SeatEvent::Cursor(CursorEvent::AbsoluteMovement{..})=>(target,Property::CursorPosition),
+                    SeatEvent::Cursor(CursorEvent::ModeChanged(_))=>(target,Property::CursorMode),
+                    SeatEvent::Cursor(CursorEvent::VisibilityChanged(_))=>(target,Property::CursorVisibility),
+                    SeatEvent::Keyboard(KeyboardEvent::Modifiers{..})=>(target,Property::KeyboardModifiers),
+                    SeatEvent::Keyboard(KeyboardEvent::Removed)=>{
+                        self.last_values.remove(&(target,Property::KeyboardModifiers));
+                        return true;
+                    }
+                    // Positions are relative to the surface under the cursor, so they restart from scratch.
+                    SeatEvent::Cursor(CursorEvent::Entered{..}) | SeatEvent::Cursor(CursorEvent::Left{..})=>{
+                        self.last_values.remove(&(target,Property::CursorPosition));
+                        return true;
+                    }
+                    SeatEvent::Removed | SeatEvent::Cursor(CursorEvent::Removed)=>{
+                        self.forget(target);
+                        return true;
+                    }
+                    _=>return true
+                }
+            }
+            Event::Output{id,event,..}=>{
+                let target = Target::Output(*id);
+                match event {
+                    OutputEvent::ModeChanged(_)=>(target,Property::OutputMode),
+                    OutputEvent::Moved(_)=>(target,Property::OutputPosition),
+                    OutputEvent::Removed=>{
+                        self.forget(target);
+                        return true;
+                    }
+                    _=>return true
+                }
+            }
+            Event::Surface{id,event,..}=>{
+                let target = Target::Surface(*id);
+                match event {
+                    SurfaceEvent::Resized(_)=>(target,Property::SurfaceSize),
+                    SurfaceEvent::ModeChanged(_)=>(target,Property::SurfaceMode),
+                    SurfaceEvent::Focused(_)=>(target,Property::SurfaceFocus),
+                    SurfaceEvent::Removed=>{
+                        self.forget(target);
+                        return true;
+                    }
+                    _=>return true
+                }
+            }
+            Event::Request{..}=>return true
+        };
+
+        match self.last_values.get(&(target,property)) {
+            Some(last) if same_value(last,event)=>false,
+            _=>{
+                self.last_values.insert((target,property),event.clone());
+                true
+            }
+        }
+    }
+
+    fn forget(&mut self, target: Target) {
+        self.last_values.retain(|(current,_),_|*current != target);
+    }
+}
+
+/// Compare two events ignoring their time.
+fn same_value(event1: &Event, event2: &Event)->bool {
+    match (event1,event2) {
+        (Event::Seat{event: event1,..},Event::Seat{event: event2,..})=>event1 == event2,
+        (Event::Output{event: event1,..},Event::Output{event: event2,..})=>event1 == event2,
+        (Event::Surface{event: event1,..},Event::Surface{event: event2,..})=>event1 == event2,
+        _=>false
+    }
+}
+
+impl EventProcessor for Deduplicator {
+    fn process(&mut self, events: Vec<Event>)->Vec<Event> {
+        events.into_iter().filter(|event|self.check(event)).collect()
+    }
+}
@@ -0,0 +1,18 @@
+use crate::definitions::Event;
+use super::EventProcessor;
+
+/// Forward only the events matching a predicate.
+pub struct Filter<F: FnMut(&Event)->bool> {
+    predicate: F
+}
+impl<F: FnMut(&Event)->bool> Filter<F> {
+    pub fn new(predicate: F)->Self {
+        Self {predicate}
+    }
+}
+
+impl<F: FnMut(&Event)->bool> EventProcessor for Filter<F> {
+    fn process(&mut self, events: Vec<Event>)->Vec<Event> {
+        events.into_iter().filter(|event|(self.predicate)(event)).collect()
+    }
+}
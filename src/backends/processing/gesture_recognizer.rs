@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::definitions::*;
+use super::EventProcessor;
+
+#[derive(Debug,Clone,PartialEq)]
+/// Thresholds used to recognize the gestures.
+pub struct GestureConfig {
+    /// Maximum time between two presses to count them as a multiple click.
+    pub multi_click_time: Duration,
+    /// Maximum distance in pixels between two presses to count them as a multiple click.
+    pub multi_click_distance: u32,
+    /// Distance in pixels the cursor has to move while pressed to start a drag.
+    pub drag_distance: u32,
+    /// Time a button has to be held still to report a long press.
+    pub long_press_time: Duration,
+}
+impl Default for GestureConfig {
+    fn default()->Self {
+        Self {
+            multi_click_time: Duration::from_millis(400),
+            multi_click_distance: 4,
+            drag_distance: 4,
+            long_press_time: Duration::from_millis(600),
+        }
+    }
+}
+
+struct Press {
+    button: Button,
+    count: u32,
+    time: EventTime,
+    surface_id: Option<SurfaceId>,
+    position: Position2D<i32>,
+    dragging: bool,
+    long_pressed: bool,
+}
+impl Press {
+    fn waiting_long_press(&self)->bool {!self.dragging && !self.long_pressed}
+    /// Time at which the press becomes a long press, on the monotonic clock of the events.
+    fn long_press_at(&self, long_press_time: Duration)->EventTime {
+        EventTime::from_monotonic(self.time.monotonic + long_press_time.as_nanos() as u64)
+    }
+}
+
+struct LastClick {
+    button: Button,
+    count: u32,
+    time: EventTime,
+    position: Position2D<i32>,
+}
+
+#[derive(Default)]
+struct SeatGestures {
+    surface_id: Option<SurfaceId>,
+    position: Option<Position2D<i32>>,
+    press: Option<Press>,
+    last_click: Option<LastClick>,
+}
+
+fn within(position1: &Position2D<i32>, position2: &Position2D<i32>, distance: u32)->bool {
+    let x = (position1.x - position2.x) as i64;
+    let y = (position1.y - position2.y) as i64;
+    x * x + y * y <= distance as i64 * distance as i64
+}
+
+/**
+Recognize clicks, multiple clicks, drags and long presses from the cursor events, reporting them as `SeatEvent::Gesture`.
+The gestures are reported right after the event completing them, while the original events are forwarded untouched.
+Only the first button pressed is tracked, until it is released.
+*/
+#[derive(Default)]
+pub struct GestureRecognizer {
+    config: GestureConfig,
+    seats: HashMap<SeatId,SeatGestures>,
+}
+impl GestureRecognizer {
+    pub fn new(config: GestureConfig)->Self {
+        Self {config,seats: HashMap::new()}
+    }
+
+    pub fn config(&self)->&GestureConfig {&self.config}
+
+    fn handle(&mut self, event: Event, processed: &mut Vec<Event>) {
+        let (time,id,seat_event) = match &event {
+            Event::Seat{time,id,event}=>(*time,*id,event),
+            Event::Surface{event: SurfaceEvent::Focused(false),..}=>{
+                // Releases happening while unfocused are not reported, so pending presses are dropped.
+                self.seats.values_mut().for_each(|seat|seat.press = None);
+                processed.push(event);
+                return;
+            }
+            _=>{
+                processed.push(event);
+                return;
+            }
+        };
+
+        if let SeatEvent::Removed | SeatEvent::Cursor(CursorEvent::Removed) = seat_event {
+            self.seats.remove(&id);
+            processed.push(event);
+            return;
+        }
+
+        let config = &self.config;
+        let seat = self.seats.entry(id).or_default();
+        if let Some(press) = seat.press.as_mut().filter(|press|press.waiting_long_press()) {
+            if time.duration_since(press.time) >= config.long_press_time {
+                press.long_pressed = true;
+                let time = press.long_press_at(config.long_press_time);
+                let event = GestureEvent::LongPress{button: press.button,surface_id: press.surface_id,position: press.position.clone()};
+                processed.push(Event::Seat{time,id,event: event.into()});
+            }
+        }
+
+        let mut gesture = None;
+        match seat_event {
+            SeatEvent::Cursor(CursorEvent::Entered{surface_id,position})=>{
+                seat.surface_id = Some(*surface_id);
+                seat.position = Some(position.clone());
+            }
+            SeatEvent::Cursor(CursorEvent::Left{surface_id}) if seat.surface_id == Some(*surface_id)=>{
+                seat.surface_id = None;
+            }
+            SeatEvent::Cursor(CursorEvent::AbsoluteMovement{position})=>{
+                seat.position = Some(position.clone());
+                if let Some(press) = &mut seat.press {
+                    if press.dragging {
+                        gesture = Some(GestureEvent::DragMoved{button: press.button,surface_id: press.surface_id,position: position.clone()});
+                    }
+                    else if !within(&press.position,position,config.drag_distance) {
+                        press.dragging = true;
+                        gesture = Some(GestureEvent::DragStarted{button: press.button,surface_id: press.surface_id,position: press.position.clone()});
+                    }
+                }
+            }
+            SeatEvent::Cursor(CursorEvent::Button{key: Some(button),state: State::Down,..}) if seat.press.is_none()=>{
+                let position = seat.position.clone().unwrap_or_else(||Position2D::from((0,0)));
+                let count = match &seat.last_click {
+                    Some(last) if last.button == *button
+                        && time.duration_since(last.time) <= config.multi_click_time
+                        && within(&last.position,&position,config.multi_click_distance)=>last.count + 1,
+                    _=>1
+                };
+                seat.press = Some(Press {
+                    button: *button,
+                    count,
+                    time,
+                    surface_id: seat.surface_id,
+                    position,
+                    dragging: false,
+                    long_pressed: false,
+                });
+            }
+            // Releases of the other buttons are ignored, the first one pressed is still tracked.
+            SeatEvent::Cursor(CursorEvent::Button{key: Some(button),state: State::Up,..})
+                if seat.press.as_ref().map(|press|press.button == *button).unwrap_or(false)=>{
+                let press = seat.press.take().unwrap();
+                seat.last_click = None;
+                if press.dragging {
+                    let position = seat.position.clone().unwrap_or_else(||press.position.clone());
+                    gesture = Some(GestureEvent::DragEnded{button: press.button,surface_id: press.surface_id,position});
+                }
+                else if !press.long_pressed {
+                    seat.last_click = Some(LastClick{button: press.button,count: press.count,time: press.time,position: press.position.clone()});
+                    gesture = Some(GestureEvent::Click{button: press.button,count: press.count,surface_id: press.surface_id,position: press.position});
+                }
+            }
+            _=>{}
+        }
+
+        processed.push(event);
+        if let Some(gesture) = gesture {
+            processed.push(Event::Seat{time,id,event: gesture.into()});
+        }
+    }
+
+    /**
+    Report the long presses elapsed without any later event.
+    The event times are on the monotonic clock, so the current time tells whether the deadline passed.
+    */
+    fn check_long_presses(&mut self, processed: &mut Vec<Event>) {
+        let now = EventTime::now();
+        let long_press_time = self.config.long_press_time;
+        let mut ids: Vec<SeatId> = self.seats.keys().cloned().collect();
+        ids.sort_by_key(|id|Into::<usize>::into(*id));
+        for id in ids {
+            if let Some(press) = self.seats.get_mut(&id).and_then(|seat|seat.press.as_mut()) {
+                let time = press.long_press_at(long_press_time);
+                if press.waiting_long_press() && now.monotonic >= time.monotonic {
+                    press.long_pressed = true;
+                    let event = GestureEvent::LongPress{button: press.button,surface_id: press.surface_id,position: press.position.clone()};
+                    processed.push(Event::Seat{time,id,event: event.into()});
+                }
+            }
+        }
+    }
+}
+
+impl EventProcessor for GestureRecognizer {
+    fn process(&mut self, events: Vec<Event>)->Vec<Event> {
+        let mut processed = Vec::with_capacity(events.len());
+        events.into_iter().for_each(|event|self.handle(event,&mut processed));
+        self.check_long_presses(&mut processed);
+        processed
+    }
+
+    fn deadline(&self)->Option<EventTime> {
+        self.seats.values()
+            .filter_map(|seat|seat.press.as_ref())
+            .filter(|press|press.waiting_long_press())
+            .map(|press|press.long_press_at(self.config.long_press_time))
+            .min()
+    }
+}
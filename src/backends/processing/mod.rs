@@ -0,0 +1,73 @@
+mod resize_coalescer;
+pub use resize_coalescer::ResizeCoalescer;
+
+mod deduplicator;
+pub use deduplicator::Deduplicator;
+
+mod filter;
+pub use filter::Filter;
+
+mod motion_coalescer;
+pub use motion_coalescer::{MotionCoalescer,MotionHistory};
+
+mod gesture_recognizer;
+pub use gesture_recognizer::{GestureRecognizer,GestureConfig};
+
+use crate::definitions::{Event,EventTime};
+
+/**
+Stage of the event pipeline.
+It receives every batch of events before `Platform::events` returns it,
+and can inject, drop or rewrite events.
+*/
+pub trait EventProcessor {
+    /// Process a batch of events, returning the ones to forward to the next processor.
+    fn process(&mut self, events: Vec<Event>) -> Vec<Event>;
+
+    /**
+    Time at which the processor has to run again, even if no event arrives.
+    The platform fd becomes readable at that time, so that the next `Platform::events` call processes an empty batch.
+    */
+    fn deadline(&self) -> Option<EventTime> {None}
+}
+impl<F: FnMut(Vec<Event>)->Vec<Event>> EventProcessor for F {
+    fn process(&mut self, events: Vec<Event>) -> Vec<Event> {self(events)}
+}
+
+/// Ordered sequence of processors applied to the platform events.
+#[derive(Default)]
+pub struct EventPipeline {
+    processors: Vec<Box<dyn EventProcessor>>
+}
+impl EventPipeline {
+    pub fn new()->Self {
+        Self {processors: Vec::new()}
+    }
+
+    /// Append a processor, running it after the existing ones.
+    pub fn push(&mut self, processor: impl EventProcessor + 'static) {
+        self.processors.push(Box::new(processor));
+    }
+
+    /// Insert a processor at the position, shifting the following ones.
+    pub fn insert(&mut self, index: usize, processor: impl EventProcessor + 'static) {
+        self.processors.insert(index,Box::new(processor));
+    }
+
+    pub fn remove(&mut self, index: usize)->Box<dyn EventProcessor> {
+        self.processors.remove(index)
+    }
+
+    pub fn clear(&mut self) {self.processors.clear();}
+    pub fn len(&self)->usize {self.processors.len()}
+    pub fn is_empty(&self)->bool {self.processors.is_empty()}
+
+    pub fn process(&mut self, events: Vec<Event>)->Vec<Event> {
+        self.processors.iter_mut().fold(events,|events,processor|processor.process(events))
+    }
+
+    /// Earliest deadline of the processors.
+    pub fn deadline(&self)->Option<EventTime> {
+        self.processors.iter().filter_map(|processor|processor.deadline()).min()
+    }
+}
@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::definitions::*;
+use super::EventProcessor;
+
+enum PendingMotion {
+    /// Latest absolute movement.
+    Absolute(Event),
+    /// Sum of the relative movements, reported with the time of the latest one.
+    Relative{time: EventTime, offset: Offset2D<f32>},
+}
+
+/**
+Merge the consecutive cursor movements of each seat.
+Absolute movements keep the latest position, while relative movements are summed.
+Any other event ends the sequence, so the merged movement is still reported before it.
+*/
+#[derive(Default)]
+pub struct MotionCoalescer {
+    history: Option<MotionHistory>
+}
+impl MotionCoalescer {
+    pub fn new()->Self {
+        Self {history: None}
+    }
+
+    /**
+    Create a coalescer keeping every original movement in the returned history,
+    for consumers needing the full path like painting applications.
+    The history grows until it is taken.
+    */
+    pub fn with_history()->(Self,MotionHistory) {
+        let history = MotionHistory::default();
+        (Self {history: Some(history.clone())},history)
+    }
+
+    fn flush(pending: &mut Vec<(SeatId,PendingMotion)>, events: &mut Vec<Event>) {
+        events.extend(pending.drain(..).map(|(id,motion)|match motion {
+            PendingMotion::Absolute(event)=>event,
+            PendingMotion::Relative{time,offset}=>{
+                Event::Seat{time,id,event: CursorEvent::RelativeMovement{offset}.into()}
+            }
+        }));
+    }
+}
+
+impl EventProcessor for MotionCoalescer {
+    fn process(&mut self, events: Vec<Event>)->Vec<Event> {
+        let mut processed = Vec::with_capacity(events.len());
+        let mut pending: Vec<(SeatId,PendingMotion)> = Vec::new();
+
+        for event in events {
+            match &event {
+                Event::Seat{id,event: SeatEvent::Cursor(CursorEvent::AbsoluteMovement{..}),..}=>{
+                    if let Some(history) = &self.history {history.push(event.clone());}
+                    match pending.iter_mut().find(|(current,_)|current == id) {
+                        Some((_,motion @ PendingMotion::Absolute(_)))=>*motion = PendingMotion::Absolute(event),
+                        Some(_)=>{
+                            Self::flush(&mut pending,&mut processed);
+                            pending.push((*id,PendingMotion::Absolute(event)));
+                        }
+                        None=>pending.push((*id,PendingMotion::Absolute(event)))
+                    }
+                }
+                Event::Seat{time,id,event: SeatEvent::Cursor(CursorEvent::RelativeMovement{offset})}=>{
+                    if let Some(history) = &self.history {history.push(event.clone());}
+                    match pending.iter_mut().find(|(current,_)|current == id) {
+                        Some((_,PendingMotion::Relative{time: pending_time,offset: pending_offset}))=>{
+                            *pending_time = *time;
+                            pending_offset.x += offset.x;
+                            pending_offset.y += offset.y;
+                        }
+                        Some(_)=>{
+                            Self::flush(&mut pending,&mut processed);
+                            pending.push((*id,PendingMotion::Relative{time: *time,offset: offset.clone()}));
+                        }
+                        None=>pending.push((*id,PendingMotion::Relative{time: *time,offset: offset.clone()}))
+                    }
+                }
+                _=>{
+                    Self::flush(&mut pending,&mut processed);
+                    processed.push(event);
+                }
+            }
+        }
+        Self::flush(&mut pending,&mut processed);
+        processed
+    }
+}
+
+#[derive(Clone,Default)]
+/// Original movements merged by a `MotionCoalescer`, in the order they happened.
+pub struct MotionHistory {
+    events: Rc<RefCell<Vec<Event>>>
+}
+impl MotionHistory {
+    fn push(&self, event: Event) {
+        self.events.borrow_mut().push(event);
+    }
+
+    /// Take the movements recorded so far, emptying the history.
+    pub fn take(&self)->Vec<Event> {
+        self.events.borrow_mut().drain(..).collect()
+    }
+
+    pub fn len(&self)->usize {self.events.borrow().len()}
+    pub fn is_empty(&self)->bool {self.events.borrow().is_empty()}
+}
@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+use crate::definitions::*;
+use super::EventProcessor;
+
+/**
+Keep only the last resize of each surface in a batch.
+The remaining resize keeps the position of the last one, so it is still reported after the events preceding it.
+*/
+#[derive(Default)]
+pub struct ResizeCoalescer;
+impl ResizeCoalescer {
+    pub fn new()->Self {Self}
+}
+
+impl EventProcessor for ResizeCoalescer {
+    fn process(&mut self, events: Vec<Event>)->Vec<Event> {
+        let mut last_resize = HashMap::new();
+        events.iter().enumerate().for_each(|(index,event)|{
+            if let Event::Surface{id,event: SurfaceEvent::Resized(_),..} = event {
+                last_resize.insert(*id,index);
+            }
+        });
+
+        events.into_iter().enumerate().filter(|(index,event)|{
+            match event {
+                Event::Surface{id,event: SurfaceEvent::Resized(_),..}=>last_resize.get(id) == Some(index),
+                _=>true
+            }
+        }).map(|(_,event)|event).collect()
+    }
+}
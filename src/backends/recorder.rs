@@ -0,0 +1,93 @@
+use std::io::{BufRead,Write};
+use log::error;
+use serde::{Serialize,Deserialize};
+
+use crate::definitions::*;
+
+#[derive(Serialize,Deserialize)]
+/**
+Entry of a recording.
+Recordings are stored as JSON lines, starting with the platform type
+and followed by the events and the requests in the order they happened.
+*/
+pub enum RecordEntry {
+    PlatformType(PlatformType),
+    Event(Event),
+    Requests{
+        time: EventTime,
+        requests: Vec<Request>
+    }
+}
+impl RecordEntry {
+    /// Parse a recording, failing on the first malformed line.
+    pub fn read_all(reader: impl BufRead)->Result<Vec<Self>,PlatformError> {
+        let mut entries = Vec::new();
+        for (index,line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {continue;}
+            let entry = serde_json::from_str(&line).map_err(|err|PlatformError::Io(format!("Malformed recording at line {}: {}",index + 1,err)))?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+/**
+Wrapper around a backend recording its events and the requests sent to it.
+The recording can be played back with a `ReplayPlatform`.
+*/
+pub struct Recorder<B: PlatformBackend> {
+    backend: B,
+    writer: Box<dyn Write + Send>,
+}
+impl<B: PlatformBackend> Recorder<B> {
+    pub fn new(backend: B, writer: impl Write + Send + 'static)->Self {
+        let mut recorder = Self {backend,writer: Box::new(writer)};
+        let platform_type = recorder.backend.platform_type();
+        recorder.write(&[RecordEntry::PlatformType(platform_type)]);
+        recorder
+    }
+
+    /// Record to a newly created file.
+    pub fn create(backend: B, path: impl AsRef<std::path::Path>)->Result<Self,PlatformError> {
+        let file = std::fs::File::create(path)?;
+        Ok(Self::new(backend,std::io::BufWriter::new(file)))
+    }
+
+    pub fn backend(&self)->&B {&self.backend}
+    pub fn backend_mut(&mut self)->&mut B {&mut self.backend}
+    pub fn into_inner(self)->B {self.backend}
+
+    fn write(&mut self, entries: &[RecordEntry]) {
+        let result = entries.iter().try_for_each(|entry|{
+            serde_json::to_writer(&mut self.writer,entry).map_err(std::io::Error::from)?;
+            self.writer.write_all(b"\n")
+        }).and_then(|_|self.writer.flush());
+        if let Err(err) = result {
+            error!("Failed to write the recording: {}",err);
+        }
+    }
+}
+
+impl<B: PlatformBackend> PlatformBackend for Recorder<B> {
+    fn platform_type(&self)->PlatformType {self.backend.platform_type()}
+    fn events(&mut self)->Vec<Event> {
+        let events = self.backend.events();
+        if !events.is_empty() {
+            let entries: Vec<RecordEntry> = events.iter().cloned().map(RecordEntry::Event).collect();
+            self.write(&entries);
+        }
+        events
+    }
+    fn requests(&mut self, requests: Vec<Request>) {
+        let entry = RecordEntry::Requests{time: EventTime::now(),requests};
+        self.write(std::slice::from_ref(&entry));
+        if let RecordEntry::Requests{requests,..} = entry {
+            self.backend.requests(requests);
+        }
+    }
+}
+
+impl<B: PlatformBackend + std::os::unix::io::AsRawFd> std::os::unix::io::AsRawFd for Recorder<B> {
+    fn as_raw_fd(&self)->std::os::unix::io::RawFd {self.backend.as_raw_fd()}
+}
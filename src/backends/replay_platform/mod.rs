@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+use std::time::{Duration,Instant};
+use log::{debug,error};
+
+use crate::definitions::*;
+use super::poller::TimerFd;
+use super::RecordEntry;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+/// Pace of the replay.
+pub enum ReplayMode {
+    /// Emit the events respecting the delays between them in the recording.
+    Realtime,
+    /// Emit every event as soon as possible.
+    AsFastAsPossible
+}
+
+/**
+Platform replaying a recording made with a `Recorder`.
+Events keep their original timestamps, while the requests are ignored,
+since the outcome of the recorded ones is already part of the recording.
+*/
+pub struct ReplayPlatform {
+    platform_type: PlatformType,
+    mode: ReplayMode,
+    events: VecDeque<Event>,
+    recorded_requests: usize,
+    first_time: Option<EventTime>,
+    start: Instant,
+    timer: TimerFd,
+}
+impl ReplayPlatform {
+    /// Replay a recording file.
+    pub fn open(path: impl AsRef<std::path::Path>, mode: ReplayMode)->Result<Self,PlatformError> {
+        let file = std::fs::File::open(path)?;
+        Self::from_reader(std::io::BufReader::new(file),mode)
+    }
+
+    pub fn from_reader(reader: impl std::io::BufRead, mode: ReplayMode)->Result<Self,PlatformError> {
+        Self::from_entries(RecordEntry::read_all(reader)?,mode)
+    }
+
+    pub fn from_entries(entries: Vec<RecordEntry>, mode: ReplayMode)->Result<Self,PlatformError> {
+        let mut platform_type = PlatformType::Compositor;
+        let mut events = VecDeque::new();
+        let mut recorded_requests = 0;
+        for entry in entries {
+            match entry {
+                RecordEntry::PlatformType(recorded_type)=>platform_type = recorded_type,
+                RecordEntry::Event(event)=>events.push_back(event),
+                RecordEntry::Requests{requests,..}=>recorded_requests += requests.len()
+            }
+        }
+        let first_time = events.front().map(Event::time);
+
+        let timer = TimerFd::new()?;
+        timer.set(Some(Duration::from_nanos(0)))?;
+
+        Ok(Self {
+            platform_type,
+            mode,
+            events,
+            recorded_requests,
+            first_time,
+            start: Instant::now(),
+            timer,
+        })
+    }
+
+    /// Number of events still to be emitted.
+    pub fn remaining_events(&self)->usize {self.events.len()}
+
+    /// Number of requests found in the recording.
+    pub fn recorded_requests(&self)->usize {self.recorded_requests}
+
+    /// Delay from the start of the replay at which the event is due.
+    fn due_delay(&self, event: &Event)->Duration {
+        match (self.mode,self.first_time) {
+            (ReplayMode::Realtime,Some(first_time))=>event.time().duration_since(first_time),
+            _=>Duration::from_nanos(0)
+        }
+    }
+}
+
+impl std::os::unix::io::AsRawFd for ReplayPlatform {
+    fn as_raw_fd(&self)->std::os::unix::io::RawFd {
+        self.timer.as_raw_fd()
+    }
+}
+
+impl PlatformBackend for ReplayPlatform {
+    fn platform_type(&self)->PlatformType {self.platform_type}
+    fn events(&mut self)->Vec<Event> {
+        self.timer.clear();
+
+        let elapsed = self.start.elapsed();
+        let mut events = Vec::new();
+        while let Some(event) = self.events.front() {
+            if self.due_delay(event) > elapsed {break;}
+            events.extend(self.events.pop_front());
+        }
+
+        let next = self.events.front().map(|event|self.due_delay(event).checked_sub(self.start.elapsed()).unwrap_or_default());
+        if let Err(err) = self.timer.set(next) {
+            error!("Failed to set the replay timer: {}",err);
+        }
+        events
+    }
+    fn requests(&mut self, requests: Vec<Request>) {
+        debug!("Ignoring {} requests during the replay",requests.len());
+    }
+}
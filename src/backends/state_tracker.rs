@@ -1,14 +1,132 @@
-use std::collections::HashMap;
-use crate::definitions::{Event,
-    SeatEvent,SeatId,SeatInfo,
-    OutputEvent,OutputId,OutputInfo,
-    SurfaceEvent,SurfaceId,SurfaceInfo
-};
+use std::collections::{HashMap,HashSet};
+use crate::definitions::*;
 
+#[derive(Debug,Clone,PartialEq,Default)]
+/// Tracked state of a keyboard.
+pub struct KeyboardState {
+    pub pressed_keys: HashSet<Key>,
+    /**
+    Held modifiers combined with the latched ones and the active locks.
+    When the backend reports the modifier state, it replaces the one guessed from the pressed keys.
+    */
+    pub modifiers: Modifiers,
+    /// Active layout group.
+    pub group: u32,
+    /// Compiled keymap, as last reported by the backend.
+    pub keymap: Option<Keymap>,
+    locks: Modifiers,
+    /// Depressed, latched and locked modifiers, as last reported by the backend.
+    reported: Option<(Modifiers,Modifiers,Modifiers)>,
+}
+impl KeyboardState {
+    pub fn is_pressed(&self, key: Key)->bool {self.pressed_keys.contains(&key)}
+
+    fn update_key(&mut self, key: Key, state: State) {
+        match state {
+            State::Down=>{
+                // Repeated presses of an already held key must not toggle the locks again.
+                if self.pressed_keys.insert(key) {
+                    if let Some(lock) = lock_modifier(key) {self.locks.toggle(lock);}
+                }
+            }
+            State::Up=>{self.pressed_keys.remove(&key);}
+        }
+        self.update_modifiers();
+    }
+
+    /// Release every key, keeping the locks.
+    fn release_all(&mut self) {
+        self.pressed_keys.clear();
+        self.update_modifiers();
+    }
+
+    fn update_reported(&mut self, depressed: Modifiers, latched: Modifiers, locked: Modifiers, group: u32) {
+        self.reported = Some((depressed,latched,locked));
+        self.group = group;
+        self.update_modifiers();
+    }
+
+    fn update_modifiers(&mut self) {
+        if let Some((depressed,latched,locked)) = self.reported {
+            self.modifiers = depressed | latched | locked;
+            return;
+        }
+        self.modifiers = self.pressed_keys.iter().filter_map(|key|held_modifier(*key)).fold(self.locks,|modifiers,modifier|modifiers | modifier);
+    }
+}
+
+fn held_modifier(key: Key)->Option<Modifiers> {
+    match key {
+        Key::ShiftLeft | Key::ShiftRight=>Some(Modifiers::SHIFT),
+        Key::ControlLeft | Key::ControlRight=>Some(Modifiers::CONTROL),
+        Key::AltLeft | Key::AltRight=>Some(Modifiers::ALT),
+        Key::MetaLeft | Key::MetaRight | Key::Super=>Some(Modifiers::SUPER),
+        _=>None
+    }
+}
+
+fn lock_modifier(key: Key)->Option<Modifiers> {
+    match key {
+        Key::CapsLock=>Some(Modifiers::CAPS_LOCK),
+        Key::NumLock=>Some(Modifiers::NUM_LOCK),
+        Key::ScrollLock=>Some(Modifiers::SCROLL_LOCK),
+        _=>None
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+/// Tracked state of a seat.
+pub struct SeatState {
+    pub name: String,
+    pub keyboard: Option<KeyboardInfo>,
+    pub cursor: Option<CursorInfo>,
+    /// Surface under the cursor.
+    pub cursor_surface: Option<SurfaceId>,
+    /// Cursor position, relative to the surface under it.
+    pub cursor_position: Option<Position2D<i32>>,
+    /**
+    Surface with the keyboard focus.
+    Backends report the focus per surface, so it is shared by every seat.
+    */
+    pub focused_surface: Option<SurfaceId>,
+    pub keyboard_state: KeyboardState,
+    pub pressed_buttons: HashSet<Button>,
+}
+impl SeatState {
+    fn new(name: String)->Self {
+        Self {
+            name,
+            keyboard: None,
+            cursor: None,
+            cursor_surface: None,
+            cursor_position: None,
+            focused_surface: None,
+            keyboard_state: KeyboardState::default(),
+            pressed_buttons: HashSet::new(),
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+/// Tracked state of a surface.
+pub struct SurfaceState {
+    pub position: Position2D<u32>,
+    pub size: Size2D<u32>,
+    pub mode: SurfaceMode,
+    pub focused: bool,
+    /// Outputs the surface is shown on.
+    pub outputs: Vec<OutputId>,
+    pub surface: Surface,
+}
+
+/**
+Snapshot of the platform state, built from the events returned by the platform.
+It is available through `Platform::state`.
+*/
 pub struct StateTracker {
-    seats: HashMap<SeatId,SeatInfo>,
+    seats: HashMap<SeatId,SeatState>,
     outputs: HashMap<OutputId,OutputInfo>,
-    surfaces: HashMap<SurfaceId,SurfaceInfo>
+    surfaces: HashMap<SurfaceId,SurfaceState>
 }
 
 impl StateTracker {
@@ -18,54 +136,242 @@ impl StateTracker {
         let surfaces = HashMap::new();
         Self{seats,outputs,surfaces}
     }
-    pub fn update(&mut self,events: &Vec<Event>){
+
+    pub fn seats(&self)->impl Iterator<Item=(SeatId,&SeatState)> {
+        self.seats.iter().map(|(id,seat)|(*id,seat))
+    }
+    pub fn seat(&self, id: SeatId)->Option<&SeatState> {self.seats.get(&id)}
+
+    /// Pressed keys and modifiers of the keyboard attached to the seat.
+    pub fn keyboard_state(&self, id: SeatId)->Option<&KeyboardState> {
+        self.seats.get(&id).filter(|seat|seat.keyboard.is_some()).map(|seat|&seat.keyboard_state)
+    }
+
+    /// Compiled keymap of the keyboard attached to the seat, to be shared through a `KeymapFile`.
+    pub fn keymap(&self, id: SeatId)->Option<&Keymap> {
+        self.keyboard_state(id).and_then(|keyboard_state|keyboard_state.keymap.as_ref())
+    }
+
+    pub fn outputs(&self)->impl Iterator<Item=(OutputId,&OutputInfo)> {
+        self.outputs.iter().map(|(id,output)|(*id,output))
+    }
+    pub fn output(&self, id: OutputId)->Option<&OutputInfo> {self.outputs.get(&id)}
+
+    pub fn surfaces(&self)->impl Iterator<Item=(SurfaceId,&SurfaceState)> {
+        self.surfaces.iter().map(|(id,surface)|(*id,surface))
+    }
+    pub fn surface(&self, id: SurfaceId)->Option<&SurfaceState> {self.surfaces.get(&id)}
+
+    /**
+    Generate the events needed to rebuild the current state from scratch, as they are reported at startup.
+    Seats come first along with their devices, then outputs and surfaces, followed by the cursor positions.
+    Pressed keys and buttons are not replayed, to avoid triggering actions.
+    */
+    pub fn synthetic_events(&self)->Vec<Event> {
+        let time = EventTime::now();
+        let mut events = Vec::new();
+
+        let mut seats: Vec<_> = self.seats().collect();
+        seats.sort_by_key(|(id,_)|Into::<usize>::into(*id));
+        let mut outputs: Vec<_> = self.outputs().collect();
+        outputs.sort_by_key(|(id,_)|*id);
+        let mut surfaces: Vec<_> = self.surfaces().collect();
+        surfaces.sort_by_key(|(id,_)|Into::<usize>::into(*id));
+
+        for (id,seat) in &seats {
+            let id = *id;
+            events.push(Event::Seat{time,id,event: SeatEvent::Added{name: seat.name.clone()}});
+            if let Some(keyboard) = &seat.keyboard {
+                events.push(Event::Seat{time,id,event: KeyboardEvent::Added(keyboard.clone()).into()});
+                if let Some(keymap) = &seat.keyboard_state.keymap {
+                    events.push(Event::Seat{time,id,event: KeyboardEvent::KeymapChanged{keymap: keymap.clone()}.into()});
+                }
+                if let Some((depressed,latched,locked)) = seat.keyboard_state.reported {
+                    let group = seat.keyboard_state.group;
+                    events.push(Event::Seat{time,id,event: KeyboardEvent::Modifiers{depressed,latched,locked,group}.into()});
+                }
+            }
+            if let Some(cursor) = &seat.cursor {
+                events.push(Event::Seat{time,id,event: CursorEvent::Added(cursor.clone()).into()});
+            }
+        }
+
+        for (id,output) in outputs {
+            events.push(Event::Output{time,id,event: OutputEvent::Added(output.clone())});
+        }
+
+        for (id,surface) in surfaces {
+            let info = SurfaceInfo {
+                position: surface.position.clone(),
+                size: surface.size.clone(),
+                surface: surface.surface.clone()
+            };
+            events.push(Event::Surface{time,id,event: SurfaceEvent::Added(info)});
+            for output in &surface.outputs {
+                events.push(Event::Surface{time,id,event: SurfaceEvent::Entered(*output)});
+            }
+            if !surface.mode.is_empty() {
+                events.push(Event::Surface{time,id,event: SurfaceEvent::ModeChanged(surface.mode)});
+            }
+            if surface.focused {
+                events.push(Event::Surface{time,id,event: SurfaceEvent::Focused(true)});
+            }
+        }
+
+        for (id,seat) in seats {
+            if let (Some(surface_id),Some(position)) = (seat.cursor_surface,seat.cursor_position.clone()) {
+                events.push(Event::Seat{time,id,event: CursorEvent::Entered{surface_id,position}.into()});
+            }
+        }
+
+        events
+    }
+
+    pub fn update(&mut self, events: &[Event]){
         events.iter().for_each(|event|{
             match event {
-                Event::Seat(ref seat_event)=>{
-                    match seat_event.event {
-                        SeatEvent::Added(ref info)=>{
-                            self.seats.insert(seat_event.id,info.clone());
-                        }
-                        SeatEvent::Removed=>{
-                            self.seats.remove(&seat_event.id);
-                        }
-                        _=>{}
-                    }
+                Event::Seat{id,event,..}=>self.update_seat(*id,event),
+                Event::Output{id,event,..}=>self.update_output(*id,event),
+                Event::Surface{id,event,..}=>self.update_surface(*id,event),
+                Event::Request{..}=>{}
+            }
+        })
+    }
+
+    fn update_seat(&mut self, id: SeatId, event: &SeatEvent) {
+        if let SeatEvent::Added{name} = event {
+            self.seats.insert(id,SeatState::new(name.clone()));
+            return;
+        }
+        if let SeatEvent::Removed = event {
+            self.seats.remove(&id);
+            return;
+        }
+
+        let seat = match self.seats.get_mut(&id) {
+            Some(seat)=>seat,
+            None=>return
+        };
+        match event {
+            SeatEvent::Keyboard(KeyboardEvent::Added(info))=>{
+                seat.keyboard = Some(info.clone());
+                seat.keyboard_state = KeyboardState::default();
+            }
+            SeatEvent::Keyboard(KeyboardEvent::Removed)=>{
+                seat.keyboard = None;
+                seat.keyboard_state = KeyboardState::default();
+            }
+            SeatEvent::Keyboard(KeyboardEvent::Key{key: Some(key),state,..})=>seat.keyboard_state.update_key(*key,*state),
+            SeatEvent::Keyboard(KeyboardEvent::Modifiers{depressed,latched,locked,group})=>{
+                seat.keyboard_state.update_reported(*depressed,*latched,*locked,*group);
+            }
+            SeatEvent::Keyboard(KeyboardEvent::LayoutModified{keymap})=>{
+                if let Some(keyboard) = &mut seat.keyboard {keyboard.keymap = keymap.clone();}
+            }
+            SeatEvent::Keyboard(KeyboardEvent::KeymapChanged{keymap})=>seat.keyboard_state.keymap = Some(keymap.clone()),
+            SeatEvent::Cursor(CursorEvent::Added(info))=>seat.cursor = Some(info.clone()),
+            SeatEvent::Cursor(CursorEvent::Removed)=>{
+                seat.cursor = None;
+                seat.pressed_buttons.clear();
+                seat.cursor_surface = None;
+                seat.cursor_position = None;
+            }
+            SeatEvent::Cursor(CursorEvent::ModeChanged(mode))=>{
+                if let Some(cursor) = &mut seat.cursor {cursor.mode = *mode;}
+            }
+            SeatEvent::Cursor(CursorEvent::VisibilityChanged(visible))=>{
+                if let Some(cursor) = &mut seat.cursor {cursor.visible = *visible;}
+            }
+            SeatEvent::Cursor(CursorEvent::Entered{surface_id,position})=>{
+                seat.cursor_surface = Some(*surface_id);
+                seat.cursor_position = Some(position.clone());
+            }
+            SeatEvent::Cursor(CursorEvent::Left{surface_id}) if seat.cursor_surface == Some(*surface_id)=>{
+                seat.cursor_surface = None;
+                seat.cursor_position = None;
+            }
+            SeatEvent::Cursor(CursorEvent::AbsoluteMovement{position})=>seat.cursor_position = Some(position.clone()),
+            SeatEvent::Cursor(CursorEvent::Button{key: Some(button),state,..})=>{
+                match state {
+                    State::Down=>{seat.pressed_buttons.insert(*button);}
+                    State::Up=>{seat.pressed_buttons.remove(button);}
                 }
-                Event::Output(ref output_event)=>{
-                    match output_event.event {
-                        OutputEvent::Added(ref info)=>{
-                            self.outputs.insert(output_event.id,info.clone());
-                        }
-                        OutputEvent::Removed=>{
-                            self.outputs.remove(&output_event.id);
-                        }
-                        _=>{}
+            }
+            _=>{}
+        }
+    }
+
+    fn update_output(&mut self, id: OutputId, event: &OutputEvent) {
+        match event {
+            OutputEvent::Added(info)=>{self.outputs.insert(id,info.clone());}
+            OutputEvent::Removed=>{
+                self.outputs.remove(&id);
+                self.surfaces.values_mut().for_each(|surface|surface.outputs.retain(|output|*output != id));
+            }
+            OutputEvent::ModeAdded(mode)=>{
+                if let Some(output) = self.outputs.get_mut(&id) {output.available_modes.push(mode.clone());}
+            }
+            OutputEvent::ModeChanged(mode)=>{
+                if let Some(output) = self.outputs.get_mut(&id) {output.selected_mode = mode.clone();}
+            }
+            OutputEvent::Moved(position)=>{
+                if let Some(output) = self.outputs.get_mut(&id) {output.position = position.clone();}
+            }
+        }
+    }
+
+    fn update_surface(&mut self, id: SurfaceId, event: &SurfaceEvent) {
+        match event {
+            SurfaceEvent::Added(info)=>{
+                self.surfaces.insert(id,SurfaceState {
+                    position: info.position.clone(),
+                    size: info.size.clone(),
+                    mode: SurfaceMode::empty(),
+                    focused: false,
+                    outputs: Vec::new(),
+                    surface: info.surface.clone(),
+                });
+            }
+            SurfaceEvent::Removed=>{
+                self.surfaces.remove(&id);
+                self.seats.values_mut().for_each(|seat|{
+                    if seat.cursor_surface == Some(id) {
+                        seat.cursor_surface = None;
+                        seat.cursor_position = None;
                     }
+                    if seat.focused_surface == Some(id) {seat.focused_surface = None;}
+                });
+            }
+            SurfaceEvent::Entered(output)=>{
+                if let Some(surface) = self.surfaces.get_mut(&id) {
+                    if !surface.outputs.contains(output) {surface.outputs.push(*output);}
                 }
-                Event::Surface(ref surface_event)=>{
-                    match surface_event.event {
-                        SurfaceEvent::Added(ref info)=>{
-                            self.surfaces.insert(surface_event.id,info.clone());
-                        }
-                        SurfaceEvent::Moved(ref position)=>{
-                            if let Some(surface) = self.surfaces.get_mut(&surface_event.id){
-                                surface.position = *position;
-                            }
-                        }
-                        SurfaceEvent::Resized(ref size)=>{
-                            if let Some(surface) = self.surfaces.get_mut(&surface_event.id){
-                                surface.size = *size;
-                            }
-                        }
-                        SurfaceEvent::Removed=>{
-                            self.surfaces.remove(&surface_event.id);
-                        }
-                        _=>{}
+            }
+            SurfaceEvent::Left(output)=>{
+                if let Some(surface) = self.surfaces.get_mut(&id) {surface.outputs.retain(|current|current != output);}
+            }
+            SurfaceEvent::Resized(size)=>{
+                if let Some(surface) = self.surfaces.get_mut(&id) {surface.size = size.clone();}
+            }
+            SurfaceEvent::ModeChanged(mode)=>{
+                if let Some(surface) = self.surfaces.get_mut(&id) {surface.mode = *mode;}
+            }
+            SurfaceEvent::Focused(focused)=>{
+                if let Some(surface) = self.surfaces.get_mut(&id) {surface.focused = *focused;}
+                // Releases happening while unfocused are not reported, so the pressed state is dropped.
+                self.seats.values_mut().for_each(|seat|{
+                    if *focused {seat.focused_surface = Some(id);}
+                    else {
+                        if seat.focused_surface == Some(id) {seat.focused_surface = None;}
+                        seat.keyboard_state.release_all();
+                        seat.pressed_buttons.clear();
                     }
-                }
+                });
             }
-        })
+        }
     }
 }
 
+impl Default for StateTracker {
+    fn default()->Self {Self::new()}
+}
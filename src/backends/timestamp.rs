@@ -0,0 +1,40 @@
+use crate::definitions::EventTime;
+use crate::definitions::monotonic_now;
+
+/**
+Map the millisecond timestamps of a backend onto the monotonic clock.
+The offset between the two clocks is anchored on the first timestamp
+and moved back whenever a mapped timestamp would end up in the future,
+so it converges to the smallest delivery latency observed. Wrap arounds of the 32 bit counter are tracked.
+*/
+pub(crate) struct TimestampMapper {
+    offset: Option<i128>,
+    last_raw: u32,
+    wraps: u64,
+}
+impl TimestampMapper {
+    pub fn new()->Self {
+        Self {offset: None, last_raw: 0, wraps: 0}
+    }
+
+    pub fn map(&mut self, raw: u32)->EventTime {
+        if self.offset.is_some() && raw < self.last_raw && self.last_raw - raw > u32::MAX / 2 {
+            self.wraps += 1;
+        }
+        self.last_raw = raw;
+
+        let now = monotonic_now() as i128;
+        let raw_nanos = (((self.wraps << 32) + raw as u64) as i128) * 1_000_000;
+        let offset = match self.offset {
+            Some(offset) if raw_nanos + offset <= now=>offset,
+            _=>now - raw_nanos
+        };
+        self.offset = Some(offset);
+
+        EventTime {monotonic: (raw_nanos + offset) as u64, raw: Some(raw)}
+    }
+}
+
+impl Default for TimestampMapper {
+    fn default()->Self {Self::new()}
+}
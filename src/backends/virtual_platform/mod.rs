@@ -0,0 +1,346 @@
+use crate::definitions::*;
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+
+struct VirtualSeat {
+    name: String,
+    keyboard: Option<KeyboardInfo>,
+    cursor: Option<CursorInfo>,
+}
+
+struct VirtualSurface {
+    position: Position2D<u32>,
+    size: Size2D<u32>,
+    ime_enabled: bool,
+    ime_cursor_area: Option<Rectangle<i32,u32>>,
+}
+
+/**
+Headless platform living completely in memory.
+Outputs and seats are configured by the user, surfaces are created through the usual requests
+and seat events can be injected to simulate user input.
+*/
+pub struct VirtualPlatform {
+    outputs: HashMap<OutputId, OutputInfo>,
+    seats: HashMap<SeatId, VirtualSeat>,
+    surfaces: HashMap<SurfaceId, VirtualSurface>,
+    id_counter: usize,
+    pending_events: Vec<Event>,
+    wake_reader: UnixStream,
+    wake_writer: UnixStream,
+    /// Whether queued events make the fd readable, as most backends do.
+    self_waking: bool,
+}
+impl VirtualPlatform {
+    /// Create a virtual platform without any output or seat.
+    pub fn new() -> Result<Self, PlatformError> {
+        let (wake_reader, wake_writer) = UnixStream::pair()?;
+        wake_reader.set_nonblocking(true)?;
+        wake_writer.set_nonblocking(true)?;
+
+        Ok(Self {
+            outputs: HashMap::new(),
+            seats: HashMap::new(),
+            surfaces: HashMap::new(),
+            id_counter: 0,
+            pending_events: Vec::new(),
+            wake_reader,
+            wake_writer,
+            self_waking: true,
+        })
+    }
+
+    #[cfg(test)]
+    /// Stop waking the fd when events are queued, to behave like the backends relying on the `Platform` to be woken up.
+    pub(crate) fn disable_self_waking(&mut self) {
+        self.self_waking = false;
+    }
+
+    /// Create a virtual platform with a 1920x1080 output and a seat with a keyboard and a cursor.
+    pub fn with_default_devices() -> Result<Self, PlatformError> {
+        let mut platform = Self::new()?;
+
+        let mode = Mode {
+            resolution: Size2D::from((1920, 1080)),
+            refresh_rate: 60000,
+            is_preferred: true,
+        };
+        platform.add_output(OutputInfo {
+            position: Position2D::from((0, 0)),
+            selected_mode: mode.clone(),
+            available_modes: vec![mode],
+            physical_size: Size2D::from((527, 296)),
+            subpixel: Subpixel::Unknown,
+        });
+
+        let seat_id = platform.add_seat(String::from("seat-0"));
+        platform.add_keyboard(seat_id, KeyboardInfo {
+            keymap: KeymapDescriptor::default(),
+            autorepeat: true,
+            encoding: KeyEncoding::XkbV1,
+        });
+        platform.add_cursor(seat_id, CursorInfo {
+            mode: CursorMode::Absolute,
+            theme: CursorImage::Default,
+            visible: true,
+        });
+
+        Ok(platform)
+    }
+
+    /// Add an output, returning its identifier.
+    pub fn add_output(&mut self, info: OutputInfo) -> OutputId {
+        let id = OutputId::from(self.next_id());
+        self.outputs.insert(id, info.clone());
+        self.push_event(Event::Output{time: self.time(), id, event: OutputEvent::Added(info)});
+        id
+    }
+
+    /// Remove an output.
+    pub fn remove_output(&mut self, id: OutputId) {
+        if self.outputs.remove(&id).is_some() {
+            self.push_event(Event::Output{time: self.time(), id, event: OutputEvent::Removed});
+        }
+    }
+
+    /// Add a seat without any device, returning its identifier.
+    pub fn add_seat(&mut self, name: String) -> SeatId {
+        let id = SeatId::from(self.next_id());
+        self.seats.insert(id, VirtualSeat {name: name.clone(), keyboard: None, cursor: None});
+        self.push_event(Event::Seat{time: self.time(), id, event: SeatEvent::Added{name}});
+        id
+    }
+
+    /// Remove a seat, removing its devices first.
+    pub fn remove_seat(&mut self, id: SeatId) {
+        self.remove_keyboard(id);
+        self.remove_cursor(id);
+        if self.seats.remove(&id).is_some() {
+            self.push_event(Event::Seat{time: self.time(), id, event: SeatEvent::Removed});
+        }
+    }
+
+    /// Attach a keyboard to a seat.
+    pub fn add_keyboard(&mut self, id: SeatId, info: KeyboardInfo) {
+        if let Some(seat) = self.seats.get_mut(&id) {
+            if seat.keyboard.is_none() {
+                seat.keyboard = Some(info.clone());
+                let event = SeatEvent::Keyboard(KeyboardEvent::Added(info));
+                self.push_event(Event::Seat{time: self.time(), id, event});
+            }
+        }
+    }
+
+    /// Detach the keyboard from a seat.
+    pub fn remove_keyboard(&mut self, id: SeatId) {
+        if let Some(seat) = self.seats.get_mut(&id) {
+            if seat.keyboard.take().is_some() {
+                let event = SeatEvent::Keyboard(KeyboardEvent::Removed);
+                self.push_event(Event::Seat{time: self.time(), id, event});
+            }
+        }
+    }
+
+    /// Attach a cursor to a seat.
+    pub fn add_cursor(&mut self, id: SeatId, info: CursorInfo) {
+        if let Some(seat) = self.seats.get_mut(&id) {
+            if seat.cursor.is_none() {
+                seat.cursor = Some(info.clone());
+                let event = SeatEvent::Cursor(CursorEvent::Added(info));
+                self.push_event(Event::Seat{time: self.time(), id, event});
+            }
+        }
+    }
+
+    /// Detach the cursor from a seat.
+    pub fn remove_cursor(&mut self, id: SeatId) {
+        if let Some(seat) = self.seats.get_mut(&id) {
+            if seat.cursor.take().is_some() {
+                let event = SeatEvent::Cursor(CursorEvent::Removed);
+                self.push_event(Event::Seat{time: self.time(), id, event});
+            }
+        }
+    }
+
+    /**
+    Inject a synthetic seat event, as if it was generated by a real device.
+    Events for unknown seats are discarded.
+    */
+    pub fn inject_seat_event(&mut self, id: SeatId, event: SeatEvent) {
+        if self.seats.contains_key(&id) {
+            self.push_event(Event::Seat{time: self.time(), id, event});
+        }
+    }
+
+    /**
+    Inject a synthetic surface event, as if it was generated by the compositor.
+    Events for unknown surfaces are discarded.
+    */
+    pub fn inject_surface_event(&mut self, id: SurfaceId, event: SurfaceEvent) {
+        if let Some(surface) = self.surfaces.get_mut(&id) {
+            if let SurfaceEvent::Resized(ref size) = event {
+                surface.size = size.clone();
+            }
+            self.push_event(Event::Surface{time: self.time(), id, event});
+        }
+    }
+
+    /// Whether the input method has been enabled on the surface, and the last cursor area set on it.
+    pub fn ime_state(&self, id: SurfaceId) -> Option<(bool,Option<Rectangle<i32,u32>>)> {
+        self.surfaces.get(&id).map(|surface|(surface.ime_enabled,surface.ime_cursor_area.clone()))
+    }
+
+    fn create_surface(&mut self, output: Option<OutputId>) -> Result<(), RequestError> {
+        let position = match output {
+            Some(output)=>match self.outputs.get(&output) {
+                Some(output)=>output.position.clone(),
+                None=>return Err(SurfaceError::InvalidOutput.into())
+            },
+            None=>Position2D::from((0, 0))
+        };
+        let size = Size2D::from((400, 400));
+
+        let id = SurfaceId::from(self.next_id());
+        self.surfaces.insert(id, VirtualSurface {position: position.clone(), size: size.clone(), ime_enabled: false, ime_cursor_area: None});
+
+        let surface = Surface::Virtual(id);
+        let event = SurfaceEvent::Added(SurfaceInfo{position,size,surface});
+        self.push_event(Event::Surface{time: self.time(), id, event});
+        Ok(())
+    }
+
+    fn next_id(&mut self) -> usize {
+        let id = self.id_counter;
+        self.id_counter += 1;
+        id
+    }
+
+    fn time(&self) -> EventTime {
+        EventTime::now()
+    }
+
+    fn push_event(&mut self, event: Event) {
+        self.pending_events.push(event);
+        if !self.self_waking {return;}
+        // A full socket buffer already means the fd is readable, so the error can be ignored.
+        let _ = self.wake_writer.write(&[0]);
+    }
+
+    fn broadcast_seat_event(&mut self, event: SeatEvent) {
+        let mut ids: Vec<SeatId> = self.seats.keys().cloned().collect();
+        ids.sort_by_key(|id| Into::<usize>::into(*id));
+        for id in ids {
+            self.push_event(Event::Seat{time: self.time(), id, event: event.clone()});
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl std::os::unix::io::AsRawFd for VirtualPlatform {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.wake_reader.as_raw_fd()
+    }
+}
+
+impl PlatformBackend for VirtualPlatform {
+    fn platform_type(&self) -> PlatformType {PlatformType::Compositor}
+    fn events(&mut self) -> Vec<Event> {
+        let mut buffer = [0u8; 64];
+        while let Ok(read) = self.wake_reader.read(&mut buffer) {
+            if read == 0 {break;}
+        }
+        self.pending_events.drain(..).collect()
+    }
+
+    fn requests(&mut self, requests: Vec<Request>) {
+        requests.into_iter().for_each(|request|{
+            let token = request.token();
+            let result = match request {
+                Request::Seat{request: SeatRequest::Keyboard(keyboard_request),..}=>{
+                    match keyboard_request {
+                        KeyboardRequest::ModifyLayout{keymap}=>{
+                            self.seats.values_mut().filter_map(|seat|seat.keyboard.as_mut()).for_each(|keyboard|{
+                                keyboard.keymap = keymap.clone();
+                            });
+                            self.broadcast_seat_event(SeatEvent::Keyboard(KeyboardEvent::LayoutModified{keymap}));
+                            Ok(())
+                        }
+                        KeyboardRequest::SwitchGroup{group}=>{
+                            let valid = self.seats.values().filter_map(|seat|seat.keyboard.as_ref()).all(|keyboard|(group as usize) < keyboard.keymap.layouts.len());
+                            if valid {
+                                // The virtual keyboards have no modifiers, only the group changes.
+                                let empty = Modifiers::empty();
+                                self.broadcast_seat_event(SeatEvent::Keyboard(KeyboardEvent::Modifiers{depressed: empty,latched: empty,locked: empty,group}));
+                                Ok(())
+                            }
+                            else {Err(KeyboardLayoutError::InvalidGroup.into())}
+                        }
+                        KeyboardRequest::SetAutoRepeat{rate,delay}=>{
+                            self.broadcast_seat_event(SeatEvent::Keyboard(KeyboardEvent::AutoRepeat{rate,delay}));
+                            Ok(())
+                        }
+                    }
+                }
+                Request::Seat{request: SeatRequest::Cursor(cursor_request),..}=>{
+                    match cursor_request {
+                        CursorRequest::ChangeMode(mode)=>{
+                            self.seats.values_mut().filter_map(|seat|seat.cursor.as_mut()).for_each(|cursor|{
+                                cursor.mode = mode;
+                            });
+                            self.broadcast_seat_event(SeatEvent::Cursor(CursorEvent::ModeChanged(mode)));
+                        }
+                        CursorRequest::ChangeImage(image)=>{
+                            let visible = image != CursorImage::Hidden;
+                            self.seats.values_mut().filter_map(|seat|seat.cursor.as_mut()).for_each(|cursor|{
+                                cursor.theme = image.clone();
+                                cursor.visible = visible;
+                            });
+                            self.broadcast_seat_event(SeatEvent::Cursor(CursorEvent::VisibilityChanged(visible)));
+                        }
+                    }
+                    Ok(())
+                }
+                Request::Seat{request: SeatRequest::Touch(_touch_request),..}=>Ok(()),
+                Request::Seat{request: SeatRequest::Gamepad(_gamepad_request),..}=>Ok(()),
+                Request::Output{..}=>Ok(()),
+                Request::Surface{request: SurfaceRequest::Create(output),..}=>self.create_surface(output),
+                Request::Surface{request: SurfaceRequest::Destroy(id),..}=>{
+                    match self.surfaces.remove(&id) {
+                        Some(_)=>{
+                            self.push_event(Event::Surface{time: self.time(), id, event: SurfaceEvent::Removed});
+                            Ok(())
+                        }
+                        None=>Err(SurfaceError::UnknownSurface.into())
+                    }
+                }
+                Request::Surface{request: SurfaceRequest::Commit(id),..}=>{
+                    if self.surfaces.contains_key(&id) {Ok(())}
+                    else {Err(SurfaceError::UnknownSurface.into())}
+                }
+                Request::Surface{request: SurfaceRequest::SetImeEnabled{id,enabled},..}=>{
+                    match self.surfaces.get_mut(&id) {
+                        Some(surface)=>{
+                            surface.ime_enabled = enabled;
+                            Ok(())
+                        }
+                        None=>Err(ImeError::UnknownSurface.into())
+                    }
+                }
+                Request::Surface{request: SurfaceRequest::SetImeCursorArea{id,area},..}=>{
+                    match self.surfaces.get_mut(&id) {
+                        Some(surface)=>{
+                            surface.ime_cursor_area = Some(area);
+                            Ok(())
+                        }
+                        None=>Err(ImeError::UnknownSurface.into())
+                    }
+                }
+            };
+            if let Some(token) = token {
+                self.push_event(Event::Request{time: self.time(), token, event: result.into()});
+            }
+        });
+    }
+}
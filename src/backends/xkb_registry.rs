@@ -0,0 +1,148 @@
+use std::path::{Path,PathBuf};
+use crate::definitions::PlatformError;
+use roxmltree::{Document,Node,ParsingOptions};
+
+/// Keyboard model of the XKB registry.
+#[derive(Debug,Clone,PartialEq)]
+pub struct XkbModel {
+    pub name: String,
+    pub description: String,
+    pub vendor: Option<String>,
+}
+
+/// Variant of a keyboard layout.
+#[derive(Debug,Clone,PartialEq)]
+pub struct XkbVariant {
+    pub name: String,
+    pub description: String,
+}
+
+/// Keyboard layout of the XKB registry, along with its variants.
+#[derive(Debug,Clone,PartialEq)]
+pub struct XkbLayout {
+    pub name: String,
+    /// Short label for the layout indicators, like `en`.
+    pub short_description: Option<String>,
+    pub description: String,
+    pub variants: Vec<XkbVariant>,
+}
+
+/// Keymap option, like `grp:alt_shift_toggle`.
+#[derive(Debug,Clone,PartialEq)]
+pub struct XkbOption {
+    pub name: String,
+    pub description: String,
+}
+
+/// Group of related keymap options, like the layout switching shortcuts of `grp`.
+#[derive(Debug,Clone,PartialEq)]
+pub struct XkbOptionGroup {
+    pub name: String,
+    pub description: String,
+    /// Whether more options of the group can be enabled at the same time.
+    pub multiple_selection: bool,
+    pub options: Vec<XkbOption>,
+}
+
+/**
+Models, layouts and options available on the system, as listed by the XKB registry of xkeyboard-config.
+The values are the ones expected by a `KeymapDescriptor`.
+*/
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct XkbRegistry {
+    models: Vec<XkbModel>,
+    layouts: Vec<XkbLayout>,
+    option_groups: Vec<XkbOptionGroup>,
+}
+impl XkbRegistry {
+    /**
+    Load the registry of the evdev rules, the ones used by both X11 and Wayland.
+    The XKB directory is taken from `XKB_CONFIG_ROOT`, defaulting to `/usr/share/X11/xkb`.
+    */
+    pub fn load() -> Result<Self, PlatformError> {
+        let root = std::env::var_os("XKB_CONFIG_ROOT").map(PathBuf::from).unwrap_or_else(||PathBuf::from("/usr/share/X11/xkb"));
+        Self::from_file(root.join("rules").join("evdev.xml"))
+    }
+
+    /// Load a registry file, like `evdev.xml`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, PlatformError> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::parse(&contents)
+    }
+
+    /// Parse the contents of a registry file.
+    pub fn parse(contents: &str) -> Result<Self, PlatformError> {
+        // The registry files declare their DTD, which has to be allowed explicitly.
+        let options = ParsingOptions {allow_dtd: true, ..ParsingOptions::default()};
+        let document = Document::parse_with_options(contents,options).map_err(|err|invalid_data(err.to_string()))?;
+        let root = document.root_element();
+        if !root.has_tag_name("xkbConfigRegistry") {
+            return Err(invalid_data(format!("Unexpected root element {}",root.tag_name().name())));
+        }
+
+        let models = children(root,"modelList").flat_map(|list|children(list,"model")).map(|model|XkbModel {
+            name: config_text(model,"name").unwrap_or_default(),
+            description: config_text(model,"description").unwrap_or_default(),
+            vendor: config_text(model,"vendor"),
+        }).collect();
+
+        let layouts = children(root,"layoutList").flat_map(|list|children(list,"layout")).map(|layout|{
+            let variants = children(layout,"variantList").flat_map(|list|children(list,"variant")).map(|variant|XkbVariant {
+                name: config_text(variant,"name").unwrap_or_default(),
+                description: config_text(variant,"description").unwrap_or_default()
+            }).collect();
+            XkbLayout {
+                name: config_text(layout,"name").unwrap_or_default(),
+                short_description: config_text(layout,"shortDescription"),
+                description: config_text(layout,"description").unwrap_or_default(),
+                variants
+            }
+        }).collect();
+
+        let option_groups = children(root,"optionList").flat_map(|list|children(list,"group")).map(|group|{
+            let options = children(group,"option").map(|option|XkbOption {
+                name: config_text(option,"name").unwrap_or_default(),
+                description: config_text(option,"description").unwrap_or_default()
+            }).collect();
+            XkbOptionGroup {
+                name: config_text(group,"name").unwrap_or_default(),
+                description: config_text(group,"description").unwrap_or_default(),
+                multiple_selection: group.attribute("allowMultipleSelection") == Some("true"),
+                options
+            }
+        }).collect();
+
+        Ok(Self {models,layouts,option_groups})
+    }
+
+    pub fn models(&self) -> &[XkbModel] {&self.models}
+
+    pub fn layouts(&self) -> &[XkbLayout] {&self.layouts}
+    pub fn layout(&self, name: &str) -> Option<&XkbLayout> {
+        self.layouts.iter().find(|layout|layout.name == name)
+    }
+
+    pub fn option_groups(&self) -> &[XkbOptionGroup] {&self.option_groups}
+    /// Option with the given name, like `grp:alt_shift_toggle`.
+    pub fn option(&self, name: &str) -> Option<&XkbOption> {
+        self.option_groups.iter().flat_map(|group|group.options.iter()).find(|option|option.name == name)
+    }
+}
+
+fn invalid_data(reason: String) -> PlatformError {
+    std::io::Error::new(std::io::ErrorKind::InvalidData,reason).into()
+}
+
+/// Elements with the given name among the children of the node.
+fn children<'a,'input: 'a>(node: Node<'a,'input>, name: &'a str) -> impl Iterator<Item=Node<'a,'input>> + 'a {
+    node.children().filter(move|child|child.has_tag_name(name))
+}
+fn child<'a,'input>(node: Node<'a,'input>, name: &str) -> Option<Node<'a,'input>> {
+    node.children().find(|child|child.has_tag_name(name))
+}
+
+/// Text of an entry of the `configItem` describing the node.
+fn config_text(node: Node, name: &str) -> Option<String> {
+    let entry = child(child(node,"configItem")?,name)?;
+    Some(entry.children().filter_map(|node|node.text()).collect::<String>().trim().to_string())
+}
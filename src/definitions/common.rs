@@ -2,6 +2,7 @@ use std::ops::{Add,Sub};
 use std::cmp::PartialOrd;
 
 #[derive(Clone,Copy,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /**
 Mode used by the cursor. Absolute means the associated coordinates are an absolute.
 Relative means the associated coordinates are relative to the previous coordinates.
@@ -12,6 +13,7 @@ pub enum CursorMode {
 }
 
 #[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Position on a bidimensional space.
 pub struct Position2D<T>{pub x: T,pub y: T}
 impl<T> From<(T,T)> for Position2D<T> {
@@ -72,6 +74,7 @@ impl<T: std::fmt::Display> std::fmt::Display for Position2D<T> {
 }
 
 #[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Position on a tridimensional space.
 pub struct Position3D<T>{pub x: T,pub y: T,pub z: T}
 impl<T> From<(T,T,T)> for Position3D<T> {
@@ -107,6 +110,7 @@ impl<T: Sub<Output = T>> Sub<Offset2D<T>> for Position3D<T> {
 }
 
 #[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Offset on a bidimensional space.
 pub struct Offset2D<T>{pub x: T,pub y: T}
 impl<T> From<(T,T)> for Offset2D<T> {
@@ -160,6 +164,7 @@ impl<T: std::fmt::Display> std::fmt::Display for Offset2D<T> {
 }
 
 #[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Size on a bidimensional space.
 pub struct Size2D<T>{pub width: T,pub height: T}
 impl<T> From<(T,T)> for Size2D<T> {
@@ -215,6 +220,7 @@ impl<T: std::fmt::Display> std::fmt::Display for Size2D<T> {
 }
 
 #[derive(Debug,Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Combination of position and offset on a bidimensional space.
 pub struct Rectangle<P,S> {
     pub position: Position2D<P>,
@@ -298,6 +304,7 @@ impl<P,S> From<(Size2D<S>,Position2D<P>)> for Rectangle<P,S> {
 }
 
 #[derive(Debug, Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Surface transformation.
 pub enum Transform {
     Normal,
@@ -312,6 +319,7 @@ pub enum Transform {
 
 
 #[derive(Debug, Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Display mode.
 pub struct Mode {
     pub resolution: Size2D<u32>,
@@ -320,6 +328,7 @@ pub struct Mode {
 }
 
 #[derive(Debug, Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Subpixel alignment.
 pub enum Subpixel {
     Unknown,
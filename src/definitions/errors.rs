@@ -1,25 +1,177 @@
+use crate::definitions::BackendKind;
 
 #[derive(Debug,Clone,Copy,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible error while creating surface.
 pub enum SurfaceError {
     TooManySurfaces,
+    InvalidOutput,
+    UnknownSurface,
     Unsupported
 }
 
 #[derive(Debug,Clone,Copy,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible error while setting cursor mode.
 pub enum CursorModeError {
     Unsupported
 }
 
 #[derive(Debug,Clone,Copy,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Possible error while setting cursor image.
+pub enum CursorImageError {
+    Unsupported
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible error while setting key repeat.
 pub enum KeyRepeatError {
     Unsupported
 }
 
 #[derive(Debug,Clone,Copy,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible error while setting keyboard layout.
 pub enum KeyboardLayoutError {
+    /// The group is out of the layouts of the keymap.
+    InvalidGroup,
+    /// The keymap cannot be compiled, because of unknown rules, models, layouts, variants or options.
+    InvalidKeymap,
+    Unsupported
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Possible error while configuring the input method.
+pub enum ImeError {
+    UnknownSurface,
     Unsupported
 }
+
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Crate-wide error reported by the backends.
+pub enum PlatformError {
+    /// Failed to connect to the display server, or the connection was lost.
+    Connection(String),
+    /// A protocol extension required by the backend is not available.
+    MissingExtension(String),
+    /// The display server answered a request with an error.
+    Protocol(String),
+    /// Failed to create a surface.
+    SurfaceCreation(SurfaceError),
+    /// The support for the backend is not compiled in.
+    BackendNotCompiled,
+    /// An operating system call failed.
+    Io(String),
+}
+impl std::fmt::Display for PlatformError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connection(reason)=>write!(f, "Connection error: {}", reason),
+            Self::MissingExtension(name)=>write!(f, "Missing {} extension", name),
+            Self::Protocol(reason)=>write!(f, "Protocol error: {}", reason),
+            Self::SurfaceCreation(error)=>write!(f, "Failed to create surface: {:?}", error),
+            Self::BackendNotCompiled=>write!(f, "Support for this backend is not compiled in"),
+            Self::Io(reason)=>write!(f, "IO error: {}", reason),
+        }
+    }
+}
+impl std::error::Error for PlatformError {}
+impl From<SurfaceError> for PlatformError {
+    fn from(error: SurfaceError)->Self {Self::SurfaceCreation(error)}
+}
+impl From<std::io::Error> for PlatformError {
+    fn from(error: std::io::Error)->Self {Self::Io(error.to_string())}
+}
+
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Possible error while handling a request.
+pub enum RequestError {
+    Surface(SurfaceError),
+    CursorMode(CursorModeError),
+    CursorImage(CursorImageError),
+    KeyRepeat(KeyRepeatError),
+    KeyboardLayout(KeyboardLayoutError),
+    Ime(ImeError),
+    Platform(PlatformError),
+}
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Surface(error)=>write!(f, "Surface request failed: {:?}", error),
+            Self::CursorMode(error)=>write!(f, "Cursor mode request failed: {:?}", error),
+            Self::CursorImage(error)=>write!(f, "Cursor image request failed: {:?}", error),
+            Self::KeyRepeat(error)=>write!(f, "Key repeat request failed: {:?}", error),
+            Self::KeyboardLayout(error)=>write!(f, "Keyboard layout request failed: {:?}", error),
+            Self::Ime(error)=>write!(f, "Input method request failed: {:?}", error),
+            Self::Platform(error)=>write!(f, "{}", error),
+        }
+    }
+}
+impl std::error::Error for RequestError {}
+impl From<PlatformError> for RequestError {
+    fn from(error: PlatformError)->Self {
+        match error {
+            PlatformError::SurfaceCreation(error)=>Self::Surface(error),
+            error=>Self::Platform(error)
+        }
+    }
+}
+impl From<SurfaceError> for RequestError {
+    fn from(error: SurfaceError)->Self {Self::Surface(error)}
+}
+impl From<CursorModeError> for RequestError {
+    fn from(error: CursorModeError)->Self {Self::CursorMode(error)}
+}
+impl From<CursorImageError> for RequestError {
+    fn from(error: CursorImageError)->Self {Self::CursorImage(error)}
+}
+impl From<KeyRepeatError> for RequestError {
+    fn from(error: KeyRepeatError)->Self {Self::KeyRepeat(error)}
+}
+impl From<KeyboardLayoutError> for RequestError {
+    fn from(error: KeyboardLayoutError)->Self {Self::KeyboardLayout(error)}
+}
+impl From<ImeError> for RequestError {
+    fn from(error: ImeError)->Self {Self::Ime(error)}
+}
+
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Reason why a backend failed to initialize.
+pub struct BackendFailure {
+    pub backend: BackendKind,
+    pub reason: PlatformError
+}
+impl std::fmt::Display for BackendFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.backend, self.reason)
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Possible error while building a platform.
+pub enum PlatformBuildError {
+    /// The requested backend name is not recognized.
+    InvalidBackendName(String),
+    /// Every attempted backend failed, in the order they were tried.
+    NoBackendAvailable(Vec<BackendFailure>)
+}
+impl std::fmt::Display for PlatformBuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidBackendName(name)=>write!(f, "Unknown backend \"{}\"", name),
+            Self::NoBackendAvailable(failures)=>{
+                write!(f, "No backend available")?;
+                for failure in failures {write!(f, "\n  {}", failure)?;}
+                Ok(())
+            }
+        }
+    }
+}
+impl std::error::Error for PlatformBuildError {}
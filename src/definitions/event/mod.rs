@@ -7,22 +7,44 @@ pub use surface_event::*;
 mod output_event;
 pub use output_event::*;
 
+mod request_event;
+pub use request_event::*;
+
+mod time;
+pub use time::*;
+
+use crate::definitions::RequestToken;
+
 #[derive(Clone, Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible events.
 pub enum Event {
     Seat{
-        time: u32,
+        time: EventTime,
         id: SeatId,
         event: SeatEvent,
     },
     Output{
-        time: u32,
+        time: EventTime,
         id: OutputId,
         event: OutputEvent
     },
     Surface{
-        time: u32,
+        time: EventTime,
         id: SurfaceId,
         event: SurfaceEvent,
     },
+    Request{
+        time: EventTime,
+        token: RequestToken,
+        event: RequestEvent,
+    },
+}
+impl Event {
+    /// Time of the event.
+    pub fn time(&self) -> EventTime {
+        match self {
+            Self::Seat{time,..} | Self::Output{time,..} | Self::Surface{time,..} | Self::Request{time,..}=>*time
+        }
+    }
 }
@@ -1,6 +1,7 @@
 use crate::definitions::{Position2D,Size2D,Mode,Subpixel};
 
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible output events.
 pub enum OutputEvent {
     Added(OutputInfo),
@@ -11,6 +12,7 @@ pub enum OutputEvent {
 }
 
 #[derive(Debug, PartialEq, Hash, Copy, Clone,Eq,Ord,PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Output identifier.
 pub struct OutputId(usize);
 impl Into<usize> for OutputId {
@@ -41,6 +43,7 @@ impl std::fmt::Display for OutputId {
 
 
 #[derive(Debug, Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Output informations.
 pub struct OutputInfo {
     pub position: Position2D<u32>,
@@ -0,0 +1,17 @@
+use crate::definitions::RequestError;
+
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Possible outcomes of a request.
+pub enum RequestEvent {
+    Completed,
+    Failed(RequestError)
+}
+impl From<Result<(),RequestError>> for RequestEvent {
+    fn from(result: Result<(),RequestError>) -> Self {
+        match result {
+            Ok(())=>Self::Completed,
+            Err(error)=>Self::Failed(error)
+        }
+    }
+}
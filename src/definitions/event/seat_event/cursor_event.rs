@@ -3,6 +3,7 @@ use keyboard_types::KeyState as State;
 use crate::definitions::{SurfaceId,Position2D,Offset2D,CursorMode};
 
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible cursor events.
 pub enum CursorEvent {
     Added(CursorInfo),
@@ -37,12 +38,14 @@ pub enum CursorEvent {
 
 
 #[derive(Clone,Copy,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Source of the axis event.
 pub enum AxisSource {
     Wheel
 }
 
 #[derive(Clone,Copy,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Direction of the axis event.
 pub enum AxisDirection {
     Horizontal,
@@ -50,13 +53,15 @@ pub enum AxisDirection {
 }
 
 #[derive(Clone,Copy,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Value of the axis event.
 pub enum AxisValue {
     Discrete(i32),
     Continuous(f32)
 }
 
-#[derive(Clone,Copy,Debug,PartialEq)]
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible buttons for button events.
 pub enum Button {
     Left,
@@ -65,6 +70,7 @@ pub enum Button {
 }
 
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Image kind for the cursor.
 pub enum CursorImage {
     Custom(Vec<u8>),
@@ -73,6 +79,7 @@ pub enum CursorImage {
 }
 
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Cursor informations.
 pub struct CursorInfo {
     pub mode: CursorMode,
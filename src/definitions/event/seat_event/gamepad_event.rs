@@ -1,4 +1,5 @@
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible gamepad events.
 pub enum GamepadEvent {
     Added(GamepadInfo),
@@ -6,6 +7,7 @@ pub enum GamepadEvent {
 }
 
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Gamepad informations.
 pub struct GamepadInfo {
 
@@ -0,0 +1,38 @@
+use crate::definitions::{SurfaceId,Position2D,Button};
+
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/**
+Possible gesture events, recognized from the button and movement events by a `GestureRecognizer`.
+Positions are relative to the surface under the cursor, and the surface is the one the gesture started on.
+*/
+pub enum GestureEvent {
+    /// Button released without dragging. The count is 2 for a double click, 3 for a triple click and so on.
+    Click {
+        button: Button,
+        count: u32,
+        surface_id: Option<SurfaceId>,
+        position: Position2D<i32>,
+    },
+    DragStarted {
+        button: Button,
+        surface_id: Option<SurfaceId>,
+        position: Position2D<i32>,
+    },
+    DragMoved {
+        button: Button,
+        surface_id: Option<SurfaceId>,
+        position: Position2D<i32>,
+    },
+    DragEnded {
+        button: Button,
+        surface_id: Option<SurfaceId>,
+        position: Position2D<i32>,
+    },
+    /// Button held still long enough. No click is reported on release.
+    LongPress {
+        button: Button,
+        surface_id: Option<SurfaceId>,
+        position: Position2D<i32>,
+    },
+}
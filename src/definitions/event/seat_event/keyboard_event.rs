@@ -1,33 +1,144 @@
-pub use keyboard_types::{Code as Key,KeyState as State};
+pub use keyboard_types::{Code as Key,Key as LogicalKey,KeyState as State,Location,Modifiers};
 
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible keyboard events.
 pub enum KeyboardEvent {
     Added(KeyboardInfo),
     Removed,
     Key{
         code: u32,
+        /// Physical key, independent from the layout.
         key: Option<Key>,
+        /// Key value with the layout and the modifiers applied, such as `LogicalKey::Character("é")` or `LogicalKey::ArrowLeft`.
+        logical_key: LogicalKey,
+        /// Location of the physical key, telling apart the left, right and numpad variants.
+        location: Location,
         state: State,
         serial: u32,
-        time: u32
+        time: crate::definitions::EventTime,
+        /// Whether the event is an auto repeat of a held key, rather than a physical press.
+        repeat: bool,
+        /**
+        Text produced by the key press, with the layout, the modifiers and the compose sequences applied.
+        It is `None` for releases, non printable keys and keys consumed by a dead key or compose sequence.
+        */
+        text: Option<String>
     },
     AutoRepeat{
         rate: u32,
         delay: u32
     },
-    LayoutModified{layout: String}
+    /**
+    Text being composed by the input method on the focused surface, replacing the previous pre-edit text.
+    The cursor is a byte range inside the text, `None` when the cursor should be hidden.
+    An empty text clears the pre-edit.
+    */
+    Preedit{
+        text: String,
+        cursor: Option<(usize,usize)>
+    },
+    /// Text committed by the input method on the focused surface, replacing the pre-edit text.
+    Commit{text: String},
+    LayoutModified{keymap: KeymapDescriptor},
+    /**
+    Compiled keymap of the keyboard, reported after it is added and whenever a layout change or a server side change replaces it.
+    Compositors forward it to their clients, for example through a `KeymapFile`.
+    */
+    KeymapChanged{keymap: Keymap},
+    /**
+    Exact modifier state of the keyboard, including the modifiers pressed before the surface got the focus.
+    The group is the index of the active layout, for the keyboards configured with multiple layouts.
+    */
+    Modifiers{
+        depressed: Modifiers,
+        latched: Modifiers,
+        locked: Modifiers,
+        group: u32
+    }
 }
 
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Keyboard informations.
 pub struct KeyboardInfo {
-    pub layout: String,
+    pub keymap: KeymapDescriptor,
     pub autorepeat: bool,
     pub encoding: KeyEncoding
 }
 
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/**
+Keymap configuration, as the XKB rules, model, layouts, variants and options (RMLVO) names.
+Each layout is a group the keyboard can switch to, with the variant at the same index.
+The available values are listed by the `XkbRegistry`.
+*/
+pub struct KeymapDescriptor {
+    pub rules: String,
+    pub model: String,
+    pub layouts: Vec<String>,
+    pub variants: Vec<String>,
+    pub options: Vec<String>,
+}
+impl KeymapDescriptor {
+    /// Descriptor with the default rules and model, from comma separated layouts like `us,it`.
+    pub fn from_layouts(layouts: &str)->Self {
+        Self {layouts: split_list(layouts),..Self::default()}
+    }
+    /// Set the comma separated variants, one for each layout, like `,winkeys`.
+    pub fn with_variants(mut self, variants: &str)->Self {
+        self.variants = split_list(variants);
+        self
+    }
+    /// Set the comma separated options, like `grp:alt_shift_toggle`.
+    pub fn with_options(mut self, options: &str)->Self {
+        self.options = split_list(options);
+        self
+    }
+
+    /// Layouts in the comma separated form used by XKB.
+    pub fn layouts_string(&self)->String {self.layouts.join(",")}
+    /// Variants in the comma separated form used by XKB.
+    pub fn variants_string(&self)->String {self.variants.join(",")}
+    /// Options in the comma separated form used by XKB.
+    pub fn options_string(&self)->String {self.options.join(",")}
+}
+impl Default for KeymapDescriptor {
+    fn default()->Self {
+        Self {
+            rules: String::from("evdev"),
+            model: String::from("pc105"),
+            layouts: vec![String::from("us")],
+            variants: Vec::new(),
+            options: Vec::new()
+        }
+    }
+}
+
+fn split_list(list: &str)->Vec<String> {
+    if list.is_empty() {return Vec::new();}
+    list.split(',').map(|item|item.trim().to_string()).collect()
+}
+
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/**
+Compiled keymap, in the XKB text format read by `xkb_keymap_new_from_string`.
+It is the keymap sent to Wayland clients through `wl_keyboard.keymap`.
+*/
+pub struct Keymap {
+    /// Configuration the keymap has been compiled from.
+    pub descriptor: KeymapDescriptor,
+    pub text: String,
+}
+impl Keymap {
+    /// Size of the keymap once shared as a file, including the NUL terminator expected by the clients.
+    pub fn size(&self)->usize {self.text.len() + 1}
+}
+
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Keystroke encoding.
 pub enum KeyEncoding {
     XkbV1
@@ -6,8 +6,11 @@ mod keyboard_event;
 pub use keyboard_event::*;
 mod gamepad_event;
 pub use gamepad_event::*;
+mod gesture_event;
+pub use gesture_event::*;
 
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible seat events.
 pub enum SeatEvent {
     Added{
@@ -18,7 +21,8 @@ pub enum SeatEvent {
     Keyboard(KeyboardEvent),
     Cursor(CursorEvent),
     Touch(TouchEvent),
-    Gamepad(GamepadEvent)
+    Gamepad(GamepadEvent),
+    Gesture(GestureEvent)
 }
 
 impl From<KeyboardEvent> for SeatEvent {
@@ -39,7 +43,14 @@ impl From<TouchEvent> for SeatEvent {
     }
 }
 
+impl From<GestureEvent> for SeatEvent {
+    fn from(event: GestureEvent) -> Self {
+        Self::Gesture(event)
+    }
+}
+
 #[derive(Debug, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Seat identifier.
 pub struct SeatId(usize);
 impl Into<usize> for SeatId {
@@ -65,6 +76,7 @@ impl From<i32> for SeatId {
 impl Eq for SeatId {}
 
 #[derive(Debug,Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Seat informations.
 pub struct SeatInfo {
     pub id: SeatId,
@@ -1,4 +1,5 @@
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible touch events.
 pub enum TouchEvent {
     Added(TouchInfo),
@@ -6,6 +7,7 @@ pub enum TouchEvent {
 }
 
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Touch informations.
 pub struct TouchInfo {
 
@@ -4,6 +4,7 @@ use crate::definitions::{Position2D,Size2D,OutputId};
 use std::sync::Arc;
 
 #[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible surface events.
 pub enum SurfaceEvent {
     Added(SurfaceInfo),
@@ -19,6 +20,7 @@ pub enum SurfaceEvent {
 }
 
 #[derive(Debug, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Surface identifier.
 pub struct SurfaceId(usize);
 impl Into<usize> for SurfaceId {
@@ -44,10 +46,13 @@ impl From<i32> for SurfaceId {
 impl Eq for SurfaceId {}
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Surface informations.
 pub struct SurfaceInfo {
     pub position: Position2D<u32>,
     pub size: Size2D<u32>,
+    /// Surface handles can't be serialized, they are deserialized as `Surface::Detached`.
+    #[cfg_attr(feature = "serde", serde(skip, default = "Surface::detached"))]
     pub surface: Surface
 }
 
@@ -56,7 +61,14 @@ pub struct SurfaceInfo {
 pub enum Surface {
     Raw(RawWindowHandle),
     #[cfg(feature="wgpu_backend")]
-    WGpu(Arc<crate::wgpu::Surface>)
+    WGpu(Arc<crate::wgpu::Surface>),
+    #[cfg(feature="virtual_platform")]
+    Virtual(SurfaceId),
+    /// Surface without an handle, like the ones restored from a serialized event.
+    Detached
+}
+impl Surface {
+    fn detached() -> Self {Self::Detached}
 }
 impl PartialEq for Surface {
     fn eq(&self, other: &Self) -> bool {
@@ -64,6 +76,9 @@ impl PartialEq for Surface {
             (Self::Raw(raw1),Self::Raw(raw2))=>raw1 == raw2,
             #[cfg(feature="wgpu_backend")]
             (Self::WGpu(raw1),Self::WGpu(raw2))=>Arc::ptr_eq(raw1,raw2),
+            #[cfg(feature="virtual_platform")]
+            (Self::Virtual(id1),Self::Virtual(id2))=>id1 == id2,
+            (Self::Detached,Self::Detached)=>true,
             _=>false
         }
     }
@@ -85,6 +100,7 @@ impl<S> std::fmt::Debug for SurfaceInfo<S> {
 
 bitflags::bitflags! {
     /// Surface modes.
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct SurfaceMode: u32 {
         const MINIMIZED = (1 << 0);
         const MAXIMIZED = (1 << 1);
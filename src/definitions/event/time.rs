@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/**
+Timestamp of an event.
+Every backend reports its events on the same monotonic clock,
+while the timestamp originally reported by the backend, if any, is kept apart.
+*/
+pub struct EventTime {
+    /// Nanoseconds on the `CLOCK_MONOTONIC` clock.
+    pub monotonic: u64,
+    /// Timestamp reported by the backend, in its own unit and epoch (milliseconds for XCB).
+    pub raw: Option<u32>,
+}
+impl EventTime {
+    #[cfg(target_os = "linux")]
+    /// Current time, without a backend timestamp.
+    pub fn now()->Self {
+        Self::from_monotonic(monotonic_now())
+    }
+
+    pub fn from_monotonic(monotonic: u64)->Self {
+        Self {monotonic,raw: None}
+    }
+
+    /// Time elapsed since the monotonic clock epoch.
+    pub fn as_duration(&self)->Duration {
+        Duration::from_nanos(self.monotonic)
+    }
+
+    /// Time elapsed since an earlier event, zero if it is not actually earlier.
+    pub fn duration_since(&self, earlier: EventTime)->Duration {
+        Duration::from_nanos(self.monotonic.saturating_sub(earlier.monotonic))
+    }
+}
+
+#[cfg(target_os = "linux")]
+/// Read the `CLOCK_MONOTONIC` clock in nanoseconds.
+pub(crate) fn monotonic_now()->u64 {
+    let mut time = libc::timespec {tv_sec: 0, tv_nsec: 0};
+    unsafe{libc::clock_gettime(libc::CLOCK_MONOTONIC,&mut time);}
+    time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64
+}
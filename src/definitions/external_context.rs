@@ -2,7 +2,7 @@
 use raw_window_handle::RawWindowHandle;
 
 
-use crate::definitions::Surface;
+use crate::definitions::{Surface,PlatformError};
 use std::sync::Arc;
 
 /*
@@ -13,19 +13,19 @@ pub enum ExternalContext {
 
 /// Trait providing the capability to create surfaces.
 pub trait ExternalContext {
-    fn create_surface(&self,raw_surface: &RawSurfaceHandle)->Result<Surface,()>;
+    fn create_surface(&self,raw_surface: &RawSurfaceHandle)->Result<Surface,PlatformError>;
 }
 
 #[derive(Debug)]
 /// Raw context to create surfaces.
 pub struct RawContext;
 impl ExternalContext for RawContext {
-    fn create_surface(&self,raw_surface: &RawSurfaceHandle)->Result<Surface,()> {
+    fn create_surface(&self,raw_surface: &RawSurfaceHandle)->Result<Surface,PlatformError> {
         match raw_surface {
             RawSurfaceHandle::Xcb(xcb_handle)=>Ok(Surface::Raw(RawWindowHandle::Xcb(*xcb_handle))),
             RawSurfaceHandle::Wayland(wayland_handle)=>Ok(Surface::Raw(RawWindowHandle::Wayland(*wayland_handle))),
             #[cfg(feature="wgpu")]
-            RawSurfaceHandle::WGpuDisplay(_display)=>Err(())
+            RawSurfaceHandle::WGpuDisplay(_display)=>Err(crate::definitions::SurfaceError::Unsupported.into())
         }
     }
 }
@@ -41,7 +41,7 @@ pub struct WgpuContext{
 
 #[cfg(feature="wgpu_backend")]
 impl ExternalContext for WgpuContext {
-    fn create_surface(&self,raw_surface: &RawSurfaceHandle)->Result<Surface,()> {
+    fn create_surface(&self,raw_surface: &RawSurfaceHandle)->Result<Surface,PlatformError> {
         match raw_surface {
             RawSurfaceHandle::Xcb(xcb_handle)=>{
                 let handle: Window = RawWindowHandle::Xcb(*xcb_handle).into();
@@ -54,7 +54,7 @@ impl ExternalContext for WgpuContext {
                 return Ok(Surface::WGpu(Arc::new(surface)));
             },
             #[cfg(feature="wgpu")]
-            RawSurfaceHandle::WGpuDisplay(_display)=>return Err(())
+            RawSurfaceHandle::WGpuDisplay(_display)=>return Err(crate::definitions::SurfaceError::Unsupported.into())
         }
     }
 }
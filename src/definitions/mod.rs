@@ -40,6 +40,7 @@ Linux platforms are required to also implement AsRawFd to get the underlying pol
 pub trait LinuxPlatformBackend: PlatformBackend + std::os::unix::io::AsRawFd {}
 
 #[derive(Debug,Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /**
 Enumeration representing the different types of platform.
 */
@@ -47,3 +48,43 @@ pub enum PlatformType {
     Compositor,
     Direct
 }
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/**
+Enumeration representing the backends a platform can be driven by.
+*/
+pub enum BackendKind {
+    Wayland,
+    Xcb,
+    Libinput,
+    Virtual
+}
+impl BackendKind {
+    /// Name of the backend, as accepted by the `PAL_BACKEND` environment variable.
+    pub fn name(&self)->&'static str {
+        match self {
+            Self::Wayland=>"wayland",
+            Self::Xcb=>"xcb",
+            Self::Libinput=>"libinput",
+            Self::Virtual=>"virtual"
+        }
+    }
+}
+impl std::str::FromStr for BackendKind {
+    type Err = PlatformBuildError;
+    fn from_str(name: &str)->Result<Self,Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "wayland"=>Ok(Self::Wayland),
+            "xcb"|"x11"=>Ok(Self::Xcb),
+            "libinput"=>Ok(Self::Libinput),
+            "virtual"|"headless"=>Ok(Self::Virtual),
+            _=>Err(PlatformBuildError::InvalidBackendName(String::from(name)))
+        }
+    }
+}
+impl std::fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -5,28 +5,67 @@ pub use output_request::*;
 mod surface_request;
 pub use surface_request::*;
 
-//use crate::definitions::{SeatId,OutputId,SurfaceId};
-/// Possible requests.
+#[derive(Debug, PartialEq, Hash, Copy, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Caller supplied token used to correlate a request with its outcome.
+pub struct RequestToken(u64);
+impl From<RequestToken> for u64 {
+    fn from(token: RequestToken) -> Self {
+        token.0
+    }
+}
+impl From<u64> for RequestToken {
+    fn from(token: u64) -> Self {
+        Self(token)
+    }
+}
+impl std::fmt::Display for RequestToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/**
+Possible requests.
+When a token is supplied, the outcome of the request is reported back through an `Event::Request`.
+*/
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Request {
     Seat{
+        token: Option<RequestToken>,
         request: SeatRequest
     },
     Output{
+        token: Option<RequestToken>,
         request: OutputRequest
     },
     Surface{
+        token: Option<RequestToken>,
         request: SurfaceRequest
     },
 }
+impl Request {
+    /// Attach a token to the request, to be notified about its outcome.
+    pub fn with_token(mut self, new_token: impl Into<RequestToken>) -> Self {
+        match &mut self {
+            Self::Seat{token,..}|Self::Output{token,..}|Self::Surface{token,..}=>*token = Some(new_token.into())
+        }
+        self
+    }
+    /// Token attached to the request, if any.
+    pub fn token(&self) -> Option<RequestToken> {
+        match self {
+            Self::Seat{token,..}|Self::Output{token,..}|Self::Surface{token,..}=>*token
+        }
+    }
+}
 
-/*
 impl From<SeatRequest> for Request {
-    fn from(request: SeatRequest)->Self {Self::Seat(request)}
+    fn from(request: SeatRequest)->Self {Self::Seat{token: None, request}}
 }
 impl From<OutputRequest> for Request {
-    fn from(request: OutputRequest)->Self {Self::Output(request)}
+    fn from(request: OutputRequest)->Self {Self::Output{token: None, request}}
 }
 impl From<SurfaceRequest> for Request {
-    fn from(request: SurfaceRequest)->Self {Self::Surface(request)}
+    fn from(request: SurfaceRequest)->Self {Self::Surface{token: None, request}}
 }
-*/
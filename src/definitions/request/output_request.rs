@@ -1,11 +1,13 @@
 use crate::definitions::OutputId;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible output requests.
 pub struct OutputRequest {
     pub id: OutputId,
     pub event: OutputRequestType,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Output request type.
 pub enum OutputRequestType {
 
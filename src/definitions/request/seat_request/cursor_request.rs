@@ -2,6 +2,7 @@ use crate::definitions::CursorMode;
 use crate::definitions::CursorImage;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible cursor requests.
 pub enum CursorRequest {
     ChangeMode(CursorMode),
@@ -1,6 +1,13 @@
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible keyboard requests.
 pub enum KeyboardRequest {
-    ModifyLayout{layout: String},
+    /**
+    Replace the keymap, reported back through `KeyboardEvent::LayoutModified`,
+    followed by `KeyboardEvent::KeymapChanged` on the backends compiling the keymap.
+    */
+    ModifyLayout{keymap: crate::definitions::KeymapDescriptor},
+    /// Activate the layout group at the given index of the keymap layouts.
+    SwitchGroup{group: u32},
     SetAutoRepeat{
         rate: u32,
         delay: u32
@@ -1,9 +1,21 @@
-use crate::definitions::{SurfaceId,OutputId};
+use crate::definitions::{SurfaceId,OutputId,Rectangle};
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible surface requests.
 pub enum SurfaceRequest {
     Create(Option<OutputId>),
     Destroy(SurfaceId),
 
-    Commit(SurfaceId)
+    Commit(SurfaceId),
+
+    /// Enable or disable the input method while the surface is focused.
+    SetImeEnabled{
+        id: SurfaceId,
+        enabled: bool
+    },
+    /// Area of the text cursor, relative to the surface, next to which the input method places its candidate window.
+    SetImeCursorArea{
+        id: SurfaceId,
+        area: Rectangle<i32,u32>
+    }
 }
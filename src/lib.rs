@@ -3,7 +3,20 @@
 pub mod definitions;
 pub use definitions::*;
 mod backends;
-pub use backends::Platform;
+pub use backends::{Platform,PlatformBuilder,BACKEND_ENV_VAR};
+pub use backends::{EventProcessor,EventPipeline,ResizeCoalescer,Deduplicator,Filter,MotionCoalescer,MotionHistory,GestureRecognizer,GestureConfig};
+#[cfg(target_os = "linux")]
+pub use backends::{DEFAULT_REPEAT_RATE,DEFAULT_REPEAT_DELAY,PlatformHandle,PlatformSource,AsyncPlatform,NextEvent,RequestSink};
+#[cfg(target_os = "linux")]
+pub use backends::KeymapFile;
+#[cfg(all(target_os = "linux",feature="linux_platform"))]
+pub use backends::{XkbRegistry,XkbModel,XkbLayout,XkbVariant,XkbOptionGroup,XkbOption};
+#[cfg(feature="virtual_platform")]
+pub use backends::VirtualPlatform;
+#[cfg(feature="state_tracker")]
+pub use backends::{StateTracker,SeatState,SurfaceState,KeyboardState};
+#[cfg(feature="record_replay")]
+pub use backends::{Recorder,RecordEntry,ReplayPlatform,ReplayMode};
 
 
 #[cfg(feature = "wgpu_custom_backend")]
@@ -7,131 +7,1045 @@ fn test_create(){
 
 */
 use std::collections::HashMap;
+use crate::Platform;
+use crate::definitions::*;
+
+/// Run the platform until every created surface is destroyed, forwarding the other events to the handler.
+fn run_until_closed(platform: Platform, mut handler: impl FnMut(&Event,&mut Platform) + 'static) {
+    use crate::PlatformSource;
+
+    let mut platform = platform;
+    if platform.platform_type() == PlatformType::Compositor {
+        platform.requests(vec![Request::Surface{token: None, request: SurfaceRequest::Create(None)}]);
+    }
+
+    let mut event_loop = calloop::EventLoop::try_new().unwrap();
+    let signal = event_loop.get_signal();
+    event_loop.handle().insert_source(PlatformSource::new(platform).unwrap(),move|event,platform,surfaces: &mut HashMap<SurfaceId,()>|{
+        println!("{:#?}", event);
+        match event {
+            Event::Output{time: _, id, event: OutputEvent::Added(_)} if platform.platform_type() == PlatformType::Direct=>{
+                platform.requests(vec![Request::Surface{token: None, request: SurfaceRequest::Create(Some(id))}]);
+            }
+            Event::Surface{ time: _, id, event: SurfaceEvent::Added(_) }=>{
+                surfaces.insert(id,());
+            }
+            Event::Surface{ time: _, id, event: SurfaceEvent::Removed }=>{
+                surfaces.remove(&id);
+                if surfaces.is_empty() {signal.stop();}
+            }
+            _=>{}
+        }
+        handler(&event,platform);
+    }).unwrap();
+
+    let mut surfaces = HashMap::new();
+    event_loop.run(None,&mut surfaces,|_|{}).unwrap();
+}
+
+#[cfg(any(feature = "wayland_platform", feature = "xcb_platform"))]
 #[test]
 fn create_window() {
-    use crate::Platform;
-    use crate::definitions::*;
-
-    let mut platform = Platform::new(vec![Box::new(RawContext)]);
-
-    match platform.platform_type(){
-        PlatformType::Compositor=>{
-            platform.request(vec![Request::Surface{request: SurfaceRequest::Create(None)}]);
-        }
-        PlatformType::Direct=>{
-            let initial_requests: Vec<_> = platform.events().into_iter().filter_map(|event|{
-                match event {
-                    Event::Output{time: _, id, event: OutputEvent::Added(_)}=>{
-                        Some(Request::Surface{request: SurfaceRequest::Create(Some(id))})
-                    },
-                    _=>None
-                }
-            }).collect();
+    let platform = Platform::new(vec![Box::new(RawContext)]);
+    run_until_closed(platform,|_event,_platform|{});
+}
+
+#[cfg(any(feature = "wayland_platform", feature = "xcb_platform"))]
+#[test]
+fn cursor_test() {
+    let platform = Platform::new(vec![Box::new(RawContext)]);
+    run_until_closed(platform,|event,platform|{
+        match event {
+            Event::Seat{ time: _, id: _, event: SeatEvent::Cursor(CursorEvent::Entered{..}) }=>{
+                platform.requests(vec![Request::Seat {
+                    token: None,
+                    request: SeatRequest::Cursor(CursorRequest::ChangeImage(CursorImage::Hidden))
+                }]);
+            }
+            Event::Seat{ time: _, id: _, event: SeatEvent::Cursor(CursorEvent::Left{..}) }=>{
+                platform.requests(vec![Request::Seat {
+                    token: None,
+                    request: SeatRequest::Cursor(CursorRequest::ChangeImage(CursorImage::Default))
+                }]);
+            }
+            _=>{}
+        }
+    });
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn virtual_event_source() {
+    use crate::VirtualPlatform;
+
+    let platform = Platform::from(VirtualPlatform::with_default_devices().unwrap());
+    run_until_closed(platform,|event,platform|{
+        // Close every surface as soon as it is created, the loop ends when all of them are gone.
+        if let Event::Surface{ time: _, id, event: SurfaceEvent::Added(_) } = event {
+            platform.requests(vec![Request::Surface{token: None, request: SurfaceRequest::Destroy(*id)}]);
+        }
+    });
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn virtual_platform() {
+    use crate::VirtualPlatform;
 
-            platform.request(initial_requests);
+    let mut platform = Platform::from(VirtualPlatform::with_default_devices().unwrap());
+
+    let events = platform.events();
+    let output_id = events.iter().find_map(|event|{
+        match event {
+            Event::Output{time: _, id, event: OutputEvent::Added(_)}=>Some(*id),
+            _=>None
+        }
+    }).unwrap();
+    let seat_id = events.iter().find_map(|event|{
+        match event {
+            Event::Seat{time: _, id, event: SeatEvent::Added{..}}=>Some(*id),
+            _=>None
+        }
+    }).unwrap();
+
+    platform.requests(vec![Request::Surface{token: None, request: SurfaceRequest::Create(Some(output_id))}]);
+    let surface_id = platform.events().into_iter().find_map(|event|{
+        match event {
+            Event::Surface{time: _, id, event: SurfaceEvent::Added(_)}=>Some(id),
+            _=>None
         }
+    }).unwrap();
+
+    let position = Position2D::from((10,20));
+    platform.as_virtual_mut().unwrap().inject_seat_event(seat_id,SeatEvent::Cursor(CursorEvent::Entered{surface_id,position}));
+    match platform.events().as_slice() {
+        [Event::Seat{time: _, id, event: SeatEvent::Cursor(CursorEvent::Entered{..})}]=>assert_eq!(*id,seat_id),
+        events=>panic!("Unexpected events: {:#?}",events)
     }
 
-    use std::os::unix::io::AsRawFd;
-    let mut event_loop = calloop::EventLoop::try_new().unwrap();
-    let interest = calloop::Interest {
-        readable: true,
-        writable: false,
-    };
-    event_loop.handle().insert_source(calloop::generic::Generic::new(platform.as_raw_fd(),interest,calloop::Mode::Edge),move|_event,_metadata,_data|Ok(calloop::PostAction::Continue)).unwrap();
+    platform.requests(vec![Request::from(SurfaceRequest::Destroy(surface_id)).with_token(1)]);
+    match platform.events().as_slice() {
+        [
+            Event::Surface{time: _, id, event: SurfaceEvent::Removed},
+            Event::Request{time: _, token, event: RequestEvent::Completed}
+        ]=>{
+            assert_eq!(*id,surface_id);
+            assert_eq!(*token,RequestToken::from(1));
+        }
+        events=>panic!("Unexpected events: {:#?}",events)
+    }
 
-    let mut surfaces = HashMap::new();
-    'main_loop: loop {
-        match event_loop.dispatch(None,&mut ()){
-            Ok(_)=>(),
-            Err(_)=>()
-        }
-        let events = platform.events();
-        println!("{:#?}", events);
-        //if !events.is_empty() {println!("{:#?}", events);}
-
-        for event in events {
-            match event {
-                Event::Surface{ time: _, id, event: SurfaceEvent::Added(_) }=>{
-                    surfaces.insert(id,());
-                }
-                Event::Surface{ time: _, id, event: SurfaceEvent::Removed }=>{
-                    surfaces.remove(&id);
-                    if surfaces.is_empty() {break 'main_loop;}
+    platform.requests(vec![Request::from(SurfaceRequest::Destroy(surface_id)).with_token(2)]);
+    match platform.events().as_slice() {
+        [Event::Request{time: _, token: _, event: RequestEvent::Failed(error)}]=>{
+            assert_eq!(*error,RequestError::Surface(SurfaceError::UnknownSurface));
+        }
+        events=>panic!("Unexpected events: {:#?}",events)
+    }
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn builder_backend_selection() {
+    let platform = Platform::builder().env_override(false).backend(BackendKind::Virtual).build();
+    assert!(platform.is_ok());
+
+    #[cfg(not(feature = "libinput_platform"))]
+    match Platform::builder().env_override(false).backend(BackendKind::Libinput).build() {
+        Err(PlatformBuildError::NoBackendAvailable(failures))=>{
+            assert_eq!(failures.len(),1);
+            assert_eq!(failures[0].backend,BackendKind::Libinput);
+        }
+        _=>panic!("The libinput backend is not expected to be available")
+    }
+
+    assert_eq!("x11".parse::<BackendKind>(),Ok(BackendKind::Xcb));
+    assert!("unknown".parse::<BackendKind>().is_err());
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn async_platform_stream() {
+    use crate::{AsyncPlatform,VirtualPlatform};
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_io().build().unwrap();
+    runtime.block_on(async {
+        let mut platform = AsyncPlatform::new(VirtualPlatform::new().unwrap().into()).unwrap();
+        let sink = platform.sink();
+        sink.send(vec![Request::from(SurfaceRequest::Create(None)).with_token(1u64)]).await.unwrap();
+
+        loop {
+            match platform.next_event().await {
+                Some(Event::Surface{id,event: SurfaceEvent::Added(_),..})=>{
+                    sink.send(vec![SurfaceRequest::Destroy(id).into()]).await.unwrap();
                 }
-                _=>{}
+                Some(Event::Request{token,event: RequestEvent::Completed,..})=>assert_eq!(Into::<u64>::into(token),1),
+                Some(Event::Surface{event: SurfaceEvent::Removed,..})=>break,
+                Some(_)=>{}
+                None=>panic!("The event stream ended early")
             }
         }
+    });
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn platform_handle() {
+    use std::os::unix::io::AsRawFd;
+    use crate::VirtualPlatform;
+
+    let mut platform = Platform::from(VirtualPlatform::new().unwrap());
+    platform.events();
+
+    let handle = platform.handle();
+    std::thread::spawn(move ||{
+        handle.requests(vec![SurfaceRequest::Create(None).into()]).unwrap();
+    }).join().unwrap();
+
+    let mut fd = libc::pollfd {fd: platform.as_raw_fd(), events: libc::POLLIN, revents: 0};
+    assert_eq!(unsafe{libc::poll(&mut fd,1,1000)},1);
+    assert!(platform.wait_events(Some(std::time::Duration::from_secs(1))).iter().any(|event|matches!(event,Event::Surface{event: SurfaceEvent::Added(_),..})));
+
+    let handle = platform.handle();
+    drop(platform);
+    assert!(handle.requests(vec![SurfaceRequest::Create(None).into()]).is_err());
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn wait_events_timeout() {
+    use std::time::{Duration,Instant};
+    use crate::VirtualPlatform;
 
+    let mut platform = Platform::from(VirtualPlatform::new().unwrap());
+    let start = Instant::now();
+    assert!(platform.wait_events(Some(Duration::from_millis(50))).is_empty());
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    platform.requests(vec![SurfaceRequest::Create(None).into()]);
+    match platform.wait_events(None).as_slice() {
+        [Event::Surface{event: SurfaceEvent::Added(_),..}]=>{}
+        events=>panic!("Unexpected events: {:?}",events)
     }
 }
 
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn requests_wake_platform() {
+    use std::os::unix::io::AsRawFd;
+    use crate::VirtualPlatform;
+
+    let mut backend = VirtualPlatform::new().unwrap();
+    backend.disable_self_waking();
+    let mut platform = Platform::from(backend);
+    platform.events();
 
+    platform.requests(vec![SurfaceRequest::Create(None).into()]);
+    let mut fd = libc::pollfd {fd: platform.as_raw_fd(), events: libc::POLLIN, revents: 0};
+    assert_eq!(unsafe{libc::poll(&mut fd,1,1000)},1);
+    assert!(platform.events().iter().any(|event|matches!(event,Event::Surface{event: SurfaceEvent::Added(_),..})));
+}
+
+#[cfg(feature = "virtual_platform")]
 #[test]
-fn cursor_test() {
-    use crate::Platform;
-    use crate::definitions::*;
-
-    let mut platform = Platform::new(vec![Box::new(RawContext)]);
-
-    match platform.platform_type(){
-        PlatformType::Compositor=>{
-            platform.request(vec![Request::Surface{request: SurfaceRequest::Create(None)}]);
-        }
-        PlatformType::Direct=>{
-            let initial_requests: Vec<_> = platform.events().into_iter().filter_map(|event|{
-                match event {
-                    Event::Output{time: _, id, event: OutputEvent::Added(_)}=>{
-                        Some(Request::Surface{request: SurfaceRequest::Create(Some(id))})
-                    },
-                    _=>None
-                }
-            }).collect();
+fn monotonic_timestamps() {
+    use crate::VirtualPlatform;
+
+    let before = EventTime::now();
+    let mut platform = Platform::from(VirtualPlatform::with_default_devices().unwrap());
+    let times: Vec<EventTime> = platform.events().into_iter().map(|event|match event {
+        Event::Seat{time,..} | Event::Output{time,..} | Event::Surface{time,..} | Event::Request{time,..}=>time
+    }).collect();
+
+    assert!(!times.is_empty());
+    assert!(times.windows(2).all(|pair|pair[0] <= pair[1]));
+    assert!(times.iter().all(|time|*time >= before && time.raw.is_none()));
+}
 
-            platform.request(initial_requests);
+#[cfg(feature = "serde")]
+#[test]
+fn serde_roundtrip() {
+    let event = Event::Surface{
+        time: EventTime{monotonic: 42, raw: Some(7)},
+        id: SurfaceId::from(3usize),
+        event: SurfaceEvent::Added(SurfaceInfo{
+            position: Position2D::from((0,0)),
+            size: Size2D::from((400,400)),
+            surface: Surface::Raw(raw_window_handle::RawWindowHandle::Xcb(raw_window_handle::unix::XcbHandle::empty()))
+        })
+    };
+    let json = serde_json::to_string(&event).unwrap();
+    match serde_json::from_str::<Event>(&json).unwrap() {
+        Event::Surface{time,id,event: SurfaceEvent::Added(info)}=>{
+            assert_eq!(time,EventTime{monotonic: 42, raw: Some(7)});
+            assert_eq!(id,SurfaceId::from(3usize));
+            assert_eq!(info.surface,Surface::Detached);
         }
+        event=>panic!("Unexpected event: {:?}",event)
+    }
+
+    let request = Request::from(SeatRequest::Cursor(CursorRequest::ChangeMode(CursorMode::Relative))).with_token(5u64);
+    let json = serde_json::to_string(&request).unwrap();
+    let request: Request = serde_json::from_str(&json).unwrap();
+    assert_eq!(request.token(),Some(RequestToken::from(5u64)));
+
+    let mode = SurfaceMode::MAXIMIZED | SurfaceMode::TILING_LEFT;
+    assert_eq!(serde_json::from_str::<SurfaceMode>(&serde_json::to_string(&mode).unwrap()).unwrap(),mode);
+}
+
+#[cfg(all(feature = "virtual_platform", feature = "record_replay"))]
+#[test]
+fn record_and_replay() {
+    use std::io::Write;
+    use std::sync::{Arc,Mutex};
+    use std::time::Duration;
+    use crate::{VirtualPlatform,Recorder,RecordEntry,ReplayPlatform,ReplayMode};
+
+    #[derive(Clone,Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+    impl Write for SharedBuffer {
+        fn write(&mut self, data: &[u8])->std::io::Result<usize> {self.0.lock().unwrap().write(data)}
+        fn flush(&mut self)->std::io::Result<()> {Ok(())}
+    }
+
+    let buffer = SharedBuffer::default();
+    let mut recorder = Recorder::new(VirtualPlatform::with_default_devices().unwrap(),buffer.clone());
+    let mut recorded = recorder.events();
+    recorder.requests(vec![SurfaceRequest::Create(None).into()]);
+    std::thread::sleep(Duration::from_millis(20));
+    recorded.append(&mut recorder.events());
+
+    let recording = buffer.0.lock().unwrap().clone();
+    let entries = RecordEntry::read_all(recording.as_slice()).unwrap();
+    assert!(matches!(entries.first(),Some(RecordEntry::PlatformType(PlatformType::Compositor))));
+
+    let mut replay = ReplayPlatform::from_reader(recording.as_slice(),ReplayMode::AsFastAsPossible).unwrap();
+    assert_eq!(replay.recorded_requests(),1);
+    assert_eq!(replay.events().len(),recorded.len());
+    assert_eq!(replay.remaining_events(),0);
+
+    let mut platform = Platform::from(ReplayPlatform::from_reader(recording.as_slice(),ReplayMode::Realtime).unwrap());
+    let mut replayed = Vec::new();
+    while replayed.len() < recorded.len() {
+        replayed.append(&mut platform.wait_events(Some(Duration::from_secs(1))));
     }
+    assert_eq!(replayed.iter().map(Event::time).collect::<Vec<_>>(),recorded.iter().map(Event::time).collect::<Vec<_>>());
+}
+
+#[cfg(all(feature = "virtual_platform", feature = "state_tracker"))]
+#[test]
+fn state_tracker() {
+    use crate::VirtualPlatform;
+
+    let mut platform = Platform::from(VirtualPlatform::with_default_devices().unwrap());
+    platform.requests(vec![SurfaceRequest::Create(None).into()]);
+    platform.events();
+
+    assert_eq!(platform.state().outputs().count(),1);
+    let (seat_id,seat) = platform.state().seats().next().unwrap();
+    assert_eq!(seat.name,"seat-0");
+    assert!(seat.keyboard.is_some() && seat.cursor.is_some());
+    let (surface_id,surface) = platform.state().surfaces().next().unwrap();
+    assert_eq!(surface.size,Size2D::from((400,400)));
+
+    let virtual_platform = platform.as_virtual_mut().unwrap();
+    virtual_platform.inject_surface_event(surface_id,SurfaceEvent::Resized(Size2D::from((800,600))));
+    virtual_platform.inject_surface_event(surface_id,SurfaceEvent::Focused(true));
+    virtual_platform.inject_seat_event(seat_id,CursorEvent::Entered{surface_id,position: Position2D::from((10,20))}.into());
+    virtual_platform.inject_seat_event(seat_id,CursorEvent::AbsoluteMovement{position: Position2D::from((30,40))}.into());
+    platform.events();
+
+    assert_eq!(platform.state().surface(surface_id).unwrap().size,Size2D::from((800,600)));
+    let seat = platform.state().seat(seat_id).unwrap();
+    assert_eq!(seat.focused_surface,Some(surface_id));
+    assert_eq!(seat.cursor_surface,Some(surface_id));
+    assert_eq!(seat.cursor_position,Some(Position2D::from((30,40))));
+
+    platform.requests(vec![SurfaceRequest::Destroy(surface_id).into()]);
+    platform.events();
+    assert!(platform.state().surface(surface_id).is_none());
+    assert_eq!(platform.state().seat(seat_id).unwrap().cursor_surface,None);
+}
+
+#[cfg(all(feature = "virtual_platform", feature = "state_tracker"))]
+#[test]
+fn keyboard_state() {
+    use crate::VirtualPlatform;
 
+    let mut platform = Platform::from(VirtualPlatform::with_default_devices().unwrap());
+    platform.requests(vec![SurfaceRequest::Create(None).into()]);
+    platform.events();
+    let (seat_id,_) = platform.state().seats().next().unwrap();
+    let (surface_id,_) = platform.state().surfaces().next().unwrap();
+
+    let key = |key: Key, state: State| -> SeatEvent {
+        KeyboardEvent::Key{code: 0, key: Some(key), logical_key: LogicalKey::Unidentified, location: Location::Standard, state, serial: 0, time: EventTime::now(), repeat: false, text: None}.into()
+    };
+    let virtual_platform = platform.as_virtual_mut().unwrap();
+    virtual_platform.inject_surface_event(surface_id,SurfaceEvent::Focused(true));
+    for event in [
+        key(Key::CapsLock,State::Down),
+        key(Key::CapsLock,State::Up),
+        key(Key::ControlLeft,State::Down),
+        key(Key::ShiftRight,State::Down),
+        key(Key::ShiftRight,State::Down),
+        key(Key::KeyA,State::Down),
+    ] {
+        virtual_platform.inject_seat_event(seat_id,event);
+    }
+    virtual_platform.inject_seat_event(seat_id,CursorEvent::Button{code: 272, key: Some(Button::Left), state: State::Down}.into());
+    platform.events();
+
+    let keyboard_state = platform.state().keyboard_state(seat_id).unwrap();
+    assert!(keyboard_state.is_pressed(Key::KeyA));
+    assert_eq!(keyboard_state.modifiers,Modifiers::CAPS_LOCK | Modifiers::CONTROL | Modifiers::SHIFT);
+    assert!(platform.state().seat(seat_id).unwrap().pressed_buttons.contains(&Button::Left));
+
+    platform.as_virtual_mut().unwrap().inject_surface_event(surface_id,SurfaceEvent::Focused(false));
+    platform.events();
+    let keyboard_state = platform.state().keyboard_state(seat_id).unwrap();
+    assert!(keyboard_state.pressed_keys.is_empty());
+    assert_eq!(keyboard_state.modifiers,Modifiers::CAPS_LOCK);
+    assert!(platform.state().seat(seat_id).unwrap().pressed_buttons.is_empty());
+
+    // The reported state wins over the one guessed from the keys, like a Shift held before the focus.
+    let event = KeyboardEvent::Modifiers{depressed: Modifiers::SHIFT, latched: Modifiers::empty(), locked: Modifiers::NUM_LOCK, group: 1};
+    platform.as_virtual_mut().unwrap().inject_seat_event(seat_id,event.clone().into());
+    platform.events();
+    let keyboard_state = platform.state().keyboard_state(seat_id).unwrap();
+    assert_eq!(keyboard_state.modifiers,Modifiers::SHIFT | Modifiers::NUM_LOCK);
+    assert_eq!(keyboard_state.group,1);
+    assert!(platform.synthetic_events().iter().any(|synthetic|matches!(synthetic,Event::Seat{event: SeatEvent::Keyboard(modifiers),..} if *modifiers == event)));
+
+    platform.as_virtual_mut().unwrap().remove_keyboard(seat_id);
+    platform.events();
+    assert!(platform.state().keyboard_state(seat_id).is_none());
+}
+
+#[cfg(all(feature = "virtual_platform", feature = "state_tracker"))]
+#[test]
+fn synthetic_state_events() {
+    use crate::VirtualPlatform;
+
+    let mut platform = Platform::from(VirtualPlatform::with_default_devices().unwrap());
+    platform.requests(vec![SurfaceRequest::Create(None).into()]);
+    let startup: Vec<Event> = platform.events();
+
+    let without_time = |event: &Event|->Event {
+        let time = EventTime::from_monotonic(0);
+        match event.clone() {
+            Event::Seat{id,event,..}=>Event::Seat{time,id,event},
+            Event::Output{id,event,..}=>Event::Output{time,id,event},
+            Event::Surface{id,event,..}=>Event::Surface{time,id,event},
+            Event::Request{token,event,..}=>Event::Request{time,token,event},
+        }
+    };
+    let startup: Vec<Event> = startup.iter().map(without_time).collect();
+    let synthetic: Vec<Event> = platform.synthetic_events().iter().map(without_time).collect();
+    assert_eq!(synthetic.len(),startup.len());
+    assert!(synthetic.iter().all(|event|startup.contains(event)));
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn event_pipeline() {
+    use crate::{VirtualPlatform,ResizeCoalescer,Deduplicator,Filter};
+
+    let mut platform = Platform::from(VirtualPlatform::new().unwrap());
+    platform.pipeline_mut().clear();
+    platform.pipeline_mut().push(ResizeCoalescer::new());
+    platform.pipeline_mut().push(Deduplicator::new());
+    platform.pipeline_mut().push(Filter::new(|event: &Event|!matches!(event,Event::Surface{event: SurfaceEvent::Focused(_),..})));
+    platform.pipeline_mut().push(|mut events: Vec<Event>|{
+        events.push(Event::Request{time: EventTime::from_monotonic(0), token: 9u64.into(), event: RequestEvent::Completed});
+        events
+    });
+
+    platform.requests(vec![SurfaceRequest::Create(None).into()]);
+    let id = match platform.events().as_slice() {
+        [Event::Surface{id,event: SurfaceEvent::Added(_),..},Event::Request{..}]=>*id,
+        events=>panic!("Unexpected events: {:?}",events)
+    };
+
+    let virtual_platform = platform.as_virtual_mut().unwrap();
+    virtual_platform.inject_surface_event(id,SurfaceEvent::Resized(Size2D::from((500,500))));
+    virtual_platform.inject_surface_event(id,SurfaceEvent::Focused(true));
+    virtual_platform.inject_surface_event(id,SurfaceEvent::Resized(Size2D::from((600,600))));
+    match platform.events().as_slice() {
+        [Event::Surface{event: SurfaceEvent::Resized(size),..},Event::Request{..}]=>assert_eq!(*size,Size2D::from((600,600))),
+        events=>panic!("Unexpected events: {:?}",events)
+    }
+
+    platform.as_virtual_mut().unwrap().inject_surface_event(id,SurfaceEvent::Resized(Size2D::from((600,600))));
+    assert_eq!(platform.events().len(),1);
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn motion_coalescing() {
+    use crate::{VirtualPlatform,MotionCoalescer};
+
+    let mut platform = Platform::from(VirtualPlatform::new().unwrap());
+    let (coalescer,history) = MotionCoalescer::with_history();
+    platform.pipeline_mut().push(coalescer);
+
+    let virtual_platform = platform.as_virtual_mut().unwrap();
+    let seat_id = virtual_platform.add_seat(String::from("seat-0"));
+    for x in 0..10 {
+        virtual_platform.inject_seat_event(seat_id,CursorEvent::AbsoluteMovement{position: Position2D::from((x,0))}.into());
+    }
+    virtual_platform.inject_seat_event(seat_id,CursorEvent::Button{code: 272, key: Some(Button::Left), state: State::Down}.into());
+    for _ in 0..4 {
+        virtual_platform.inject_seat_event(seat_id,CursorEvent::RelativeMovement{offset: Offset2D::from((1.0,-0.5))}.into());
+    }
+
+    let events: Vec<SeatEvent> = platform.events().into_iter().filter_map(|event|match event {
+        Event::Seat{event,..}=>Some(event),
+        _=>None
+    }).collect();
+    assert_eq!(events,vec![
+        SeatEvent::Added{name: String::from("seat-0")},
+        CursorEvent::AbsoluteMovement{position: Position2D::from((9,0))}.into(),
+        CursorEvent::Button{code: 272, key: Some(Button::Left), state: State::Down}.into(),
+        CursorEvent::RelativeMovement{offset: Offset2D::from((4.0,-2.0))}.into(),
+    ]);
+    assert_eq!(history.take().len(),14);
+    assert!(history.is_empty());
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn gesture_recognition() {
+    use std::time::Duration;
+    use crate::{VirtualPlatform,GestureRecognizer,GestureConfig};
+
+    let config = GestureConfig{long_press_time: Duration::from_millis(50),..GestureConfig::default()};
+    let mut platform = Platform::from(VirtualPlatform::new().unwrap());
+    platform.pipeline_mut().push(GestureRecognizer::new(config));
+    let gestures = |events: Vec<Event>|->Vec<GestureEvent> {
+        events.into_iter().filter_map(|event|match event {
+            Event::Seat{event: SeatEvent::Gesture(gesture),..}=>Some(gesture),
+            _=>None
+        }).collect()
+    };
+
+    let virtual_platform = platform.as_virtual_mut().unwrap();
+    let seat_id = virtual_platform.add_seat(String::from("seat-0"));
+    let surface_id = SurfaceId::from(7usize);
+    let button = |state: State|->SeatEvent {CursorEvent::Button{code: 272, key: Some(Button::Left), state}.into()};
+    let movement = |x: i32, y: i32|->SeatEvent {CursorEvent::AbsoluteMovement{position: Position2D::from((x,y))}.into()};
+
+    virtual_platform.inject_seat_event(seat_id,CursorEvent::Entered{surface_id,position: Position2D::from((10,10))}.into());
+    for _ in 0..2 {
+        virtual_platform.inject_seat_event(seat_id,button(State::Down));
+        virtual_platform.inject_seat_event(seat_id,button(State::Up));
+    }
+    virtual_platform.inject_seat_event(seat_id,button(State::Down));
+    virtual_platform.inject_seat_event(seat_id,movement(30,10));
+    virtual_platform.inject_seat_event(seat_id,movement(40,10));
+    virtual_platform.inject_seat_event(seat_id,button(State::Up));
+
+    let position = |x: i32, y: i32|Position2D::from((x,y));
+    let surface_id = Some(surface_id);
+    let button = Button::Left;
+    assert_eq!(gestures(platform.events()),vec![
+        GestureEvent::Click{button,count: 1,surface_id,position: position(10,10)},
+        GestureEvent::Click{button,count: 2,surface_id,position: position(10,10)},
+        GestureEvent::DragStarted{button,surface_id,position: position(10,10)},
+        GestureEvent::DragMoved{button,surface_id,position: position(40,10)},
+        GestureEvent::DragEnded{button,surface_id,position: position(40,10)},
+    ]);
+
+    platform.as_virtual_mut().unwrap().inject_seat_event(seat_id,CursorEvent::Button{code: 272, key: Some(button), state: State::Down}.into());
+    assert!(gestures(platform.events()).is_empty());
+    assert_eq!(gestures(platform.wait_events(Some(Duration::from_secs(1)))),vec![
+        GestureEvent::LongPress{button,surface_id,position: position(40,10)}
+    ]);
+    platform.as_virtual_mut().unwrap().inject_seat_event(seat_id,CursorEvent::Button{code: 272, key: Some(button), state: State::Up}.into());
+    assert!(gestures(platform.events()).is_empty());
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn software_key_repeat() {
+    use std::time::Duration;
+    use crate::VirtualPlatform;
+
+    let mut platform = Platform::from(VirtualPlatform::with_default_devices().unwrap());
+    platform.set_software_repeat(true);
+    platform.events();
+    platform.requests(vec![Request::from(SeatRequest::Keyboard(KeyboardRequest::SetAutoRepeat{rate: 100, delay: 20})).with_token(1u64)]);
+    let events = platform.events();
+    assert!(events.iter().any(|event|matches!(event,Event::Seat{event: SeatEvent::Keyboard(KeyboardEvent::AutoRepeat{rate: 100,delay: 20}),..})));
+    assert!(events.iter().any(|event|matches!(event,Event::Request{event: RequestEvent::Completed,..})));
+
+    let seat_id = SeatId::from(1usize);
+    let key = |state: State, repeat: bool|->SeatEvent {
+        KeyboardEvent::Key{code: 30, key: Some(Key::KeyA), logical_key: LogicalKey::Character(String::from("a")), location: Location::Standard, state, serial: 0, time: EventTime::now(), repeat, text: None}.into()
+    };
+    let repeats = |events: Vec<Event>|events.into_iter().filter(|event|matches!(event,Event::Seat{event: SeatEvent::Keyboard(KeyboardEvent::Key{repeat: true,..}),..})).count();
+
+    platform.as_virtual_mut().unwrap().inject_seat_event(seat_id,key(State::Down,false));
+    platform.as_virtual_mut().unwrap().inject_seat_event(seat_id,key(State::Down,true));
+    assert_eq!(repeats(platform.events()),0);
+
+    let mut count = 0;
+    while count < 3 {
+        count += repeats(platform.wait_events(Some(Duration::from_secs(1))));
+    }
+
+    platform.as_virtual_mut().unwrap().inject_seat_event(seat_id,key(State::Up,false));
+    platform.events();
+    assert!(platform.wait_events(Some(Duration::from_millis(50))).is_empty());
+}
+
+#[cfg(all(target_os = "linux",feature = "linux_platform"))]
+#[test]
+fn keysym_text_and_compose() {
+    use crate::backends::linux::{XkbKeyboard,XkbCompose,keysym_to_logical_key};
+    use xkbcommon::xkb;
+    use xkbcommon::xkb::compose::Status;
+
+    let mut keyboard = XkbKeyboard::from_descriptor(&KeymapDescriptor::default()).unwrap();
+    assert_eq!(keyboard.key_text(30),Some(String::from("a")));
+    assert_eq!(keyboard.key_text(28),None);
+    keyboard.update_key(42,State::Down);
+    assert_eq!(keyboard.key_text(30),Some(String::from("A")));
+    assert_eq!(keyboard.key_text(42),None);
+    assert_eq!(keysym_to_logical_key(0xe9),keyboard_types::Key::Character(String::from("é")));
+    assert_eq!(keysym_to_logical_key(0x10020ac),keyboard_types::Key::Character(String::from("€")));
+
+    let contents = r#"
+<dead_acute> <e> : "é" eacute
+<Multi_key> <o> <c> : "©" copyright
+<Multi_key> <minus> <minus> <period> : "\342\200\223"
+"#;
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    let table = xkb::compose::Table::new_from_buffer(&context,contents,std::ffi::OsStr::new("C"),xkb::compose::FORMAT_TEXT_V1,xkb::compose::COMPILE_NO_FLAGS).unwrap();
+    let mut compose = XkbCompose::from_table(&table);
+    let keysym = |name|xkb::keysym_from_name(name,xkb::KEYSYM_NO_FLAGS);
+    assert_eq!(compose.feed(keysym("dead_acute")),Status::Composing);
+    assert_eq!(compose.feed(keysym("Shift_L")),Status::Nothing);
+    assert_eq!(compose.feed(keysym("e")),Status::Composed);
+    assert_eq!(compose.text(),Some(String::from("é")));
+    assert_eq!(compose.feed(keysym("e")),Status::Nothing);
+    assert_eq!(compose.feed(keysym("Multi_key")),Status::Composing);
+    assert_eq!(compose.feed(keysym("o")),Status::Composing);
+    assert_eq!(compose.feed(keysym("x")),Status::Cancelled);
+    assert_eq!(compose.feed(keysym("Multi_key")),Status::Composing);
+    assert_eq!(compose.feed(keysym("minus")),Status::Composing);
+    assert_eq!(compose.feed(keysym("minus")),Status::Composing);
+    assert_eq!(compose.feed(keysym("period")),Status::Composed);
+    assert_eq!(compose.text(),Some(String::from("–")));
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn input_method_requests() {
+    use crate::VirtualPlatform;
+
+    let mut platform = Platform::from(VirtualPlatform::with_default_devices().unwrap());
+    platform.events();
+    platform.requests(vec![Request::from(SurfaceRequest::Create(None))]);
+    let surface_id = platform.events().into_iter().find_map(|event|{
+        match event {
+            Event::Surface{time: _, id, event: SurfaceEvent::Added(_)}=>Some(id),
+            _=>None
+        }
+    }).unwrap();
+
+    let area = Rectangle{position: Position2D::from((10,20)), size: Size2D::from((2,16))};
+    platform.requests(vec![
+        Request::from(SurfaceRequest::SetImeEnabled{id: surface_id, enabled: true}).with_token(1u64),
+        Request::from(SurfaceRequest::SetImeCursorArea{id: surface_id, area: area.clone()}).with_token(2u64),
+        Request::from(SurfaceRequest::SetImeEnabled{id: SurfaceId::from(1000usize), enabled: true}).with_token(3u64),
+    ]);
+    let outcomes: Vec<(RequestToken,RequestEvent)> = platform.events().into_iter().filter_map(|event|{
+        match event {
+            Event::Request{time: _, token, event}=>Some((token,event)),
+            _=>None
+        }
+    }).collect();
+    assert_eq!(outcomes,vec![
+        (RequestToken::from(1),RequestEvent::Completed),
+        (RequestToken::from(2),RequestEvent::Completed),
+        (RequestToken::from(3),RequestEvent::Failed(RequestError::Ime(ImeError::UnknownSurface))),
+    ]);
+    assert_eq!(platform.as_virtual_mut().unwrap().ime_state(surface_id),Some((true,Some(area))));
+
+    let seat_id = SeatId::from(1usize);
+    platform.as_virtual_mut().unwrap().inject_seat_event(seat_id,KeyboardEvent::Preedit{text: String::from("にほ"), cursor: Some((6,6))}.into());
+    platform.as_virtual_mut().unwrap().inject_seat_event(seat_id,KeyboardEvent::Commit{text: String::from("日本")}.into());
+    match platform.events().as_slice() {
+        [
+            Event::Seat{event: SeatEvent::Keyboard(KeyboardEvent::Preedit{cursor: Some((6,6)),..}),..},
+            Event::Seat{event: SeatEvent::Keyboard(KeyboardEvent::Commit{text}),..}
+        ]=>assert_eq!(text,"日本"),
+        events=>panic!("Unexpected events: {:#?}",events)
+    }
+}
+
+#[cfg(all(target_os = "linux",feature = "linux_platform"))]
+#[test]
+fn evdev_code_table() {
+    use crate::backends::linux::keycodes::{evdev_to_w3c_code,w3c_code_to_evdev};
+
+    let mut mapped = Vec::new();
+    for code in 0..0x300 {
+        if let Some(key) = evdev_to_w3c_code(code) {
+            assert_eq!(w3c_code_to_evdev(key),Some(code),"{:?} does not map back to {}",key,code);
+            assert!(!mapped.contains(&key),"{:?} is mapped twice",key);
+            mapped.push(key);
+        }
+    }
+    assert_eq!(evdev_to_w3c_code(0),None);
+    assert_eq!(w3c_code_to_evdev(Key::Unidentified),None);
+
+    let expected = [
+        (1,Key::Escape),(14,Key::Backspace),(15,Key::Tab),(28,Key::Enter),(57,Key::Space),
+        (30,Key::KeyA),(44,Key::KeyZ),(2,Key::Digit1),(11,Key::Digit0),
+        (12,Key::Minus),(26,Key::BracketLeft),(39,Key::Semicolon),(40,Key::Quote),(41,Key::Backquote),(52,Key::Period),
+        (29,Key::ControlLeft),(97,Key::ControlRight),(42,Key::ShiftLeft),(54,Key::ShiftRight),
+        (56,Key::AltLeft),(100,Key::AltRight),(125,Key::MetaLeft),(126,Key::MetaRight),(58,Key::CapsLock),
+        (59,Key::F1),(68,Key::F10),(87,Key::F11),(88,Key::F12),(183,Key::F13),(194,Key::F24),
+        (103,Key::ArrowUp),(105,Key::ArrowLeft),(106,Key::ArrowRight),(108,Key::ArrowDown),
+        (102,Key::Home),(107,Key::End),(104,Key::PageUp),(109,Key::PageDown),(110,Key::Insert),(111,Key::Delete),
+        (69,Key::NumLock),(82,Key::Numpad0),(73,Key::Numpad9),(96,Key::NumpadEnter),(98,Key::NumpadDivide),
+        (55,Key::NumpadMultiply),(74,Key::NumpadSubtract),(78,Key::NumpadAdd),(83,Key::NumpadDecimal),
+        (113,Key::AudioVolumeMute),(114,Key::AudioVolumeDown),(115,Key::AudioVolumeUp),
+        (164,Key::MediaPlayPause),(163,Key::MediaTrackNext),(165,Key::MediaTrackPrevious),(166,Key::MediaStop),
+        (158,Key::BrowserBack),(159,Key::BrowserForward),(172,Key::BrowserHome),(217,Key::BrowserSearch),
+        (86,Key::IntlBackslash),(89,Key::IntlRo),(124,Key::IntlYen),(127,Key::ContextMenu),(99,Key::PrintScreen),
+    ];
+    for (code,key) in expected.iter() {
+        assert_eq!(evdev_to_w3c_code(*code),Some(*key));
+    }
+    assert_eq!(mapped.len(),165);
+}
+
+#[cfg(all(target_os = "linux",feature = "linux_platform"))]
+#[test]
+fn logical_keys() {
+    use crate::backends::linux::{keysym_to_logical_key,w3c_code_location};
+
+    assert_eq!(keysym_to_logical_key(0x61),LogicalKey::Character(String::from("a")));
+    assert_eq!(keysym_to_logical_key(0xe9),LogicalKey::Character(String::from("é")));
+    assert_eq!(keysym_to_logical_key(0xffb1),LogicalKey::Character(String::from("1")));
+    assert_eq!(keysym_to_logical_key(0xff51),LogicalKey::ArrowLeft);
+    assert_eq!(keysym_to_logical_key(0xff96),LogicalKey::ArrowLeft);
+    assert_eq!(keysym_to_logical_key(0xff0d),LogicalKey::Enter);
+    assert_eq!(keysym_to_logical_key(0xffe2),LogicalKey::Shift);
+    assert_eq!(keysym_to_logical_key(0xfe03),LogicalKey::AltGraph);
+    assert_eq!(keysym_to_logical_key(0xffca),LogicalKey::F13);
+    assert_eq!(keysym_to_logical_key(0xffd5),LogicalKey::F24);
+    assert_eq!(keysym_to_logical_key(0xfe51),LogicalKey::Dead);
+    assert_eq!(keysym_to_logical_key(0x1008ff13),LogicalKey::AudioVolumeUp);
+    assert_eq!(keysym_to_logical_key(0xfd01),LogicalKey::Unidentified);
+
+    assert_eq!(w3c_code_location(Key::ShiftLeft),Location::Left);
+    assert_eq!(w3c_code_location(Key::ControlRight),Location::Right);
+    assert_eq!(w3c_code_location(Key::NumpadEnter),Location::Numpad);
+    assert_eq!(w3c_code_location(Key::KeyA),Location::Standard);
+}
+
+#[cfg(all(target_os = "linux",feature="linux_platform"))]
+#[test]
+fn keymap_descriptor_and_registry() {
+    use crate::XkbRegistry;
+
+    let keymap = KeymapDescriptor::from_layouts("us,it").with_variants(",winkeys").with_options("grp:alt_shift_toggle");
+    assert_eq!(keymap.rules,"evdev");
+    assert_eq!(keymap.layouts,vec!["us","it"]);
+    assert_eq!(keymap.variants,vec!["","winkeys"]);
+    assert_eq!(keymap.options_string(),"grp:alt_shift_toggle");
+
+    let registry = XkbRegistry::parse(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE xkbConfigRegistry SYSTEM "xkb.dtd">
+<xkbConfigRegistry version="1.1">
+  <modelList>
+    <model><configItem><name>pc105</name><description>Generic 105-key PC</description><vendor>Generic</vendor></configItem></model>
+  </modelList>
+  <layoutList>
+    <layout>
+      <!-- Keyboard indicator for Italian layouts -->
+      <configItem><name>it</name><shortDescription>it</shortDescription><description>Italian</description></configItem>
+      <variantList>
+        <variant><configItem><name>winkeys</name><description>Italian (Windows)</description></configItem></variant>
+        <variant><configItem><name>mac</name><description>Italian (Macintosh &amp; co)</description></configItem></variant>
+      </variantList>
+    </layout>
+  </layoutList>
+  <optionList>
+    <group allowMultipleSelection="true">
+      <configItem><name>grp</name><description>Switching to another layout</description></configItem>
+      <option><configItem><name>grp:alt_shift_toggle</name><description>Alt+Shift</description></configItem></option>
+    </group>
+  </optionList>
+</xkbConfigRegistry>
+"#).unwrap();
+    assert_eq!(registry.models()[0].vendor.as_deref(),Some("Generic"));
+    let layout = registry.layout("it").unwrap();
+    assert_eq!(layout.short_description.as_deref(),Some("it"));
+    assert_eq!(layout.variants.iter().map(|variant|variant.name.as_str()).collect::<Vec<_>>(),vec!["winkeys","mac"]);
+    assert_eq!(layout.variants[1].description,"Italian (Macintosh & co)");
+    assert!(registry.option_groups()[0].multiple_selection);
+    assert_eq!(registry.option("grp:alt_shift_toggle").unwrap().description,"Alt+Shift");
+    assert!(XkbRegistry::parse("<xkbConfigRegistry><layoutList></modelList></xkbConfigRegistry>").is_err());
+}
+
+#[cfg(feature = "virtual_platform")]
+#[test]
+fn keymap_requests() {
+    use crate::VirtualPlatform;
+
+    let mut platform = Platform::from(VirtualPlatform::with_default_devices().unwrap());
+    platform.events();
+
+    let keymap = KeymapDescriptor::from_layouts("us,it").with_options("grp:alt_shift_toggle");
+    platform.requests(vec![
+        Request::from(SeatRequest::Keyboard(KeyboardRequest::ModifyLayout{keymap: keymap.clone()})),
+        Request::from(SeatRequest::Keyboard(KeyboardRequest::SwitchGroup{group: 1})),
+        Request::from(SeatRequest::Keyboard(KeyboardRequest::SwitchGroup{group: 2})).with_token(1u64),
+    ]);
+    match platform.events().as_slice() {
+        [
+            Event::Seat{event: SeatEvent::Keyboard(KeyboardEvent::LayoutModified{keymap: modified}),..},
+            Event::Seat{event: SeatEvent::Keyboard(KeyboardEvent::Modifiers{group: 1,..}),..},
+            Event::Request{event: RequestEvent::Failed(RequestError::KeyboardLayout(KeyboardLayoutError::InvalidGroup)),..}
+        ]=>assert_eq!(*modified,keymap),
+        events=>panic!("Unexpected events: {:#?}",events)
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "virtual_platform", feature = "state_tracker"))]
+#[test]
+fn keymap_export() {
+    use crate::{VirtualPlatform,KeymapFile};
+    use std::io::Read;
     use std::os::unix::io::AsRawFd;
-    let mut event_loop = calloop::EventLoop::try_new().unwrap();
-    let interest = calloop::Interest {
-        readable: true,
-        writable: false,
+
+    let mut platform = Platform::from(VirtualPlatform::with_default_devices().unwrap());
+    platform.events();
+    let (seat_id,_) = platform.state().seats().next().unwrap();
+    assert!(platform.state().keymap(seat_id).is_none());
+
+    let keymap = Keymap {
+        descriptor: KeymapDescriptor::default(),
+        text: String::from("xkb_keymap {\n\txkb_keycodes \"evdev\" { include \"evdev\" };\n};\n")
     };
-    event_loop.handle().insert_source(calloop::generic::Generic::new(platform.as_raw_fd(),interest,calloop::Mode::Edge),move|_event,_metadata,_data|Ok(calloop::PostAction::Continue)).unwrap();
+    platform.as_virtual_mut().unwrap().inject_seat_event(seat_id,KeyboardEvent::KeymapChanged{keymap: keymap.clone()}.into());
+    platform.events();
+    assert_eq!(platform.state().keymap(seat_id),Some(&keymap));
+    assert!(platform.synthetic_events().iter().any(|event|matches!(event,Event::Seat{event: SeatEvent::Keyboard(KeyboardEvent::KeymapChanged{keymap: synthetic}),..} if *synthetic == keymap)));
 
-    let mut surfaces = HashMap::new();
-    'main_loop: loop {
-        match event_loop.dispatch(None,&mut ()){
-            Ok(_)=>(),
-            Err(_)=>()
-        }
-        let events = platform.events();
-        println!("{:#?}", events);
-        //if !events.is_empty() {println!("{:#?}", events);}
-
-        for event in events {
-            match event {
-                Event::Surface{ time: _, id, event: SurfaceEvent::Added(_) }=>{
-                    surfaces.insert(id,());
+    let file = KeymapFile::new(platform.state().keymap(seat_id).unwrap()).unwrap();
+    assert_eq!(file.size(),keymap.text.len() + 1);
+    let seals = unsafe{libc::fcntl(file.as_raw_fd(),libc::F_GET_SEALS)};
+    assert_eq!(seals,libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE | libc::F_SEAL_SEAL);
+
+    let mut contents = Vec::new();
+    file.file().read_to_end(&mut contents).unwrap();
+    assert_eq!(contents.len(),file.size());
+    assert_eq!(&contents[..keymap.text.len()],keymap.text.as_bytes());
+    assert_eq!(contents.last(),Some(&0));
+    assert!(std::io::Write::write_all(&mut file.file(),b"x").is_err());
+}
+
+#[cfg(all(target_os = "linux",feature = "linux_platform"))]
+#[test]
+fn xkb_keyboard_decoding() {
+    use crate::backends::linux::XkbKeyboard;
+
+    let descriptor = KeymapDescriptor::from_layouts("us,de").with_variants("dvorak,").with_options("ctrl:nocaps");
+    let mut keyboard = XkbKeyboard::from_descriptor(&descriptor).unwrap();
+    assert_eq!(keyboard.num_layouts(),2);
+    // The variant and the options are part of the keymap: KEY_Q is an apostrophe on dvorak and KEY_CAPSLOCK a control.
+    assert_eq!(keyboard.key_sym(16),0x27);
+    assert_eq!(keyboard.key_sym(58),0xffe3);
+
+    assert!(keyboard.update_key(42,State::Down));
+    assert_eq!(keyboard.key_sym(16),0x22);
+    assert!(keyboard.update_key(42,State::Up));
+
+    keyboard.lock_group(1).unwrap();
+    assert_eq!(keyboard.group(),1);
+    assert_eq!(keyboard.key_sym(21),0x7a);
+    assert_eq!(keyboard.lock_group(2),Err(KeyboardLayoutError::InvalidGroup));
+
+    keyboard.update_mask(0,0,0,0,0,0);
+    assert_eq!(keyboard.group(),0);
+    assert_eq!(XkbKeyboard::from_descriptor(&KeymapDescriptor::from_layouts("nonexistent")).err(),Some(KeyboardLayoutError::InvalidKeymap));
+}
+
+#[cfg(all(target_os = "linux",feature = "linux_platform"))]
+#[test]
+fn xkb_modifier_mapping() {
+    use crate::backends::linux::XkbKeyboard;
+
+    let mut keyboard = XkbKeyboard::from_descriptor(&KeymapDescriptor::from_layouts("us")).unwrap();
+    assert_eq!(keyboard.modifiers(0x01 | 0x04),Modifiers::SHIFT | Modifiers::CONTROL);
+    assert_eq!(keyboard.modifiers(0x08),Modifiers::ALT);
+    assert_eq!(keyboard.modifiers(0x10),Modifiers::NUM_LOCK);
+    assert_eq!(keyboard.modifiers(0x40),Modifiers::SUPER);
+    assert_eq!(keyboard.modifiers(0x80),Modifiers::ALT_GRAPH);
+
+    keyboard.update_key(125,State::Down);
+    match keyboard.modifiers_event() {
+        KeyboardEvent::Modifiers{depressed,latched,locked,group}=>{
+            assert_eq!(depressed,Modifiers::SUPER);
+            assert_eq!((latched,locked,group),(Modifiers::empty(),Modifiers::empty(),0));
+        }
+        event=>panic!("Unexpected event: {:?}",event)
+    }
+}
+
+#[cfg(all(target_os = "linux",feature = "linux_platform"))]
+#[test]
+fn xkb_keymap_text() {
+    use xkbcommon::xkb;
+    use crate::backends::linux::XkbKeyboard;
+
+    let descriptor = KeymapDescriptor::default();
+    let keymap = XkbKeyboard::from_descriptor(&descriptor).unwrap().keymap(descriptor.clone());
+    assert_eq!(keymap.descriptor,descriptor);
+    assert!(keymap.text.starts_with("xkb_keymap {"));
+
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    let reloaded = xkb::Keymap::new_from_string(&context,keymap.text.clone(),xkb::KEYMAP_FORMAT_TEXT_V1,xkb::KEYMAP_COMPILE_NO_FLAGS).unwrap();
+    assert_eq!(reloaded.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1),keymap.text);
+    assert_eq!(XkbKeyboard::from_keymap(reloaded).key_sym(30),0x61);
+}
+
+#[cfg(all(target_os = "linux",feature = "xcb_platform"))]
+#[test]
+fn xim_stub_server() {
+    use crate::backends::linux::{XimProtocol,XimEvent};
+    use xim_parser::{attrs,Attribute,CommitData,Feedback,ForwardEventFlag,InputStyle,InputStyleList,Point,PreeditDrawStatus,Request,XEvent};
+
+    /// Input method server composing "にほ" with the first two keys, committing "日本" with the third one and giving back the others.
+    #[derive(Default)]
+    struct StubServer {
+        requests: Vec<Request>,
+        spot: Option<Point>,
+        keys: usize
+    }
+    impl StubServer {
+        fn reply(&mut self, request: Request) -> Vec<Request> {
+            self.requests.push(request.clone());
+            let (input_method_id,input_context_id) = (1,2);
+            match request {
+                Request::Connect{..}=>vec![Request::ConnectReply{server_major_protocol_version: 1,server_minor_protocol_version: 0}],
+                Request::Open{..}=>vec![Request::OpenReply{
+                    input_method_id,
+                    im_attrs: vec![attrs::QUERY_INPUT_STYLE],
+                    ic_attrs: vec![attrs::INPUT_STYLE,attrs::CLIENTWIN,attrs::FOCUSWIN,attrs::PREEDIT_ATTRIBUTES,attrs::SPOT_LOCATION]
+                }],
+                Request::EncodingNegotiation{..}=>vec![Request::EncodingNegotiationReply{input_method_id,category: 0,index: 0}],
+                Request::GetImValues{..}=>{
+                    let styles = vec![InputStyle::PREEDIT_POSITION | InputStyle::STATUS_NOTHING,InputStyle::PREEDIT_CALLBACKS | InputStyle::STATUS_NOTHING];
+                    let value = xim_parser::write_to_vec(InputStyleList{styles});
+                    vec![Request::GetImValuesReply{input_method_id,im_attributes: vec![Attribute{id: attrs::QUERY_INPUT_STYLE.id,value}]}]
                 }
-                Event::Surface{ time: _, id, event: SurfaceEvent::Removed }=>{
-                    surfaces.remove(&id);
-                    if surfaces.is_empty() {break 'main_loop;}
+                Request::CreateIc{..}=>vec![
+                    Request::CreateIcReply{input_method_id,input_context_id},
+                    Request::SetEventMask{input_method_id,input_context_id,forward_event_mask: 1,synchronous_event_mask: 0}
+                ],
+                Request::SetIcValues{ic_attributes,..}=>{
+                    let nested = ic_attributes.iter().find(|attribute|attribute.id == attrs::PREEDIT_ATTRIBUTES.id).unwrap();
+                    let spot: Attribute = xim_parser::read(&nested.value).unwrap();
+                    assert_eq!(spot.id,attrs::SPOT_LOCATION.id);
+                    self.spot = Some(xim_parser::read(&spot.value).unwrap());
+                    vec![Request::SetIcValuesReply{input_method_id,input_context_id}]
                 }
-                Event::Seat{ time: _, id, event: SeatEvent::Cursor(CursorEvent::Entered{..}) }=>{
-                    platform.request(vec![Request::Seat {
-                        request: SeatRequest::Cursor(CursorRequest::ChangeImage(CursorImage::Hidden))
-                    }]);
+                Request::ForwardEvent{xev,..}=>{
+                    self.keys += 1;
+                    let draw = |caret: i32,chg_first: i32,text: &str|Request::PreeditDraw{
+                        input_method_id,
+                        input_context_id,
+                        caret,
+                        chg_first,
+                        chg_length: 0,
+                        status: PreeditDrawStatus::empty(),
+                        preedit_string: xim_ctext::utf8_to_compound_text(text),
+                        feedbacks: vec![Feedback::UNDERLINE]
+                    };
+                    match self.keys {
+                        1=>vec![Request::PreeditStart{input_method_id,input_context_id},draw(1,0,"に")],
+                        2=>vec![draw(2,1,"ほ")],
+                        3=>vec![
+                            Request::Commit{input_method_id,input_context_id,data: CommitData::Chars{commited: xim_ctext::utf8_to_compound_text("日本"),syncronous: false}},
+                            Request::PreeditDone{input_method_id,input_context_id}
+                        ],
+                        _=>vec![Request::ForwardEvent{input_method_id,input_context_id,flag: ForwardEventFlag::empty(),serial_number: xev.sequence,xev}]
+                    }
                 }
-                Event::Seat{ time: _, id, event: SeatEvent::Cursor(CursorEvent::Left{..}) }=>{
-                    platform.request(vec![Request::Seat {
-                        request: SeatRequest::Cursor(CursorRequest::ChangeImage(CursorImage::Default))
-                    }]);
+                _=>Vec::new()
+            }
+        }
+
+        /// Exchange the messages with the client until it has nothing left to send.
+        fn exchange(&mut self, client: &mut XimProtocol) {
+            loop {
+                let messages = client.take_messages();
+                if messages.is_empty() {return;}
+                for message in messages {
+                    let request: Request = xim_parser::read(&message).unwrap();
+                    for reply in self.reply(request) {
+                        client.handle_message(&xim_parser::write_to_vec(reply)).unwrap();
+                    }
                 }
-                _=>{}
             }
         }
+    }
+
+    let window = 7;
+    let mut server = StubServer::default();
+    let mut client = XimProtocol::new(String::from("ja_JP"));
+    client.set_enabled(window,true);
+    client.set_focus(window,true);
+    server.exchange(&mut client);
+    assert!(server.requests.contains(&Request::Open{locale: String::from("ja_JP")}));
+    let input_style = server.requests.iter().find_map(|request|match request {
+        Request::CreateIc{ic_attributes,..}=>ic_attributes.iter().find(|attribute|attribute.id == attrs::INPUT_STYLE.id),
+        _=>None
+    }).map(|attribute|xim_parser::read::<u32>(&attribute.value).unwrap());
+    assert_eq!(input_style,Some((InputStyle::PREEDIT_CALLBACKS | InputStyle::STATUS_NOTHING).bits()));
+    assert_eq!(server.requests.last(),Some(&Request::SetIcFocus{input_method_id: 1,input_context_id: 2}));
 
+    // The spot location is the bottom left corner of the cursor area.
+    client.set_cursor_area(window,&Rectangle{position: Position2D::from((10,20)),size: Size2D::from((2,16))});
+    server.exchange(&mut client);
+    assert_eq!(server.spot,Some(Point{x: 10,y: 36}));
+
+    let key = |response_type: u8,detail: u8|XEvent {
+        response_type,
+        detail,
+        sequence: 0,
+        time: 0,
+        root: 1,
+        event: window,
+        child: 0,
+        root_x: 0,
+        root_y: 0,
+        event_x: 0,
+        event_y: 0,
+        state: 0,
+        same_screen: true
+    };
+    for detail in [46,43,65,36].iter() {
+        assert!(client.filter_key(key(2,*detail)));
+        server.exchange(&mut client);
     }
+    // Only the presses are asked for by the server.
+    assert!(!client.filter_key(key(3,36)));
+    assert_eq!(client.take_events(),vec![
+        XimEvent::Preedit{text: String::from("に"),cursor: Some((3,3))},
+        XimEvent::Preedit{text: String::from("にほ"),cursor: Some((6,6))},
+        XimEvent::Commit{text: String::from("日本")},
+        XimEvent::Key(key(2,36)),
+    ]);
+
+    client.set_enabled(window,false);
+    server.exchange(&mut client);
+    assert_eq!(server.requests.last(),Some(&Request::DestroyIc{input_method_id: 1,input_context_id: 2}));
 }